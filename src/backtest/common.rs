@@ -1,12 +1,25 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 
 use chrono::{DateTime, Utc};
 use rayon::prelude::*;
+use serde::Deserialize;
 
 use crate::{
-    data::Sample,
-    indicators::{AtrFilter, RegimeFilter, sma::SmaConfig},
-    signal::{BreakoutConfig, FilterConfig, PullbackConfig, StrategyConfig},
+    data::{DataError, Sample},
+    indicators::{AtrFilter, RegimeFilter, RsiFilter, sma::SmaConfig},
+    signal::{
+        AnalysisResult, BollingerConfig, BreakoutConfig, BreakoutTrigger, FilterConfig, MacdConfig,
+        PullbackConfig, PullbackToleranceMode, RetestConfig, RoundNumberFilter, StrategyConfig,
+        VolumeFilter,
+    },
+    source::{PriceSource, SliceSource},
+    stats::stddev,
+    units::Pct,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -35,20 +48,43 @@ impl AtrFilter {
     }
 }
 
-pub fn compute_max_drawdown(curve: &[(DateTime<Utc>, f64)]) -> f64 {
+/// Round `qty` down to a whole number of `step` lots (unchanged without a step).
+pub(crate) fn round_down_to_step(qty: f64, step: Option<f64>) -> f64 {
+    match step {
+        Some(step) if step > 0.0 => (qty / step).floor() * step,
+        _ => qty,
+    }
+}
+
+/// How drawdown is measured on an equity curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrawdownKind {
+    /// Largest fall from the running peak.
+    #[default]
+    PeakToTrough,
+    /// Largest fall below the starting equity (0 if it never goes below).
+    FromInitial,
+}
+
+pub fn compute_drawdown(curve: &[(DateTime<Utc>, f64)], kind: DrawdownKind) -> f64 {
     if curve.is_empty() {
         return 0.0;
     }
 
-    let mut peak = curve[0].1;
+    let initial = curve[0].1;
+    let mut peak = initial;
     let mut max_dd = 0.0;
 
     for &(_, equity) in curve {
         if equity > peak {
             peak = equity;
         }
-        if peak > 0.0 {
-            let dd = (peak - equity) / peak;
+        let reference = match kind {
+            DrawdownKind::PeakToTrough => peak,
+            DrawdownKind::FromInitial => initial,
+        };
+        if reference > 0.0 {
+            let dd = (reference - equity) / reference;
             if dd > max_dd {
                 max_dd = dd;
             }
@@ -58,25 +94,214 @@ pub fn compute_max_drawdown(curve: &[(DateTime<Utc>, f64)]) -> f64 {
     max_dd
 }
 
+pub fn compute_max_drawdown(curve: &[(DateTime<Utc>, f64)]) -> f64 {
+    compute_drawdown(curve, DrawdownKind::PeakToTrough)
+}
+
+/// Max drawdown on log-equity: largest `ln(peak) - ln(equity)`, so every drop is
+/// measured by the same log-ratio no matter how far the curve has compounded.
+/// Non-positive equity is skipped.
+pub fn compute_max_log_drawdown(curve: &[(DateTime<Utc>, f64)]) -> f64 {
+    let mut peak = f64::NEG_INFINITY;
+    let mut max_dd = 0.0;
+
+    for &(_, equity) in curve.iter().filter(|(_, equity)| *equity > 0.0) {
+        let log_equity = equity.ln();
+        peak = peak.max(log_equity);
+        max_dd = f64::max(max_dd, peak - log_equity);
+    }
+
+    max_dd
+}
+
+/// Candles per year used to annualize per-candle returns (hourly candles).
+const CANDLES_PER_YEAR: f64 = 24.0 * 365.0;
+
+/// Annualized Sharpe ratio (risk-free rate 0) from the per-step returns of an equity curve.
+/// A flat curve (zero stddev) yields 0.
+pub fn compute_sharpe(curve: &[(DateTime<Utc>, f64)]) -> f64 {
+    let returns: Vec<f64> = curve
+        .windows(2)
+        .filter(|w| w[0].1 > 0.0)
+        .map(|w| w[1].1 / w[0].1 - 1.0)
+        .collect();
+
+    let Some(std) = stddev(&returns, 1) else {
+        return 0.0;
+    };
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+
+    mean / std * CANDLES_PER_YEAR.sqrt()
+}
+
+/// Compound annual growth rate over the curve's calendar span.
+/// 0 with fewer than two points or no time between the first and last one.
+pub fn compute_cagr(curve: &[(DateTime<Utc>, f64)]) -> f64 {
+    let (Some(first), Some(last)) = (curve.first(), curve.last()) else {
+        return 0.0;
+    };
+    let years = (last.0 - first.0).num_seconds() as f64 / (365.0 * 24.0 * 3600.0);
+    if first.1 <= 0.0 || years <= 0.0 {
+        return 0.0;
+    }
+
+    (last.1 / first.1).max(0.0).powf(1.0 / years) - 1.0
+}
+
+/// (profit factor, average win, average loss) over closed trades' profits.
+/// Profit factor is gross profit over gross loss: infinite with wins but no losses,
+/// 0 without wins. The average loss is a positive amount; both averages are 0 when
+/// there's nothing to average.
+pub fn compute_profit_factor(profits: &[f64]) -> (f64, f64, f64) {
+    let wins: Vec<f64> = profits.iter().copied().filter(|&p| p > 0.0).collect();
+    let losses: Vec<f64> = profits.iter().filter(|&&p| p < 0.0).map(|p| -p).collect();
+    let mean = |xs: &[f64]| {
+        if xs.is_empty() {
+            0.0
+        } else {
+            xs.iter().sum::<f64>() / xs.len() as f64
+        }
+    };
+
+    let gross_profit: f64 = wins.iter().sum();
+    let gross_loss: f64 = losses.iter().sum();
+    let profit_factor = if gross_loss > 0.0 {
+        gross_profit / gross_loss
+    } else if gross_profit > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    };
+
+    (profit_factor, mean(&wins), mean(&losses))
+}
+
+/// Outcomes in R-multiples: profit over the risk taken at entry (entry to stop).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RMultipleStats {
+    /// Trades that had a stop, and so an R-multiple.
+    pub trades: usize,
+    /// Mean R of winning and losing trades (the loss as a positive amount).
+    pub avg_win_r: f64,
+    pub avg_loss_r: f64,
+    /// Mean R over all of them: what a trade is expected to make per unit of risk.
+    pub expectancy_r: f64,
+}
+
+impl RMultipleStats {
+    /// None when no trade had a stop.
+    pub fn from_r_multiples(r_multiples: &[f64]) -> Option<Self> {
+        if r_multiples.is_empty() {
+            return None;
+        }
+        let (_, avg_win_r, avg_loss_r) = compute_profit_factor(r_multiples);
+        Some(Self {
+            trades: r_multiples.len(),
+            avg_win_r,
+            avg_loss_r,
+            expectancy_r: r_multiples.iter().sum::<f64>() / r_multiples.len() as f64,
+        })
+    }
+}
+
+/// Risk/return summary of an equity curve, used for both strategy and benchmark.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquityMetrics {
+    pub total_return_pct: f64,
+    pub max_drawdown_pct: f64,
+    pub sharpe: f64,
+    pub cagr_pct: f64,
+}
+
+impl EquityMetrics {
+    pub fn from_curve(curve: &[(DateTime<Utc>, f64)]) -> Self {
+        let total_return_pct = match (curve.first(), curve.last()) {
+            (Some(first), Some(last)) if first.1 > 0.0 => last.1 / first.1 - 1.0,
+            _ => 0.0,
+        };
+
+        Self {
+            total_return_pct,
+            max_drawdown_pct: compute_max_drawdown(curve),
+            sharpe: compute_sharpe(curve),
+            cagr_pct: compute_cagr(curve),
+        }
+    }
+}
+
+/// Equity curve of holding `qty` coins over `samples`.
+pub(crate) fn holding_curve(samples: &[Sample], qty: f64) -> Vec<(DateTime<Utc>, f64)> {
+    samples.iter().map(|s| (s.ts, qty * s.price)).collect()
+}
+
+/// Print the R-multiple summary lines; nothing when no trade had a stop.
+pub(crate) fn print_r_stats(out: &mut impl Write, stats: Option<RMultipleStats>) -> io::Result<()> {
+    let Some(stats) = stats else {
+        return Ok(());
+    };
+    writeln!(
+        out,
+        "Expectancy:       {:.2}R over {} trades with a stop",
+        stats.expectancy_r, stats.trades
+    )?;
+    writeln!(
+        out,
+        "Avg win/loss R:   {:.2}R / {:.2}R",
+        stats.avg_win_r, stats.avg_loss_r
+    )
+}
+
+/// Print strategy and benchmark metrics side by side.
+pub(crate) fn print_benchmark_comparison(
+    out: &mut impl Write,
+    strategy: &EquityMetrics,
+    benchmark: &EquityMetrics,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "                  {:>10} {:>10}",
+        "Strategy", "Buy&hold"
+    )?;
+    writeln!(
+        out,
+        "Total return:     {:>#10} {:>#10}",
+        Pct(strategy.total_return_pct),
+        Pct(benchmark.total_return_pct)
+    )?;
+    writeln!(
+        out,
+        "Max drawdown:     {:>#10} {:>#10}",
+        Pct(strategy.max_drawdown_pct),
+        Pct(benchmark.max_drawdown_pct)
+    )?;
+    writeln!(
+        out,
+        "Sharpe:           {:>10.2} {:>10.2}",
+        strategy.sharpe, benchmark.sharpe
+    )?;
+    writeln!(
+        out,
+        "CAGR:             {:>#10} {:>#10}",
+        Pct(strategy.cagr_pct),
+        Pct(benchmark.cagr_pct)
+    )?;
+    Ok(())
+}
+
 pub fn generate_strategies(
     min_lookback: usize,
     max_lookback: usize,
     pullback_pairs: Vec<(f64, f64)>,
 ) -> Vec<StrategyConfig> {
-    let mut strategies = Vec::new();
-
     let short_candidates = [10, 20, 30];
     let long_candidates = [40, 60, 80, 100];
 
-    let sma_configs: Vec<SmaConfig> = short_candidates
+    let sma_windows: Vec<(usize, usize)> = short_candidates
         .iter()
         .flat_map(|&short| {
             long_candidates.iter().filter_map(move |&long| {
                 if long >= short * 2 {
-                    Some(SmaConfig {
-                        short_window: short,
-                        long_window: long,
-                    })
+                    Some((short, long))
                 } else {
                     None
                 }
@@ -84,6 +309,38 @@ pub fn generate_strategies(
         })
         .collect();
 
+    // Every pair above has long >= 2 * short, so nothing is skipped
+    generate_strategies_for_windows(
+        &sma_windows,
+        min_lookback,
+        max_lookback,
+        pullback_pairs,
+        &mut io::sink(),
+    )
+    .expect("writing to a sink can't fail")
+}
+
+/// Same grid as `generate_strategies`, over explicit (short, long) SMA window pairs.
+/// Pairs with `long <= short` can't produce meaningful crossovers and are skipped with a
+/// warning written to `warnings`.
+pub fn generate_strategies_for_windows(
+    sma_windows: &[(usize, usize)],
+    min_lookback: usize,
+    max_lookback: usize,
+    pullback_pairs: Vec<(f64, f64)>,
+    warnings: &mut impl Write,
+) -> io::Result<Vec<StrategyConfig>> {
+    let mut strategies = Vec::new();
+
+    let mut sma_configs = Vec::new();
+    for &(short, long) in sma_windows {
+        let sma_config = SmaConfig::new(short, long);
+        match sma_config.validate() {
+            Ok(()) => sma_configs.push(sma_config),
+            Err(err) => writeln!(warnings, "Warning: skipping SMA{short}/{long}: {err}")?,
+        }
+    }
+
     for sma_config in sma_configs {
         // bit 0: breakouts
         // bit 1: pullbacks
@@ -104,19 +361,25 @@ pub fn generate_strategies(
                             let strategy = StrategyConfig {
                                 breakouts: Some(BreakoutConfig {
                                     breakout_lookback: lookback,
+                                    min_trend_bars: 0,
+                                    require_retest: None,
+                                    breakout_trigger: BreakoutTrigger::Close,
                                 }),
                                 pullbacks: Some(PullbackConfig {
                                     bounce_tolerance_pct: *pullback_bounce_tol,
                                     reject_tolerance_pct: *pullback_rejection_tol,
+                                    tolerance_mode: PullbackToleranceMode::Percent,
                                 }),
                                 enable_crossovers,
                                 enable_bias_only,
-                                sma_config,
+                                enable_macd: None,
+                                rule_order: Vec::new(),
+                                bollinger: None,
+                                sma_config: sma_config.clone(),
                                 filters: FilterConfig {
-                                    atr: None,
-                                    regime: None,
                                     require_price_confirmation: true,
                                     require_trend_filter: true,
+                                    ..Default::default()
                                 },
                             };
 
@@ -129,16 +392,21 @@ pub fn generate_strategies(
                         let strategy = StrategyConfig {
                             breakouts: Some(BreakoutConfig {
                                 breakout_lookback: lookback,
+                                min_trend_bars: 0,
+                                require_retest: None,
+                                breakout_trigger: BreakoutTrigger::Close,
                             }),
                             pullbacks: None,
                             enable_crossovers,
                             enable_bias_only,
-                            sma_config,
+                            enable_macd: None,
+                            rule_order: Vec::new(),
+                            bollinger: None,
+                            sma_config: sma_config.clone(),
                             filters: FilterConfig {
-                                atr: None,
-                                regime: None,
                                 require_price_confirmation: true,
                                 require_trend_filter: true,
+                                ..Default::default()
                             },
                         };
 
@@ -152,15 +420,18 @@ pub fn generate_strategies(
                             pullbacks: Some(PullbackConfig {
                                 bounce_tolerance_pct: *pullback_bounce_tol,
                                 reject_tolerance_pct: *pullback_rejection_tol,
+                                tolerance_mode: PullbackToleranceMode::Percent,
                             }),
                             enable_crossovers,
                             enable_bias_only,
-                            sma_config,
+                            enable_macd: None,
+                            rule_order: Vec::new(),
+                            bollinger: None,
+                            sma_config: sma_config.clone(),
                             filters: FilterConfig {
-                                atr: None,
-                                regime: None,
                                 require_price_confirmation: true,
                                 require_trend_filter: true,
+                                ..Default::default()
                             },
                         };
 
@@ -177,12 +448,14 @@ pub fn generate_strategies(
                         pullbacks: None,
                         enable_crossovers,
                         enable_bias_only,
-                        sma_config,
+                        enable_macd: None,
+                        rule_order: Vec::new(),
+                        bollinger: None,
+                        sma_config: sma_config.clone(),
                         filters: FilterConfig {
-                            atr: None,
-                            regime: None,
                             require_price_confirmation: true,
                             require_trend_filter: true,
+                            ..Default::default()
                         },
                     };
 
@@ -192,7 +465,138 @@ pub fn generate_strategies(
         }
     }
 
-    strategies
+    Ok(strategies)
+}
+
+/// Inclusive parameter ranges `generate_random_strategies` samples from.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StrategyRanges {
+    pub short_window: (usize, usize),
+    pub long_window: (usize, usize),
+    pub breakout_lookback: (usize, usize),
+    /// Pullback tolerances; the rejection tolerance is drawn above the bounce one.
+    pub pullback_pct: (f64, f64),
+}
+
+/// `k` random configurations from `ranges`, the same ones for the same `seed`.
+/// Rules are switched on and off like in the grid (bias-only always on, at least
+/// one other rule enabled), and SMA pairs failing `validate()` are redrawn. Gives
+/// up with a warning written to `warnings` if the ranges hardly ever produce a valid pair.
+pub fn generate_random_strategies(
+    ranges: &StrategyRanges,
+    k: usize,
+    seed: u64,
+    warnings: &mut impl Write,
+) -> io::Result<Vec<StrategyConfig>> {
+    let mut rng = SplitMix64::new(seed);
+    let mut strategies = Vec::with_capacity(k);
+    let max_attempts = k.saturating_mul(100);
+    let mut attempts = 0;
+
+    while strategies.len() < k {
+        if attempts == max_attempts {
+            writeln!(
+                warnings,
+                "Warning: only {} of {} random strategies are valid for these ranges",
+                strategies.len(),
+                k
+            )?;
+            break;
+        }
+        attempts += 1;
+
+        let sma_config = SmaConfig::new(
+            rng.usize_in(ranges.short_window),
+            rng.usize_in(ranges.long_window),
+        );
+        if sma_config.validate().is_err() {
+            continue;
+        }
+        // Same rule switches as the grid's mask, minus the empty combination
+        let mask = rng.usize_in((1, 7));
+        let breakouts = (mask & 0b001 != 0).then(|| BreakoutConfig {
+            breakout_lookback: rng.usize_in(ranges.breakout_lookback),
+            min_trend_bars: 0,
+            require_retest: None,
+            breakout_trigger: BreakoutTrigger::Close,
+        });
+        let pullbacks = (mask & 0b010 != 0).then(|| {
+            let (min, max) = ranges.pullback_pct;
+            let bounce = rng.f64_in(min, max);
+            PullbackConfig {
+                bounce_tolerance_pct: bounce,
+                reject_tolerance_pct: rng.f64_in(bounce, max),
+                tolerance_mode: PullbackToleranceMode::Percent,
+            }
+        });
+
+        strategies.push(StrategyConfig {
+            breakouts,
+            pullbacks,
+            enable_crossovers: mask & 0b100 != 0,
+            enable_bias_only: true,
+            enable_macd: None,
+            rule_order: Vec::new(),
+            bollinger: None,
+            sma_config,
+            filters: FilterConfig {
+                require_price_confirmation: true,
+                require_trend_filter: true,
+                ..Default::default()
+            },
+        });
+    }
+
+    Ok(strategies)
+}
+
+/// Small seedable PRNG (SplitMix64), enough for reproducible parameter sampling.
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[lo, hi]`; `hi < lo` yields `lo`.
+    pub(crate) fn usize_in(&mut self, (lo, hi): (usize, usize)) -> usize {
+        let span = hi.saturating_sub(lo) as u64 + 1;
+        lo + (self.next_u64() % span) as usize
+    }
+
+    /// Uniform in `[lo, hi)`.
+    pub(crate) fn f64_in(&mut self, lo: f64, hi: f64) -> f64 {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        lo + unit * (hi - lo)
+    }
+}
+
+/// Plain SMA crossover without breakouts, pullbacks or bias signals, e.g. as a
+/// sweep benchmark.
+pub fn crossover_strategy(sma_config: SmaConfig) -> StrategyConfig {
+    StrategyConfig {
+        breakouts: None,
+        pullbacks: None,
+        enable_crossovers: true,
+        enable_bias_only: false,
+        enable_macd: None,
+        rule_order: Vec::new(),
+        bollinger: None,
+        sma_config,
+        filters: FilterConfig {
+            require_price_confirmation: true,
+            require_trend_filter: true,
+            ..Default::default()
+        },
+    }
 }
 
 pub fn generate_pullback_pairs(min: f64, max: f64, step: f64) -> Vec<(f64, f64)> {
@@ -215,97 +619,1969 @@ pub fn generate_backtest_sweep_jobs(
 ) -> Vec<(StrategyConfig, usize)> {
     strategies
         .iter()
-        .flat_map(|&strategy| {
-            (1..=buy_sell_frac_steps).map(move |buy_sell_frac_step| (strategy, buy_sell_frac_step))
+        .flat_map(|strategy| {
+            (1..=buy_sell_frac_steps)
+                .map(move |buy_sell_frac_step| (strategy.clone(), buy_sell_frac_step))
         })
         .collect()
 }
 
+#[derive(Debug, Clone)]
 pub struct Candidate {
     pub buy_sell_fraction: f64,
     pub strategy: StrategyConfig,
+    /// Protective stop for each position, placed at entry and held until exit.
+    /// Only the position backtester honors it.
+    pub stop: Option<StopPlacement>,
+}
+
+impl Candidate {
+    /// Stable order between candidates whose results tie: the simpler one (shorter SMA
+    /// long window, then shorter breakout lookback, then shorter SMA short window) and
+    /// then the smaller buy/sell fraction comes first.
+    pub fn tie_break_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let key = |c: &Self| {
+            (
+                c.strategy.sma_config.long_window,
+                c.strategy.breakouts.as_ref().map(|b| b.breakout_lookback),
+                c.strategy.sma_config.short_window,
+            )
+        };
+        key(self)
+            .cmp(&key(other))
+            .then(self.buy_sell_fraction.total_cmp(&other.buy_sell_fraction))
+    }
+
+    /// Stable 64-bit hash of every parameter, for deduping candidates and keying caches
+    /// and checkpoints. FNV-1a over the `name=value` lines of `canonical_params` rather
+    /// than the std hasher, so it's the same across runs, builds and platforms.
+    pub fn fingerprint(&self) -> u64 {
+        self.canonical_params()
+            .iter()
+            .flat_map(|(name, value)| [name.as_bytes(), b"=", value.as_bytes(), b"\n"])
+            .flatten()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+            })
+    }
+
+    /// Every parameter by name, sorted. Floats are written as their bit pattern,
+    /// fieldless enums as their variant name and disabled options as "none". Each
+    /// config is destructured in full, so a new field won't compile until it's
+    /// listed here.
+    pub fn canonical_params(&self) -> BTreeMap<String, String> {
+        let mut params = BTreeMap::new();
+        let mut put = |name: &str, value: String| {
+            params.insert(name.to_string(), value);
+        };
+        let float = |value: f64| format!("{:#018x}", value.to_bits());
+        let none = || "none".to_string();
+
+        let Candidate {
+            buy_sell_fraction,
+            strategy,
+            stop,
+        } = self;
+        put("buy_sell_fraction", float(*buy_sell_fraction));
+        match stop {
+            Some(StopPlacement::Percent(pct)) => put("stop.percent", float(*pct)),
+            Some(StopPlacement::SwingLookback(lookback)) => {
+                put("stop.swing_lookback", lookback.to_string())
+            }
+            None => put("stop", none()),
+        }
+
+        let StrategyConfig {
+            breakouts,
+            pullbacks,
+            enable_crossovers,
+            enable_bias_only,
+            enable_macd,
+            bollinger,
+            rule_order,
+            sma_config,
+            filters,
+        } = strategy;
+        match breakouts {
+            Some(BreakoutConfig {
+                breakout_lookback,
+                min_trend_bars,
+                require_retest,
+                breakout_trigger,
+            }) => {
+                put("breakouts.lookback", breakout_lookback.to_string());
+                put("breakouts.min_trend_bars", min_trend_bars.to_string());
+                put("breakouts.trigger", format!("{breakout_trigger:?}"));
+                match require_retest {
+                    Some(RetestConfig {
+                        lookahead,
+                        tolerance_pct,
+                    }) => {
+                        put("breakouts.retest.lookahead", lookahead.to_string());
+                        put("breakouts.retest.tolerance_pct", float(*tolerance_pct));
+                    }
+                    None => put("breakouts.retest", none()),
+                }
+            }
+            None => put("breakouts", none()),
+        }
+        match pullbacks {
+            Some(PullbackConfig {
+                bounce_tolerance_pct,
+                reject_tolerance_pct,
+                tolerance_mode,
+            }) => {
+                put("pullbacks.bounce_tolerance", float(*bounce_tolerance_pct));
+                put("pullbacks.reject_tolerance", float(*reject_tolerance_pct));
+                put("pullbacks.tolerance_mode", format!("{tolerance_mode:?}"));
+            }
+            None => put("pullbacks", none()),
+        }
+        put("crossovers", enable_crossovers.to_string());
+        put("bias_only", enable_bias_only.to_string());
+        match enable_macd {
+            Some(MacdConfig { fast, slow, signal }) => {
+                put("macd.fast", fast.to_string());
+                put("macd.slow", slow.to_string());
+                put("macd.signal", signal.to_string());
+            }
+            None => put("macd", none()),
+        }
+        match bollinger {
+            Some(BollingerConfig { period, num_std }) => {
+                put("bollinger.period", period.to_string());
+                put("bollinger.num_std", float(*num_std));
+            }
+            None => put("bollinger", none()),
+        }
+        let rule_order: Vec<String> = rule_order.iter().map(|kind| format!("{kind:?}")).collect();
+        put("rule_order", rule_order.join(","));
+
+        let SmaConfig {
+            short_window,
+            long_window,
+            stack_windows,
+            eq_epsilon,
+            min_separation_pct,
+        } = sma_config;
+        put("sma.short_window", short_window.to_string());
+        put("sma.long_window", long_window.to_string());
+        let stack_windows: Vec<String> = stack_windows.iter().map(usize::to_string).collect();
+        put("sma.stack_windows", stack_windows.join(","));
+        put("sma.eq_epsilon", float(*eq_epsilon));
+        put("sma.min_separation_pct", float(*min_separation_pct));
+
+        let FilterConfig {
+            require_trend_filter,
+            trend_source,
+            require_price_confirmation,
+            require_ma_stack,
+            atr,
+            rsi,
+            volume,
+            regime,
+            round_number,
+            min_signal_atr_mult,
+            max_candle_gap,
+        } = filters;
+        put("filters.trend", require_trend_filter.to_string());
+        put("filters.trend_source", format!("{trend_source:?}"));
+        put(
+            "filters.price_confirmation",
+            require_price_confirmation.to_string(),
+        );
+        put("filters.ma_stack", require_ma_stack.to_string());
+        match atr {
+            Some(atr) => {
+                put("filters.atr.period", atr.period().to_string());
+                put("filters.atr.floor", float(atr.floor()));
+            }
+            None => put("filters.atr", none()),
+        }
+        match rsi {
+            Some(RsiFilter {
+                period,
+                overbought,
+                oversold,
+            }) => {
+                put("filters.rsi.period", period.to_string());
+                put("filters.rsi.overbought", float(*overbought));
+                put("filters.rsi.oversold", float(*oversold));
+            }
+            None => put("filters.rsi", none()),
+        }
+        match volume {
+            Some(VolumeFilter {
+                lookback,
+                min_ratio,
+            }) => {
+                put("filters.volume.lookback", lookback.to_string());
+                put("filters.volume.min_ratio", float(*min_ratio));
+            }
+            None => put("filters.volume", none()),
+        }
+        match regime {
+            Some(RegimeFilter {
+                long_window,
+                slope_window,
+                min_trend_strength,
+                min_range,
+                smooth_slope,
+            }) => {
+                put("filters.regime.long_window", long_window.to_string());
+                put("filters.regime.slope_window", slope_window.to_string());
+                put(
+                    "filters.regime.min_trend_strength",
+                    float(*min_trend_strength),
+                );
+                put("filters.regime.min_range", float(*min_range));
+                put("filters.regime.smooth_slope", smooth_slope.to_string());
+            }
+            None => put("filters.regime", none()),
+        }
+        match round_number {
+            Some(RoundNumberFilter { grid, band_pct }) => {
+                put("filters.round_number.grid", float(*grid));
+                put("filters.round_number.band_pct", float(*band_pct));
+            }
+            None => put("filters.round_number", none()),
+        }
+        put(
+            "filters.min_signal_atr_mult",
+            min_signal_atr_mult.map_or_else(none, float),
+        );
+        put(
+            "filters.max_candle_gap_secs",
+            max_candle_gap.map_or_else(none, |gap| gap.num_seconds().to_string()),
+        );
+        params
+    }
+}
+
+/// Where a position's stop goes when it's opened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopPlacement {
+    /// This fraction away from the entry price (e.g. 0.02 = 2% below a long's entry).
+    Percent(f64),
+    /// At the lowest (long) or highest (short) close of this many candles before entry.
+    SwingLookback(usize),
+}
+
+/// Exit reason of a position (or spot holding) closed by a trailing stop.
+pub const TRAILING_STOP_EXIT_REASON: &str = "trailing-stop";
+
+/// Exit reason of a position (or spot holding) closed for reaching `max_hold_candles`.
+pub const MAX_HOLD_EXIT_REASON: &str = "max-hold";
+
+/// Metric a sweep can rank on or stop at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SweepMetric {
+    #[default]
+    TotalReturn,
+    Sharpe,
+}
+
+impl SweepMetric {
+    pub fn value<M: TradingMetrics>(&self, metrics: &M) -> f64 {
+        match self {
+            Self::TotalReturn => metrics.total_return_pct(),
+            Self::Sharpe => metrics.sharpe_ratio(),
+        }
+    }
+}
+
+/// Stop the sweep as soon as a candidate reaches `target` on `metric`.
+#[derive(Debug, Clone, Copy)]
+pub struct EarlyStop {
+    pub metric: SweepMetric,
+    pub target: f64,
+}
+
+/// A fixed strategy (e.g. a plain SMA20/50 crossover) the sweep measures candidates against.
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    pub candidate: Candidate,
+    /// Optimize the excess over the benchmark: candidates that don't beat its return
+    /// are not eligible, and the early-stop target is measured as excess.
+    pub relative: bool,
+}
+
+/// A result's metrics minus the benchmark's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Excess {
+    pub return_pct: f64,
+    pub max_drawdown_pct: f64,
+    pub trades: i64,
+}
+
+impl Excess {
+    pub fn over<M: TradingMetrics>(metrics: &M, benchmark: &M) -> Self {
+        Self {
+            return_pct: metrics.total_return_pct() - benchmark.total_return_pct(),
+            max_drawdown_pct: metrics.max_drawdown_pct() - benchmark.max_drawdown_pct(),
+            trades: metrics.trade_count() as i64 - benchmark.trade_count() as i64,
+        }
+    }
+}
+
+impl fmt::Display for Excess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:+.2}% return, {:+.2}% max drawdown, {:+} trades",
+            self.return_pct * 100.0,
+            self.max_drawdown_pct * 100.0,
+            self.trades
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SweepOptions {
+    /// Return the first qualifying candidate (in job order) instead of the best one.
+    pub early_stop: Option<EarlyStop>,
+    /// Candidates with fewer trades than this are not eligible.
+    pub min_trades: usize,
+    /// Once set (e.g. from a Ctrl-C handler), no new jobs are started and
+    /// the best result found so far is returned.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Run this strategy once and report every candidate, and return the winner,
+    /// relative to it.
+    pub benchmark: Option<Benchmark>,
+    /// What "best" maximizes; ties go to the lower drawdown.
+    pub rank_by: SweepMetric,
+}
+
+impl SweepOptions {
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+}
+
+/// What a sweep found.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum SweepOutcome<R> {
+    /// The winning candidate, its result, and how it did against the benchmark
+    /// (when the sweep ran one).
+    Best(Candidate, R, Option<Excess>),
+    /// The best candidate never traded, or no candidate traded at all, so the "best"
+    /// would just be the flat one.
+    NoTradingStrategy,
+    /// No candidate produced an eligible result.
+    NoResult,
+}
+
+impl<R> SweepOutcome<R> {
+    /// The winner, if there is one.
+    pub fn best(self) -> Option<(Candidate, R)> {
+        match self {
+            Self::Best(candidate, result, _) => Some((candidate, result)),
+            Self::NoTradingStrategy | Self::NoResult => None,
+        }
+    }
 }
 
-pub fn find_best_strategy<B, F>(
+/// Run every job and pick the best candidate. Progress, the benchmark's metrics, each
+/// candidate's excess over the benchmark and failed backtests are reported to `out`.
+pub fn find_best_strategy<B, F, W>(
     jobs: Vec<(StrategyConfig, usize)>,
     max_buy_sell_fraction: f64,
     buy_sell_frac_steps: usize,
     samples: &[Sample],
+    options: &SweepOptions,
+    out: &mut W,
     // use factory instead of restricting with Sync
     make_backtester: F,
-) -> Option<(Candidate, B::Output)>
+) -> io::Result<SweepOutcome<B::Output>>
 where
     B: Backtester,
     F: Fn() -> B + Sync + Send,
+    W: Write + Send,
 {
     const EPS: f64 = 1e-9;
 
     let total_iters = jobs.len() as u64;
     let done = AtomicU64::new(0);
     let progress_every = (total_iters / 100).max(1);
+    // Lowest job index that reached the early-stop target so far
+    let first_hit = AtomicUsize::new(usize::MAX);
+    let any_evaluated = AtomicBool::new(false);
+    let any_traded = AtomicBool::new(false);
+
+    let benchmark = match &options.benchmark {
+        Some(benchmark) => match make_backtester()
+            .run_backtest(&mut SliceSource::new(samples), &benchmark.candidate)
+        {
+            Ok(bench) => Some(bench),
+            Err(err) => {
+                writeln!(out, "Failed to run the benchmark: {}", err)?;
+                None
+            }
+        },
+        None => None,
+    };
+    if let Some(bench) = &benchmark {
+        writeln!(
+            out,
+            "Benchmark: {:.2}% return, {:.2}% max drawdown, {} trades",
+            bench.total_return_pct() * 100.0,
+            bench.max_drawdown_pct() * 100.0,
+            bench.trade_count()
+        )?;
+    }
+    // What a relative benchmark subtracts from the return and from the early-stop metric
+    let baseline = benchmark
+        .as_ref()
+        .filter(|_| options.benchmark.as_ref().is_some_and(|b| b.relative))
+        .map(|bench| {
+            (
+                bench.total_return_pct(),
+                options
+                    .early_stop
+                    .map_or(0.0, |stop| stop.metric.value(bench)),
+            )
+        });
 
-    println!(
+    writeln!(
+        out,
         "Running parameter sweep... ({} total combinations)",
         total_iters
-    );
+    )?;
+    // Shared by the workers; the first failed write is returned once they're done
+    let out = Mutex::new(out);
+    let write_error = OnceLock::new();
+    let report = |args: fmt::Arguments| {
+        let mut out = out.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(err) = writeln!(out, "{}", args) {
+            let _ = write_error.set(err);
+        }
+    };
 
-    let best_pair: Option<(Candidate, B::Output)> = jobs
+    let best_pair = jobs
         .into_par_iter()
+        .enumerate()
         .map_init(
-            || make_backtester(),
-            |backtester, (strategy, buy_sell_frac_step)| {
+            &make_backtester,
+            |backtester, (idx, (strategy, buy_sell_frac_step))| {
+                // A job later in the canonical order already qualified: this one can't win.
+                if idx > first_hit.load(Ordering::Relaxed) || options.is_cancelled() {
+                    return None;
+                }
                 let current = done.fetch_add(1, Ordering::Relaxed) + 1;
                 if progress_every != 0
                     && (current.is_multiple_of(progress_every) || current == total_iters)
                 {
                     let pct = (current as f64 / total_iters as f64) * 100.0;
-                    println!("Progress: {:6.2}% ({}/{})", pct, current, total_iters);
+                    report(format_args!(
+                        "Progress: {:6.2}% ({}/{})",
+                        pct, current, total_iters
+                    ));
                 }
                 let buy_sell_fraction = (buy_sell_frac_step as f64 / buy_sell_frac_steps as f64)
                     * max_buy_sell_fraction;
                 let candidate = Candidate {
                     buy_sell_fraction,
                     strategy,
+                    stop: None,
                 };
                 let result = backtester
-                    .run_backtest(samples, &candidate)
-                    .inspect_err(|err| println!("Failed to get backtest result: {}", err))
+                    .run_backtest(&mut SliceSource::new(samples), &candidate)
+                    .inspect_err(|err| {
+                        report(format_args!("Failed to get backtest result: {}", err))
+                    })
                     .ok()?;
-                Some((candidate, result))
+                any_evaluated.store(true, Ordering::Relaxed);
+                let excess = benchmark.as_ref().map(|bench| Excess::over(&result, bench));
+                if let Some(excess) = excess {
+                    report(format_args!(
+                        "{} (fraction {:.2}) vs benchmark: {}",
+                        candidate.strategy.describe_config(),
+                        candidate.buy_sell_fraction,
+                        excess
+                    ));
+                }
+                if result.trade_count() > 0 {
+                    any_traded.store(true, Ordering::Relaxed);
+                }
+                if result.trade_count() < options.min_trades {
+                    return None;
+                }
+                let (baseline_return, baseline_stop) = baseline.unwrap_or_default();
+                if baseline.is_some() && result.total_return_pct() <= baseline_return + EPS {
+                    return None;
+                }
+                let reached_target = options
+                    .early_stop
+                    .is_some_and(|stop| stop.metric.value(&result) - baseline_stop >= stop.target);
+                if reached_target {
+                    first_hit.fetch_min(idx, Ordering::Relaxed);
+                }
+                Some((idx, reached_target, candidate, result, excess))
             },
         )
         .filter_map(|x| x)
         .reduce_with(|res_a, res_b| {
-            let a_ret = res_a.1.total_return_pct();
-            let b_ret = res_b.1.total_return_pct();
-            let a_dd = res_a.1.max_drawdown_pct();
-            let b_dd = res_b.1.max_drawdown_pct();
-
-            // "Better" = higher total return, tie-break by lower drawdown
-            let pick_b = if b_ret > a_ret + EPS {
-                true
-            } else if (b_ret - a_ret).abs() < EPS {
-                b_dd < a_dd
-            } else {
-                false
+            // Early stop: a qualifying candidate beats any other, the earliest job wins
+            let pick_b = match (res_a.1, res_b.1) {
+                (true, true) => res_b.0 < res_a.0,
+                (false, true) => true,
+                (true, false) => false,
+                (false, false) => {
+                    let a_ret = options.rank_by.value(&res_a.3);
+                    let b_ret = options.rank_by.value(&res_b.3);
+                    let a_dd = res_a.3.max_drawdown_pct();
+                    let b_dd = res_b.3.max_drawdown_pct();
+
+                    // "Better" = higher ranking metric, tie-break by lower drawdown, then
+                    // by a stable key so that the reduction order doesn't pick the winner
+                    if b_ret > a_ret + EPS {
+                        true
+                    } else if (b_ret - a_ret).abs() < EPS {
+                        if (b_dd - a_dd).abs() < EPS {
+                            res_b
+                                .2
+                                .tie_break_cmp(&res_a.2)
+                                .then(res_b.0.cmp(&res_a.0))
+                                .is_lt()
+                        } else {
+                            b_dd < a_dd
+                        }
+                    } else {
+                        false
+                    }
+                }
             };
 
             if pick_b { res_b } else { res_a }
         });
 
-    best_pair
+    if let Some(err) = write_error.into_inner() {
+        return Err(err);
+    }
+    let out = out.into_inner().unwrap_or_else(PoisonError::into_inner);
+    if options.is_cancelled() {
+        writeln!(
+            out,
+            "Sweep cancelled after {}/{} combinations, returning the best so far",
+            done.load(Ordering::Relaxed),
+            total_iters
+        )?;
+    }
+
+    Ok(match best_pair {
+        Some((_, _, _, result, _)) if result.trade_count() == 0 => SweepOutcome::NoTradingStrategy,
+        Some((_, _, candidate, result, excess)) => SweepOutcome::Best(candidate, result, excess),
+        // Every result was filtered out (e.g. by `min_trades`) and none of them traded
+        None if any_evaluated.load(Ordering::Relaxed) && !any_traded.load(Ordering::Relaxed) => {
+            SweepOutcome::NoTradingStrategy
+        }
+        None => SweepOutcome::NoResult,
+    })
 }
 
-pub trait Backtester {
-    type Output: TradingMetrics + Send;
-    fn run_backtest(
+/// Execution-realism knobs shared by both backtesters.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecutionConfig {
+    /// Delay every action (entries and exits) by this many candles.
+    /// The fill happens at the price `latency_candles` after the signal.
+    pub latency_candles: usize,
+    /// Candles past the SMA warmup required for a full result (0 = no guard).
+    pub min_usable_candles: usize,
+    /// Below `min_usable_candles`, return a result flagged as partial instead of an error.
+    pub allow_partial: bool,
+    /// Pause new entries after this many losing trades in a row (0 = never pause).
+    pub max_consecutive_losses: usize,
+    /// Candles to sit out once `max_consecutive_losses` is hit.
+    pub cooldown_candles: usize,
+    /// An entry signal must repeat on this many consecutive candles before it's acted on
+    /// (0 or 1 = act on the first one).
+    pub confirm_candles: usize,
+    /// How a due signal is filled.
+    pub style: ExecutionStyle,
+    /// Keep only the price history the strategy looks back over
+    /// (`StrategyConfig::required_history`) instead of the whole series.
+    pub bounded_history: bool,
+    /// Keep every Nth point (plus the last) of the result's equity curve; 0 or 1 keeps
+    /// all of them. Drawdowns are still measured on every candle.
+    pub equity_curve_stride: usize,
+    /// What the entry fraction is a fraction of. Only the spot backtester adds to an
+    /// open position; the position backtester is always flat when it opens, so every
+    /// base sizes it the same.
+    pub sizing: SizingBase,
+    /// Shrink a long SMA window the data can't warm up to the largest one that leaves
+    /// `min_usable_candles` (see `SmaConfig::fit_to_history`), instead of failing.
+    pub fit_long_window: bool,
+    /// Size entries from the strategy's own recent trades instead of a fixed fraction.
+    pub kelly: Option<KellySizing>,
+    /// Lot size: bought, sold and opened quantities are rounded down to whole lots
+    /// (a sell of the whole holding sells it all). None trades any fraction.
+    pub qty_step: Option<f64>,
+}
+
+/// Fractional-Kelly entry sizing: `fraction * (p - (1 - p) / b)` over the last `window`
+/// closed trades, where `p` is the win rate and `b` the average win over the average
+/// loss. Until `window` trades have closed, entries use the candidate's fraction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KellySizing {
+    pub window: usize,
+    /// Share of the full Kelly fraction to bet (e.g. 0.5 = half Kelly).
+    pub fraction: f64,
+    /// Entry fraction cap, however strong the edge.
+    pub max_fraction: f64,
+}
+
+/// Amount the buy/sell fraction is applied to when sizing an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizingBase {
+    /// Cash on hand.
+    #[default]
+    Cash,
+    /// Mark-to-market equity (cash plus open positions at the current price).
+    Equity,
+    /// Initial equity plus realized profits; ignores unrealized P&L.
+    RealizedEquity,
+}
+
+impl SizingBase {
+    /// Cash to commit for an entry of `fraction`, never more than `cash`.
+    pub(crate) fn entry_budget(
+        self,
+        fraction: f64,
+        cash: f64,
+        equity: f64,
+        realized_equity: f64,
+    ) -> f64 {
+        let base = match self {
+            SizingBase::Cash => cash,
+            SizingBase::Equity => equity,
+            SizingBase::RealizedEquity => realized_equity,
+        };
+        (base * fraction).clamp(0.0, cash.max(0.0))
+    }
+}
+
+/// How a signal is turned into a fill once it's due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionStyle {
+    /// One fill at the due candle's price.
+    #[default]
+    Immediate,
+    /// Spread the order evenly over `candles` candles starting at the due candle, each
+    /// slice filled at its own candle's price. An opposite signal coming due cancels
+    /// the slices not filled yet (the filled ones stand) and starts its own order.
+    Twap { candles: usize },
+}
+
+impl ExecutionConfig {
+    /// The candidate to actually run: with `fit_long_window`, one whose long SMA
+    /// window fits `samples_len` candles.
+    pub(crate) fn fit_candidate<'a>(
         &self,
-        samples: &[Sample],
-        candidate: &Candidate,
-    ) -> Result<Self::Output, String>;
+        candidate: &'a Candidate,
+        samples_len: usize,
+    ) -> Cow<'a, Candidate> {
+        let fitted = self
+            .fit_long_window
+            .then(|| {
+                candidate
+                    .strategy
+                    .sma_config
+                    .fit_to_history(samples_len, self.min_usable_candles)
+            })
+            .flatten();
+        match fitted {
+            Some(sma_config) => {
+                let mut candidate = candidate.clone();
+                candidate.strategy.sma_config = sma_config;
+                Cow::Owned(candidate)
+            }
+            None => Cow::Borrowed(candidate),
+        }
+    }
+
+    /// Number of candles with computable SMAs, and whether the result is partial.
+    pub(crate) fn check_usable_candles(
+        &self,
+        samples_len: usize,
+        long_window: usize,
+    ) -> Result<(usize, bool), String> {
+        if samples_len < long_window + 1 {
+            return Err(DataError::TooShort {
+                have: samples_len,
+                need: long_window + 1,
+            }
+            .to_string());
+        }
+
+        let usable = samples_len - long_window;
+        if usable >= self.min_usable_candles {
+            Ok((usable, false))
+        } else if self.allow_partial {
+            Ok((usable, true))
+        } else {
+            Err(format!(
+                "not enough data: {} usable candles, need {}",
+                usable, self.min_usable_candles
+            ))
+        }
+    }
 }
 
-pub trait TradingMetrics {
-    fn total_return_pct(&self) -> f64;
-    fn max_drawdown_pct(&self) -> f64;
+/// First broken invariant found by a dry (validation) run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvariantViolation {
+    /// Candle index at which the violation was seen.
+    pub index: usize,
+    pub ts: DateTime<Utc>,
+    pub message: String,
+}
+
+/// Outcome of a dry run: the strategy is replayed and fills are checked, no metrics computed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    /// Candles simulated (up to and including a violating one).
+    pub candles_checked: usize,
+    pub violation: Option<InvariantViolation>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.violation.is_none()
+    }
+}
+
+/// Book-keeping invariants every fill must preserve.
+pub(crate) fn check_fill_invariants(cash: f64, size: f64, equity: f64) -> Result<(), String> {
+    if !cash.is_finite() || cash < 0.0 {
+        return Err(format!("cash went negative or non-finite: {cash}"));
+    }
+    if !size.is_finite() || size < 0.0 {
+        return Err(format!("position size is negative or non-finite: {size}"));
+    }
+    if !equity.is_finite() {
+        return Err(format!("equity is not finite: {equity}"));
+    }
+    Ok(())
+}
+
+/// Why a signal was acted on: the analysis reason and the rule that fired it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SignalReason {
+    pub(crate) text: String,
+    pub(crate) rule: Option<String>,
+}
+
+impl SignalReason {
+    pub(crate) fn of(analysis: &AnalysisResult) -> Self {
+        Self {
+            text: analysis.reason.clone(),
+            rule: analysis.rule.clone(),
+        }
+    }
+}
+
+/// One slice of an order, filled at `price`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Fill {
+    pub(crate) signal: Signal,
+    pub(crate) reason: SignalReason,
+    pub(crate) price: f64,
+    /// Share of the whole order this slice fills, and the share filled before it.
+    pub(crate) share: f64,
+    pub(crate) filled: f64,
+}
+
+impl Fill {
+    /// The order's first slice, which sizes the whole order.
+    pub(crate) fn is_first(&self) -> bool {
+        self.filled == 0.0
+    }
+}
+
+/// An order being worked over several candles (TWAP).
+#[derive(Debug)]
+struct WorkingOrder {
+    signal: Signal,
+    reason: SignalReason,
+    slices_filled: usize,
+    /// Whether a slice was already filled on the current candle.
+    filled_this_candle: bool,
+}
+
+/// Turns due signals into fills according to `ExecutionStyle`: one fill per signal, or
+/// with TWAP one per slice.
+#[derive(Debug)]
+pub(crate) struct FillScheduler {
+    slices: usize,
+    working: Option<WorkingOrder>,
+}
+
+impl FillScheduler {
+    pub(crate) fn new(execution: &ExecutionConfig) -> Self {
+        let slices = match execution.style {
+            ExecutionStyle::Immediate => 1,
+            ExecutionStyle::Twap { candles } => candles.max(1),
+        };
+        Self {
+            slices,
+            working: None,
+        }
+    }
+
+    /// A signal became due at a candle priced `price`: fill the first slice of its
+    /// order, unless an order for the same signal is already being worked. An
+    /// opposite signal cancels the rest of the working order.
+    pub(crate) fn submit(
+        &mut self,
+        signal: Signal,
+        reason: SignalReason,
+        price: f64,
+    ) -> Option<Fill> {
+        if self.working.as_ref().is_some_and(|w| w.signal == signal) {
+            return None;
+        }
+        self.working = Some(WorkingOrder {
+            signal,
+            reason,
+            slices_filled: 0,
+            filled_this_candle: true,
+        });
+        self.next_slice(price)
+    }
+
+    /// Fill the working order's next slice at `price`. Call once per candle, after
+    /// submitting that candle's due signals; an order submitted on this candle
+    /// already had its slice.
+    pub(crate) fn advance(&mut self, price: f64) -> Option<Fill> {
+        let working = self.working.as_mut()?;
+        if std::mem::take(&mut working.filled_this_candle) {
+            return None;
+        }
+        self.next_slice(price)
+    }
+
+    fn next_slice(&mut self, price: f64) -> Option<Fill> {
+        let working = self.working.as_mut()?;
+        let share = 1.0 / self.slices as f64;
+        let fill = Fill {
+            signal: working.signal,
+            reason: working.reason.clone(),
+            price,
+            share,
+            filled: working.slices_filled as f64 * share,
+        };
+        working.slices_filled += 1;
+        if working.slices_filled >= self.slices {
+            self.working = None;
+        }
+        Some(fill)
+    }
+}
+
+/// Debounces signals: counts how many candles in a row produced the same one.
+#[derive(Debug)]
+pub(crate) struct SignalPersistence {
+    required: usize,
+    last: Option<Signal>,
+    streak: usize,
+}
+
+impl SignalPersistence {
+    pub(crate) fn new(execution: &ExecutionConfig) -> Self {
+        Self {
+            required: execution.confirm_candles,
+            last: None,
+            streak: 0,
+        }
+    }
+
+    /// Feed this candle's signal (None = HOLD); true once it has persisted long enough.
+    pub(crate) fn observe(&mut self, signal: Option<Signal>) -> bool {
+        if signal.is_some() && signal == self.last {
+            self.streak += 1;
+        } else {
+            self.last = signal;
+            self.streak = usize::from(signal.is_some());
+        }
+        signal.is_some() && self.streak >= self.required
+    }
+}
+
+/// Consecutive-loss circuit breaker: once `max_consecutive_losses` trades in a row
+/// lose, new entries are blocked for `cooldown_candles`. A winner resets the streak.
+#[derive(Debug)]
+pub(crate) struct LossStreak {
+    max_losses: usize,
+    cooldown: usize,
+    losses: usize,
+    /// Last candle (inclusive) on which entries are blocked.
+    paused_through: Option<usize>,
+}
+
+impl LossStreak {
+    pub(crate) fn new(execution: &ExecutionConfig) -> Self {
+        Self {
+            max_losses: execution.max_consecutive_losses,
+            cooldown: execution.cooldown_candles,
+            losses: 0,
+            paused_through: None,
+        }
+    }
+
+    /// Record a trade closed at candle `index`.
+    pub(crate) fn record(&mut self, profit: f64, index: usize) {
+        if profit > 0.0 {
+            self.losses = 0;
+            return;
+        }
+
+        self.losses += 1;
+        if self.max_losses > 0 && self.losses >= self.max_losses {
+            self.losses = 0;
+            self.paused_through = Some(index + self.cooldown);
+        }
+    }
+
+    /// Whether new entries are blocked at candle `index`.
+    pub(crate) fn is_paused(&self, index: usize) -> bool {
+        self.paused_through.is_some_and(|last| index <= last)
+    }
+}
+
+/// Rolling window of closed-trade returns that `KellySizing` sizes entries from.
+#[derive(Debug)]
+pub(crate) struct EdgeTracker {
+    kelly: Option<KellySizing>,
+    returns: VecDeque<f64>,
+}
+
+impl EdgeTracker {
+    pub(crate) fn new(execution: &ExecutionConfig) -> Self {
+        Self {
+            kelly: execution.kelly,
+            returns: VecDeque::new(),
+        }
+    }
+
+    /// Record a closed trade's return.
+    pub(crate) fn record(&mut self, return_pct: f64) {
+        let Some(kelly) = self.kelly else {
+            return;
+        };
+        self.returns.push_back(return_pct);
+        if self.returns.len() > kelly.window {
+            self.returns.pop_front();
+        }
+    }
+
+    /// Fraction to enter with; `fixed` until the window is full (or without Kelly sizing).
+    pub(crate) fn entry_fraction(&self, fixed: f64) -> f64 {
+        let Some(kelly) = self.kelly else {
+            return fixed;
+        };
+        if kelly.window == 0 || self.returns.len() < kelly.window {
+            return fixed;
+        }
+
+        let (wins, losses): (Vec<f64>, Vec<f64>) = self.returns.iter().partition(|&&r| r > 0.0);
+        let mean = |values: &[f64]| {
+            if values.is_empty() {
+                0.0
+            } else {
+                values.iter().sum::<f64>() / values.len() as f64
+            }
+        };
+        let win_rate = wins.len() as f64 / self.returns.len() as f64;
+        let payoff = mean(&wins) / mean(&losses).abs();
+        let full_kelly = win_rate - (1.0 - win_rate) / payoff;
+
+        // f64::max drops the NaN of an all-flat window
+        (kelly.fraction * full_kelly)
+            .max(0.0)
+            .min(kelly.max_fraction)
+    }
+}
+
+/// Prices seen so far, as fed to the strategy. With `bounded_history` it's trimmed to
+/// (at most twice) the history the strategy needs, so memory doesn't grow with the series.
+#[derive(Debug)]
+pub(crate) struct PriceHistory {
+    prices: Vec<f64>,
+    keep: Option<usize>,
+}
+
+impl PriceHistory {
+    pub(crate) fn new(execution: &ExecutionConfig, candidate: &Candidate, total: usize) -> Self {
+        let keep = if execution.bounded_history {
+            // A swing stop looks back over candles before the entry too
+            let stop_lookback = match candidate.stop {
+                Some(StopPlacement::SwingLookback(lookback)) => lookback + 1,
+                _ => 0,
+            };
+            candidate
+                .strategy
+                .required_history()
+                .map(|keep| keep.max(stop_lookback))
+        } else {
+            None
+        };
+        let capacity = keep.map_or(total, |keep| (2 * keep).min(total));
+        Self {
+            prices: Vec::with_capacity(capacity),
+            keep,
+        }
+    }
+
+    pub(crate) fn push(&mut self, price: f64) {
+        self.prices.push(price);
+        if let Some(keep) = self.keep
+            && self.prices.len() >= 2 * keep
+        {
+            self.prices.drain(..self.prices.len() - keep);
+        }
+    }
+
+    pub(crate) fn as_slice(&self) -> &[f64] {
+        &self.prices
+    }
+}
+
+/// Drawdowns accumulated one equity point at a time; the same numbers
+/// `compute_drawdown` and `compute_max_log_drawdown` give on the full curve.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DrawdownTracker {
+    initial: Option<f64>,
+    peak: Option<f64>,
+    log_peak: Option<f64>,
+    pub(crate) max_drawdown_pct: f64,
+    pub(crate) max_drawdown_from_initial_pct: f64,
+    pub(crate) max_log_drawdown: f64,
+}
+
+impl DrawdownTracker {
+    pub(crate) fn observe(&mut self, equity: f64) {
+        let initial = *self.initial.get_or_insert(equity);
+        let peak = self.peak.map_or(equity, |peak| peak.max(equity));
+        self.peak = Some(peak);
+        if peak > 0.0 {
+            self.max_drawdown_pct = self.max_drawdown_pct.max((peak - equity) / peak);
+        }
+        if initial > 0.0 {
+            self.max_drawdown_from_initial_pct = self
+                .max_drawdown_from_initial_pct
+                .max((initial - equity) / initial);
+        }
+        if equity > 0.0 {
+            let log_equity = equity.ln();
+            let log_peak = self
+                .log_peak
+                .map_or(log_equity, |peak| peak.max(log_equity));
+            self.log_peak = Some(log_peak);
+            self.max_log_drawdown = self.max_log_drawdown.max(log_peak - log_equity);
+        }
+    }
+}
+
+/// Equity curve recorder: keeps every `equity_curve_stride`-th point (and the last one)
+/// and tracks drawdowns over all of them.
+#[derive(Debug)]
+pub(crate) struct EquityRecorder {
+    stride: usize,
+    seen: usize,
+    curve: Vec<(DateTime<Utc>, f64)>,
+    last: Option<(DateTime<Utc>, f64)>,
+    drawdowns: DrawdownTracker,
+}
+
+impl EquityRecorder {
+    pub(crate) fn new(execution: &ExecutionConfig, total: usize) -> Self {
+        let stride = execution.equity_curve_stride.max(1);
+        Self {
+            stride,
+            seen: 0,
+            curve: Vec::with_capacity(total / stride + 1),
+            last: None,
+            drawdowns: DrawdownTracker::default(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, ts: DateTime<Utc>, equity: f64) {
+        self.drawdowns.observe(equity);
+        if self.seen.is_multiple_of(self.stride) {
+            self.curve.push((ts, equity));
+        }
+        self.seen += 1;
+        self.last = Some((ts, equity));
+    }
+
+    /// The (possibly thinned) curve and the drawdowns over every point pushed.
+    pub(crate) fn finish(mut self) -> (Vec<(DateTime<Utc>, f64)>, DrawdownTracker) {
+        if let Some(last) = self.last
+            && !(self.seen - 1).is_multiple_of(self.stride)
+        {
+            self.curve.push(last);
+        }
+        (self.curve, self.drawdowns)
+    }
+}
+
+/// Signals waiting for their (possibly delayed) execution candle.
+#[derive(Debug, Default)]
+pub(crate) struct PendingSignals {
+    queue: VecDeque<(usize, Signal, SignalReason)>,
+}
+
+impl PendingSignals {
+    /// Queue a signal seen at candle `index` to be executed `latency` candles later.
+    pub(crate) fn push(
+        &mut self,
+        index: usize,
+        latency: usize,
+        signal: Signal,
+        reason: SignalReason,
+    ) {
+        self.queue.push_back((index + latency, signal, reason));
+    }
+
+    /// Pop the next signal due at (or before) candle `index`.
+    pub(crate) fn pop_due(&mut self, index: usize) -> Option<(Signal, SignalReason)> {
+        match self.queue.front() {
+            Some((due, _, _)) if *due <= index => self
+                .queue
+                .pop_front()
+                .map(|(_, signal, reason)| (signal, reason)),
+            _ => None,
+        }
+    }
+}
+
+pub trait Backtester {
+    type Output: TradingMetrics + Send + Sync;
+    /// Backtest `candidate` over everything `source` yields. The run needs the whole
+    /// series before simulating (window fitting, the usable-candle check, the buy &
+    /// hold benchmark), so the source is read to the end first; a source error fails
+    /// the run.
+    fn run_backtest(
+        &self,
+        source: &mut dyn PriceSource,
+        candidate: &Candidate,
+    ) -> Result<Self::Output, String>;
+}
+
+pub trait TradingMetrics {
+    fn total_return_pct(&self) -> f64;
+    fn max_drawdown_pct(&self) -> f64;
+    fn trade_count(&self) -> usize;
+    /// Annualized Sharpe ratio of the equity curve (see `compute_sharpe`).
+    fn sharpe_ratio(&self) -> f64;
+    /// Compound annual growth rate over the equity curve's first to last timestamp
+    /// (see `compute_cagr`), so backtests over different date ranges compare.
+    fn cagr(&self) -> f64;
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::data::Sample;
+    use crate::indicators::sma::SmaConfig;
+    use crate::signal::{FilterConfig, StrategyConfig};
+
+    /// Hourly samples starting at 2025-01-01T00:00:00Z.
+    pub fn hourly_samples(prices: &[f64]) -> Vec<Sample> {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, &price)| Sample {
+                ts: start + Duration::hours(i as i64),
+                price,
+                high: None,
+                low: None,
+                volume: None,
+            })
+            .collect()
+    }
+
+    /// SMA2/3 bias-only strategy without filters:
+    /// BUY while SMA(2) > SMA(3), SELL while SMA(2) < SMA(3).
+    pub fn bias_only_strategy() -> StrategyConfig {
+        StrategyConfig {
+            breakouts: None,
+            pullbacks: None,
+            enable_crossovers: false,
+            enable_bias_only: true,
+            enable_macd: None,
+            rule_order: Vec::new(),
+            bollinger: None,
+            sma_config: SmaConfig::new(2, 3),
+            filters: FilterConfig::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::{bias_only_strategy, hourly_samples};
+    use super::*;
+
+    fn curve(equities: &[f64]) -> Vec<(DateTime<Utc>, f64)> {
+        hourly_samples(equities)
+            .into_iter()
+            .map(|s| (s.ts, s.price))
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_sharpe_flat_curve_is_zero() {
+        let curve = curve(&[100.0; 10]);
+        assert_eq!(compute_sharpe(&curve), 0.0);
+    }
+
+    #[test]
+    fn test_equity_metrics_from_curve() {
+        // +10% then -10%: 100 -> 110 -> 99
+        let curve = curve(&[100.0, 110.0, 99.0]);
+        let m = EquityMetrics::from_curve(&curve);
+
+        assert!((m.total_return_pct - (-0.01)).abs() < 1e-12);
+        assert!((m.max_drawdown_pct - 0.1).abs() < 1e-12);
+        // mean return = (0.1 - 0.1) / 2 = 0
+        assert!(m.sharpe.abs() < 1e-12);
+        assert!(m.cagr_pct < 0.0);
+    }
+
+    #[test]
+    fn test_compute_profit_factor_and_average_win_loss() {
+        let (profit_factor, avg_win, avg_loss) = compute_profit_factor(&[30.0, -10.0, 10.0, -10.0]);
+        assert!((profit_factor - 2.0).abs() < 1e-12);
+        assert!((avg_win - 20.0).abs() < 1e-12);
+        assert!((avg_loss - 10.0).abs() < 1e-12);
+
+        // Breakeven trades count as neither
+        assert_eq!(
+            compute_profit_factor(&[5.0, 0.0]),
+            (f64::INFINITY, 5.0, 0.0)
+        );
+        assert_eq!(compute_profit_factor(&[-5.0]), (0.0, 0.0, 5.0));
+        assert_eq!(compute_profit_factor(&[]), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_compute_drawdown_peak_to_trough_vs_from_initial() {
+        // Rises to 120, dips to 105 (below the peak, above the start), recovers.
+        let curve = curve(&[100.0, 120.0, 105.0, 110.0]);
+
+        let peak_to_trough = compute_drawdown(&curve, DrawdownKind::PeakToTrough);
+        let from_initial = compute_drawdown(&curve, DrawdownKind::FromInitial);
+
+        assert!((peak_to_trough - 15.0 / 120.0).abs() < 1e-12);
+        assert_eq!(from_initial, 0.0);
+        assert_eq!(compute_max_drawdown(&curve), peak_to_trough);
+
+        // Going below the start shows up in both
+        let curve = self::curve(&[100.0, 120.0, 90.0]);
+        assert!((compute_drawdown(&curve, DrawdownKind::PeakToTrough) - 0.25).abs() < 1e-12);
+        assert!((compute_drawdown(&curve, DrawdownKind::FromInitial) - 0.10).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_generate_strategies_for_windows_drops_equal_and_inverted_windows() {
+        let windows = [(10, 40), (20, 20), (30, 10)];
+        let mut warnings = Vec::new();
+        let strategies =
+            generate_strategies_for_windows(&windows, 3, 3, vec![(0.001, 0.001)], &mut warnings)
+                .unwrap();
+
+        assert!(!strategies.is_empty());
+        assert!(
+            strategies
+                .iter()
+                .all(|s| { s.sma_config.short_window == 10 && s.sma_config.long_window == 40 })
+        );
+        assert!(strategies.iter().all(|s| s.validate().is_ok()));
+
+        let warnings = String::from_utf8(warnings).unwrap();
+        assert!(warnings.contains("skipping SMA20/20"));
+        assert!(warnings.contains("skipping SMA30/10"));
+
+        let only_invalid =
+            generate_strategies_for_windows(&[(20, 20)], 3, 3, vec![], &mut io::sink()).unwrap();
+        assert!(only_invalid.is_empty());
+    }
+
+    #[test]
+    fn test_log_drawdown_of_a_fixed_curve() {
+        // Dips of 20% (100 -> 80), 25% (160 -> 120) and, after compounding, 37.5%
+        // (480 -> 300): the last one is the max either way
+        let curve = curve(&[100.0, 80.0, 160.0, 120.0, 480.0, 300.0, 400.0]);
+
+        assert!((compute_max_drawdown(&curve) - 0.375).abs() < 1e-12);
+        assert!((compute_max_log_drawdown(&curve) - (480.0_f64 / 300.0).ln()).abs() < 1e-12);
+
+        // Zero equity is a 100% arithmetic drawdown but is skipped on the log scale
+        let wiped = self::curve(&[100.0, 0.0, 50.0]);
+        assert_eq!(compute_max_drawdown(&wiped), 1.0);
+        assert!((compute_max_log_drawdown(&wiped) - 2.0_f64.ln()).abs() < 1e-12);
+
+        // A flat or rising curve has no drawdown either way
+        assert_eq!(
+            compute_max_log_drawdown(&self::curve(&[1.0, 2.0, 4.0])),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_equity_recorder_thins_curve_but_tracks_every_drawdown() {
+        let points = curve(&[100.0, 120.0, 60.0, 90.0, 150.0, 140.0, 0.0, 50.0]);
+        let mut recorder = EquityRecorder::new(
+            &ExecutionConfig {
+                equity_curve_stride: 3,
+                ..Default::default()
+            },
+            points.len(),
+        );
+        for &(ts, equity) in &points {
+            recorder.push(ts, equity);
+        }
+        let (thinned, drawdowns) = recorder.finish();
+
+        // Points 0, 3 and 6, plus the last one
+        assert_eq!(thinned, [points[0], points[3], points[6], points[7]]);
+        // The dip to 60 falls between kept points but still counts
+        assert_eq!(drawdowns.max_drawdown_pct, compute_max_drawdown(&points));
+        assert_eq!(
+            drawdowns.max_drawdown_from_initial_pct,
+            compute_drawdown(&points, DrawdownKind::FromInitial)
+        );
+        assert_eq!(
+            drawdowns.max_log_drawdown,
+            compute_max_log_drawdown(&points)
+        );
+    }
+
+    struct MockResult {
+        total_return_pct: f64,
+        trades: usize,
+    }
+
+    impl TradingMetrics for MockResult {
+        fn total_return_pct(&self) -> f64 {
+            self.total_return_pct
+        }
+
+        fn max_drawdown_pct(&self) -> f64 {
+            0.0
+        }
+
+        fn trade_count(&self) -> usize {
+            self.trades
+        }
+
+        fn sharpe_ratio(&self) -> f64 {
+            1.0 - self.total_return_pct
+        }
+
+        fn cagr(&self) -> f64 {
+            0.0
+        }
+    }
+
+    /// Reports the candidate's fraction as its return (and one minus it as its Sharpe)
+    /// and counts evaluations.
+    /// The full-size candidate (fraction 1.0) gets there with a single lucky trade.
+    struct MockBacktester {
+        evaluations: Arc<AtomicUsize>,
+    }
+
+    impl Backtester for MockBacktester {
+        type Output = MockResult;
+        fn run_backtest(
+            &self,
+            _source: &mut dyn PriceSource,
+            candidate: &Candidate,
+        ) -> Result<Self::Output, String> {
+            self.evaluations.fetch_add(1, Ordering::Relaxed);
+            let fraction = candidate.buy_sell_fraction;
+            Ok(MockResult {
+                total_return_pct: fraction,
+                trades: if fraction >= 1.0 { 1 } else { 20 },
+            })
+        }
+    }
+
+    #[test]
+    fn test_find_best_strategy_without_early_stop_picks_highest_return() {
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 10);
+        let evaluations = Arc::new(AtomicUsize::new(0));
+
+        let (candidate, result) = find_best_strategy(
+            jobs,
+            1.0,
+            10,
+            &hourly_samples(&[1.0]),
+            &SweepOptions::default(),
+            &mut io::sink(),
+            || MockBacktester {
+                evaluations: evaluations.clone(),
+            },
+        )
+        .unwrap()
+        .best()
+        .unwrap();
+
+        assert!((candidate.buy_sell_fraction - 1.0).abs() < 1e-12);
+        assert!((result.total_return_pct - 1.0).abs() < 1e-12);
+        assert_eq!(evaluations.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn test_find_best_strategy_breaks_full_ties_by_candidate_key() {
+        /// Every candidate gets the same result.
+        struct FlatBacktester;
+
+        impl Backtester for FlatBacktester {
+            type Output = MockResult;
+            fn run_backtest(
+                &self,
+                _source: &mut dyn PriceSource,
+                _candidate: &Candidate,
+            ) -> Result<Self::Output, String> {
+                Ok(MockResult {
+                    total_return_pct: 0.1,
+                    trades: 20,
+                })
+            }
+        }
+
+        let strategies: Vec<StrategyConfig> = [(3, 8), (4, 5), (2, 5), (2, 6)]
+            .into_iter()
+            .map(|(short, long)| StrategyConfig {
+                sma_config: SmaConfig::new(short, long),
+                ..bias_only_strategy()
+            })
+            .collect();
+        for _ in 0..20 {
+            let jobs = generate_backtest_sweep_jobs(strategies.clone(), 10);
+            let (candidate, _) = find_best_strategy(
+                jobs,
+                1.0,
+                10,
+                &hourly_samples(&[1.0]),
+                &SweepOptions::default(),
+                &mut io::sink(),
+                || FlatBacktester,
+            )
+            .unwrap()
+            .best()
+            .unwrap();
+
+            // Shortest long window, then shortest short window, then smallest fraction
+            assert_eq!(candidate.strategy.sma_config.long_window, 5);
+            assert_eq!(candidate.strategy.sma_config.short_window, 2);
+            assert!((candidate.buy_sell_fraction - 0.1).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_find_best_strategy_ranks_by_sharpe() {
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 10);
+        let options = SweepOptions {
+            rank_by: SweepMetric::Sharpe,
+            ..Default::default()
+        };
+
+        let (candidate, result) = find_best_strategy(
+            jobs,
+            1.0,
+            10,
+            &hourly_samples(&[1.0]),
+            &options,
+            &mut io::sink(),
+            || MockBacktester {
+                evaluations: Arc::new(AtomicUsize::new(0)),
+            },
+        )
+        .unwrap()
+        .best()
+        .unwrap();
+
+        // The smallest fraction has the lowest return but the best Sharpe
+        assert!((candidate.buy_sell_fraction - 0.1).abs() < 1e-12);
+        assert!((result.sharpe_ratio() - 0.9).abs() < 1e-12);
+    }
+
+    /// Run `f` on a one-thread rayon pool, so a sweep evaluates its jobs in order.
+    fn single_threaded<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(f)
+    }
+
+    #[test]
+    fn test_find_best_strategy_early_stop_returns_first_qualifying_job() {
+        // 400 jobs, fractions 0.0025, 0.005, ..., 1.0. Return == fraction,
+        // so job index 199 (fraction 0.5) is the first to reach the target.
+        let steps = 400;
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], steps);
+        let evaluations = Arc::new(AtomicUsize::new(0));
+        let options = SweepOptions {
+            early_stop: Some(EarlyStop {
+                metric: SweepMetric::TotalReturn,
+                target: 0.5,
+            }),
+            ..Default::default()
+        };
+
+        let (candidate, _) = single_threaded(|| {
+            find_best_strategy(
+                jobs,
+                1.0,
+                steps,
+                &hourly_samples(&[1.0]),
+                &options,
+                &mut io::sink(),
+                || MockBacktester {
+                    evaluations: evaluations.clone(),
+                },
+            )
+        })
+        .unwrap()
+        .best()
+        .unwrap();
+
+        assert!((candidate.buy_sell_fraction - 0.5).abs() < 1e-12);
+        // A single worker takes the jobs in order and skips everything after the hit
+        assert_eq!(evaluations.load(Ordering::Relaxed), 200);
+    }
+
+    #[test]
+    fn test_find_best_strategy_excludes_candidates_below_min_trades() {
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 10);
+        let evaluations = Arc::new(AtomicUsize::new(0));
+        let options = SweepOptions {
+            min_trades: 5,
+            ..Default::default()
+        };
+
+        let (candidate, result) = find_best_strategy(
+            jobs,
+            1.0,
+            10,
+            &hourly_samples(&[1.0]),
+            &options,
+            &mut io::sink(),
+            || MockBacktester {
+                evaluations: evaluations.clone(),
+            },
+        )
+        .unwrap()
+        .best()
+        .unwrap();
+
+        // The one-trade 100% candidate is disqualified, the 90% many-trade one wins
+        assert!((candidate.buy_sell_fraction - 0.9).abs() < 1e-12);
+        assert_eq!(result.trades, 20);
+    }
+
+    /// Requests cancellation on its first evaluation.
+    struct CancellingBacktester {
+        inner: MockBacktester,
+        cancel: Arc<AtomicBool>,
+    }
+
+    impl Backtester for CancellingBacktester {
+        type Output = MockResult;
+
+        fn run_backtest(
+            &self,
+            source: &mut dyn PriceSource,
+            candidate: &Candidate,
+        ) -> Result<Self::Output, String> {
+            self.cancel.store(true, Ordering::Relaxed);
+            self.inner.run_backtest(source, candidate)
+        }
+    }
+
+    #[test]
+    fn test_find_best_strategy_cancel_returns_best_so_far() {
+        let steps = 400;
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], steps);
+        let evaluations = Arc::new(AtomicUsize::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = SweepOptions {
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+
+        let (candidate, result) = single_threaded(|| {
+            find_best_strategy(
+                jobs,
+                1.0,
+                steps,
+                &hourly_samples(&[1.0]),
+                &options,
+                &mut io::sink(),
+                || CancellingBacktester {
+                    inner: MockBacktester {
+                        evaluations: evaluations.clone(),
+                    },
+                    cancel: cancel.clone(),
+                },
+            )
+        })
+        .unwrap()
+        .best()
+        .expect("the job in flight still produces a result");
+
+        // The first job raised the flag; no other job was started after it
+        assert_eq!(evaluations.load(Ordering::Relaxed), 1);
+        assert!((result.total_return_pct - candidate.buy_sell_fraction).abs() < 1e-12);
+    }
+
+    fn benchmark(buy_sell_fraction: f64, relative: bool) -> Benchmark {
+        Benchmark {
+            candidate: Candidate {
+                buy_sell_fraction,
+                strategy: bias_only_strategy(),
+                stop: None,
+            },
+            relative,
+        }
+    }
+
+    #[test]
+    fn test_benchmark_own_excess_is_zero() {
+        let bench = benchmark(0.5, false);
+        let jobs = vec![(bench.candidate.strategy.clone(), 1)];
+        let evaluations = Arc::new(AtomicUsize::new(0));
+        let make_backtester = || MockBacktester {
+            evaluations: evaluations.clone(),
+        };
+        let options = SweepOptions {
+            benchmark: Some(bench.clone()),
+            ..Default::default()
+        };
+
+        let outcome = find_best_strategy(
+            jobs,
+            0.5,
+            1,
+            &hourly_samples(&[1.0]),
+            &options,
+            &mut io::sink(),
+            make_backtester,
+        )
+        .unwrap();
+
+        let SweepOutcome::Best(_, _, excess) = outcome else {
+            panic!("the single job should win");
+        };
+        assert_eq!(
+            excess,
+            Some(Excess {
+                return_pct: 0.0,
+                max_drawdown_pct: 0.0,
+                trades: 0
+            })
+        );
+        // One run for the job, one for the benchmark
+        assert_eq!(evaluations.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_find_best_strategy_reports_to_the_given_writer() {
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 1);
+        let options = SweepOptions {
+            benchmark: Some(benchmark(0.5, false)),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+
+        find_best_strategy(
+            jobs,
+            1.0,
+            1,
+            &hourly_samples(&[1.0]),
+            &options,
+            &mut out,
+            || MockBacktester {
+                evaluations: Arc::new(AtomicUsize::new(0)),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Benchmark: 50.00% return, 0.00% max drawdown, 20 trades\n\
+             Running parameter sweep... (1 total combinations)\n\
+             Progress: 100.00% (1/1)\n\
+             SMA2/3 + bias_only (fraction 1.00) vs benchmark: \
+             +50.00% return, +0.00% max drawdown, -19 trades\n"
+        );
+    }
+
+    #[test]
+    fn test_relative_benchmark_measures_early_stop_as_excess() {
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 10);
+        let options = SweepOptions {
+            early_stop: Some(EarlyStop {
+                metric: SweepMetric::TotalReturn,
+                target: 0.3,
+            }),
+            benchmark: Some(benchmark(0.5, true)),
+            ..Default::default()
+        };
+
+        let (candidate, _) = find_best_strategy(
+            jobs,
+            1.0,
+            10,
+            &hourly_samples(&[1.0]),
+            &options,
+            &mut io::sink(),
+            || MockBacktester {
+                evaluations: Arc::new(AtomicUsize::new(0)),
+            },
+        )
+        .unwrap()
+        .best()
+        .unwrap();
+
+        // 0.8 is the first fraction to beat the benchmark's 0.5 by 0.3
+        assert!((candidate.buy_sell_fraction - 0.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_relative_benchmark_nothing_beats_it() {
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 10);
+        let options = SweepOptions {
+            benchmark: Some(benchmark(1.0, true)),
+            ..Default::default()
+        };
+
+        let best = find_best_strategy(
+            jobs,
+            1.0,
+            10,
+            &hourly_samples(&[1.0]),
+            &options,
+            &mut io::sink(),
+            || MockBacktester {
+                evaluations: Arc::new(AtomicUsize::new(0)),
+            },
+        )
+        .unwrap();
+
+        assert!(best.best().is_none());
+    }
+
+    fn ranges() -> StrategyRanges {
+        StrategyRanges {
+            short_window: (5, 40),
+            long_window: (20, 120),
+            breakout_lookback: (3, 10),
+            pullback_pct: (0.001, 0.01),
+        }
+    }
+
+    #[test]
+    fn test_generate_random_strategies_is_reproducible_and_valid() {
+        let describe = |strategies: &[StrategyConfig]| -> Vec<String> {
+            strategies.iter().map(|s| format!("{s:?}")).collect()
+        };
+        let a = generate_random_strategies(&ranges(), 50, 42, &mut io::sink()).unwrap();
+        let b = generate_random_strategies(&ranges(), 50, 42, &mut io::sink()).unwrap();
+        let c = generate_random_strategies(&ranges(), 50, 43, &mut io::sink()).unwrap();
+
+        assert_eq!(a.len(), 50);
+        assert_eq!(describe(&a), describe(&b));
+        assert_ne!(describe(&a), describe(&c));
+        for strategy in &a {
+            assert!(strategy.validate().is_ok());
+            assert!(
+                strategy.breakouts.is_some()
+                    || strategy.pullbacks.is_some()
+                    || strategy.enable_crossovers
+            );
+            if let Some(p) = strategy.pullbacks {
+                assert!(p.bounce_tolerance_pct <= p.reject_tolerance_pct);
+                assert!((0.001..0.01).contains(&p.reject_tolerance_pct));
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_random_strategies_gives_up_on_impossible_ranges() {
+        let ranges = StrategyRanges {
+            short_window: (50, 60),
+            long_window: (10, 20),
+            ..ranges()
+        };
+        let mut warnings = Vec::new();
+        assert!(
+            generate_random_strategies(&ranges, 5, 1, &mut warnings)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            String::from_utf8(warnings).unwrap(),
+            "Warning: only 0 of 5 random strategies are valid for these ranges\n"
+        );
+    }
+
+    #[test]
+    fn test_kelly_sizing_follows_recent_edge() {
+        let execution = ExecutionConfig {
+            kelly: Some(KellySizing {
+                window: 4,
+                fraction: 0.5,
+                max_fraction: 0.8,
+            }),
+            ..Default::default()
+        };
+        let sized_after = |returns: &[f64]| {
+            let mut edge = EdgeTracker::new(&execution);
+            for &r in returns {
+                edge.record(r);
+            }
+            edge.entry_fraction(0.3)
+        };
+
+        // Window not full yet: the fixed fraction
+        assert_eq!(sized_after(&[0.05, 0.05]), 0.3);
+
+        // 3 of 4 winners, payoff 2: Kelly 0.75 - 0.25 / 2 = 0.625, halved
+        let winning = sized_after(&[0.04, 0.04, -0.02, 0.04]);
+        assert!((winning - 0.3125).abs() < 1e-12);
+        // Only the last 4 count: old losers roll out
+        assert_eq!(sized_after(&[-0.1, -0.1, 0.04, 0.04, -0.02, 0.04]), winning);
+
+        // Mostly losers: no edge, no size
+        let losing = sized_after(&[-0.04, -0.04, 0.02, -0.04]);
+        assert_eq!(losing, 0.0);
+        assert!(winning > losing);
+
+        // No losers: full Kelly of 1, halved
+        assert_eq!(sized_after(&[0.01; 4]), 0.5);
+    }
+
+    #[test]
+    fn test_candidate_fingerprint_is_stable_and_covers_parameters() {
+        let candidate = Candidate {
+            buy_sell_fraction: 0.5,
+            strategy: bias_only_strategy(),
+            stop: None,
+        };
+        assert_eq!(candidate.fingerprint(), candidate.clone().fingerprint());
+        // Pinned: a change here invalidates every saved cache and checkpoint
+        assert_eq!(candidate.fingerprint(), 1124617707721136341);
+        let params = candidate.canonical_params();
+        assert_eq!(params["sma.long_window"], "3");
+        assert_eq!(params["buy_sell_fraction"], "0x3fe0000000000000");
+        assert_eq!(params["breakouts"], "none");
+
+        let mut fraction = candidate.clone();
+        fraction.buy_sell_fraction = 0.6;
+        let mut window = candidate.clone();
+        window.strategy.sma_config = SmaConfig::new(2, 4);
+        let mut stop = candidate.clone();
+        stop.stop = Some(StopPlacement::Percent(0.02));
+        for changed in [fraction, window, stop] {
+            assert_ne!(changed.fingerprint(), candidate.fingerprint());
+        }
+    }
+
+    #[test]
+    fn test_find_best_strategy_reports_no_trading_strategy_on_flat_data() {
+        use crate::backtest::position::PositionBacktester;
+
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 4);
+        let samples = hourly_samples(&[100.0; 20]);
+
+        let outcome = find_best_strategy(
+            jobs,
+            1.0,
+            4,
+            &samples,
+            &SweepOptions::default(),
+            &mut io::sink(),
+            || PositionBacktester::new(1_000.0),
+        )
+        .unwrap();
+
+        assert!(matches!(outcome, SweepOutcome::NoTradingStrategy));
+    }
+
+    /// Trades once and loses the candidate's fraction, except at full size where it
+    /// stays flat.
+    struct LosingTradesBacktester;
+
+    impl Backtester for LosingTradesBacktester {
+        type Output = MockResult;
+
+        fn run_backtest(
+            &self,
+            _source: &mut dyn PriceSource,
+            candidate: &Candidate,
+        ) -> Result<Self::Output, String> {
+            let fraction = candidate.buy_sell_fraction;
+            Ok(if fraction >= 1.0 {
+                MockResult {
+                    total_return_pct: 0.0,
+                    trades: 0,
+                }
+            } else {
+                MockResult {
+                    total_return_pct: -fraction,
+                    trades: 1,
+                }
+            })
+        }
+    }
+
+    #[test]
+    fn test_find_best_strategy_reports_no_trading_strategy_when_the_winner_never_traded() {
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 4);
+
+        let outcome = find_best_strategy(
+            jobs,
+            1.0,
+            4,
+            &hourly_samples(&[1.0]),
+            &SweepOptions::default(),
+            &mut io::sink(),
+            || LosingTradesBacktester,
+        )
+        .unwrap();
+
+        // The others traded, but the flat candidate is the "best"
+        assert!(matches!(outcome, SweepOutcome::NoTradingStrategy));
+    }
+
+    #[test]
+    fn test_find_best_strategy_reports_no_trading_strategy_when_min_trades_filters_everything() {
+        use crate::backtest::position::PositionBacktester;
+
+        let jobs = generate_backtest_sweep_jobs(vec![bias_only_strategy()], 4);
+        let samples = hourly_samples(&[100.0; 20]);
+        let options = SweepOptions {
+            min_trades: 1,
+            ..Default::default()
+        };
+
+        let outcome = find_best_strategy(jobs, 1.0, 4, &samples, &options, &mut io::sink(), || {
+            PositionBacktester::new(1_000.0)
+        })
+        .unwrap();
+
+        assert!(matches!(outcome, SweepOutcome::NoTradingStrategy));
+    }
 }
@@ -2,6 +2,11 @@ mod common;
 pub mod position;
 pub mod spot;
 pub use common::{
-    Backtester, Candidate, TradingMetrics, find_best_strategy, generate_backtest_sweep_jobs,
-    generate_pullback_pairs, generate_strategies,
+    Backtester, Benchmark, Candidate, DrawdownKind, EarlyStop, EquityMetrics, Excess,
+    ExecutionConfig, ExecutionStyle, InvariantViolation, KellySizing, MAX_HOLD_EXIT_REASON,
+    RMultipleStats, SizingBase, StopPlacement, StrategyRanges, SweepMetric, SweepOptions,
+    SweepOutcome, TRAILING_STOP_EXIT_REASON, TradingMetrics, ValidationReport, compute_drawdown,
+    compute_max_log_drawdown, crossover_strategy, find_best_strategy, generate_backtest_sweep_jobs,
+    generate_pullback_pairs, generate_random_strategies, generate_strategies,
+    generate_strategies_for_windows,
 };
@@ -1,17 +1,29 @@
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::PathBuf;
 
 use anyhow::Result;
-use chrono::{DateTime, Utc};
-use serde::Serialize;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::{Deserialize, Serialize};
 
-use crate::backtest::{Backtester, Candidate, TradingMetrics};
-use crate::data::Sample;
-use crate::indicators::compute_smas;
-use crate::signal::analyze;
+use crate::backtest::{
+    Backtester, Candidate, ExecutionConfig, MAX_HOLD_EXIT_REASON, StopPlacement,
+    TRAILING_STOP_EXIT_REASON, TradingMetrics,
+};
+use crate::data::{Sample, validate_dataset};
+use crate::indicators::RollingSma;
+use crate::indicators::sma::SmaConfig;
+use crate::patterns::recent_range;
+use crate::signal::{Action, SignalStrength, analyze};
+use crate::source::{PriceSource, collect_samples};
+use crate::units::Pct;
 
-use super::common::{Signal, suggestion_to_signal};
+use super::common::{
+    EdgeTracker, EquityMetrics, EquityRecorder, Fill, FillScheduler, LossStreak, PendingSignals,
+    PriceHistory, RMultipleStats, Signal, SignalPersistence, SignalReason, compute_cagr,
+    compute_profit_factor, compute_sharpe, holding_curve, print_benchmark_comparison,
+    print_r_stats, round_down_to_step, suggestion_to_signal,
+};
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Position {
@@ -21,15 +33,28 @@ pub struct Position {
     pub entry_price: f64,
     pub exit_price: Option<f64>,
     pub entry_reason: String,
+    /// Rule whose signal opened the position, as named in the decision trace.
+    pub rule: Option<String>,
     pub exit_reason: Option<String>,
     pub size: f64,
     pub profit: Option<f64>,
     pub return_pct: Option<f64>,
     /// Gross collateral removed from cash at entry (before entry fee).
     pub entry_collateral_gross: f64,
+    /// Maximum favorable excursion: best unrealized move in our favor, as a fraction of entry.
+    pub mfe_pct: f64,
+    /// Maximum adverse excursion: worst unrealized move against us, as a (positive) fraction of entry.
+    pub mae_pct: f64,
+    /// Stop level placed at entry (see `StopPlacement`), if the candidate has one.
+    pub stop_price: Option<f64>,
+    /// What the position stood to lose at the stop placed at entry (`size` times the
+    /// entry-to-stop distance). None without a stop on the losing side of the entry.
+    pub initial_risk: Option<f64>,
+    /// Profit in multiples of `initial_risk`; None without one.
+    pub r_multiple: Option<f64>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum PositionSide {
     Long,
     Short,
@@ -52,7 +77,35 @@ pub struct PositionBacktestResult {
     pub final_equity: f64,
     pub total_return_pct: f64,
     pub max_drawdown_pct: f64,
+    /// Largest fall below the initial equity (see `DrawdownKind::FromInitial`).
+    pub max_drawdown_from_initial_pct: f64,
+    /// Max drawdown on log-equity (see `compute_max_log_drawdown`).
+    pub max_log_drawdown: f64,
     pub win_rate_pct: f64,
+    /// Gross profit over gross loss (infinite without losses), and the mean profit of
+    /// winning and losing closed positions (the loss as a positive amount).
+    pub profit_factor: f64,
+    pub avg_win: f64,
+    pub avg_loss: f64,
+    /// R-multiple summary of closed positions that had a stop.
+    pub r_stats: Option<RMultipleStats>,
+    /// P&L of the position still open at the end (marked at the last price), if any.
+    pub unrealized_pnl: Option<f64>,
+    /// Mean MFE / MAE over closed positions.
+    pub avg_mfe_pct: f64,
+    pub avg_mae_pct: f64,
+    /// Candles past the SMA warmup (i.e. where the strategy could act).
+    pub usable_candles: usize,
+    /// Fewer usable candles than `min_usable_candles`; interpret with care.
+    pub partial: bool,
+    /// Usable candles the ATR filter held for lack of data (its period is longer
+    /// than the SMA warmup), so no rule could act on them.
+    pub atr_warmup_candles: usize,
+    /// Buy & hold over the same candles, run through the same metrics.
+    pub benchmark_metrics: Option<EquityMetrics>,
+    /// SMA windows the strategy ran with; a shorter long window than the candidate's
+    /// when `fit_long_window` had to shrink it.
+    pub effective_sma_config: SmaConfig,
 }
 
 fn position_liquidation_value(pos: &Position, price: f64) -> f64 {
@@ -69,6 +122,94 @@ fn position_liquidation_value(pos: &Position, price: f64) -> f64 {
     }
 }
 
+/// Widen the position's MFE/MAE with the current candle's price.
+fn update_excursions(pos: &mut Position, price: f64) {
+    if pos.entry_price <= 0.0 {
+        return;
+    }
+
+    let favorable = match pos.side {
+        PositionSide::Long => price / pos.entry_price - 1.0,
+        PositionSide::Short => 1.0 - price / pos.entry_price,
+    };
+    pos.mfe_pct = pos.mfe_pct.max(favorable);
+    pos.mae_pct = pos.mae_pct.max(-favorable);
+}
+
+/// Stop level for a position entered at `entry_price`; `prices` ends at the entry candle.
+/// None if there isn't enough history for a swing stop.
+fn stop_level(
+    placement: StopPlacement,
+    side: PositionSide,
+    entry_price: f64,
+    prices: &[f64],
+) -> Option<f64> {
+    match (placement, side) {
+        (StopPlacement::Percent(pct), PositionSide::Long) => Some(entry_price * (1.0 - pct)),
+        (StopPlacement::Percent(pct), PositionSide::Short) => Some(entry_price * (1.0 + pct)),
+        (StopPlacement::SwingLookback(lookback), side) => {
+            let (low, high) = recent_range(prices, lookback)?;
+            Some(match side {
+                PositionSide::Long => low,
+                PositionSide::Short => high,
+            })
+        }
+    }
+}
+
+/// What `pos` stands to lose at its stop: `size` times the entry-to-stop distance.
+/// None without a stop on the losing side of the entry.
+fn risk_to_stop(pos: &Position) -> Option<f64> {
+    let distance = match pos.side {
+        PositionSide::Long => pos.entry_price - pos.stop_price?,
+        PositionSide::Short => pos.stop_price? - pos.entry_price,
+    };
+    (distance > 0.0).then_some(distance * pos.size)
+}
+
+/// Add a TWAP slice to `pos`: `collateral` more (at most the cash left) at `price`,
+/// in whole `qty_step` lots. The entry price becomes the size-weighted average and the
+/// risk at entry follows it.
+fn add_to_position(
+    pos: &mut Position,
+    price: f64,
+    cash: &mut f64,
+    collateral: f64,
+    qty_step: Option<f64>,
+) {
+    if price <= 0.0 {
+        return;
+    }
+    let collateral = collateral.min(*cash);
+    let unrounded = collateral / price;
+    let size = round_down_to_step(unrounded, qty_step);
+    if size <= 0.0 {
+        return;
+    }
+    let collateral = if size < unrounded {
+        size * price
+    } else {
+        collateral
+    };
+    pos.entry_price = (pos.entry_price * pos.size + price * size) / (pos.size + size);
+    pos.size += size;
+    pos.entry_collateral_gross += collateral;
+    *cash -= collateral;
+    if pos.initial_risk.is_some() {
+        pos.initial_risk = risk_to_stop(pos);
+    }
+}
+
+/// Raise (long) or lower (short) the stop to the entry price, never loosening it.
+fn move_stop_to_breakeven(pos: &mut Position) {
+    let entry = pos.entry_price;
+    pos.stop_price = Some(match (pos.side, pos.stop_price) {
+        (PositionSide::Long, Some(stop)) => stop.max(entry),
+        (PositionSide::Short, Some(stop)) => stop.min(entry),
+        (_, None) => entry,
+    });
+}
+
 fn close_position(
     mut pos: Position,
     exit_price: f64,
@@ -93,6 +234,7 @@ fn close_position(
 
     pos.profit = Some(profit);
     pos.return_pct = Some(ret);
+    pos.r_multiple = pos.initial_risk.map(|risk| profit / risk);
     pos
 }
 
@@ -102,21 +244,25 @@ fn open_position(
     ts: DateTime<Utc>,
     cash: &mut f64,
     entry_frac: f64,
-    reason: String,
+    qty_step: Option<f64>,
+    reason: SignalReason,
 ) -> Option<Position> {
     if price <= 0.0 || *cash <= 0.0 || entry_frac <= 0.0 {
         return None;
     }
 
     let entry_collateral_gross = (*cash) * entry_frac;
-    if entry_collateral_gross <= 0.0 {
-        return None;
-    }
-
-    let size = entry_collateral_gross / price;
+    let unrounded = entry_collateral_gross / price;
+    let size = round_down_to_step(unrounded, qty_step);
     if size <= 0.0 {
         return None;
     }
+    // Whole lots only; the rest of the fraction stays in cash
+    let entry_collateral_gross = if size < unrounded {
+        size * price
+    } else {
+        entry_collateral_gross
+    };
 
     *cash -= entry_collateral_gross;
 
@@ -126,38 +272,21 @@ fn open_position(
         exit_time: None,
         entry_price: price,
         exit_price: None,
-        entry_reason: reason,
+        entry_reason: reason.text,
+        rule: reason.rule,
         exit_reason: None,
         size,
         entry_collateral_gross,
         profit: None,
         return_pct: None,
+        mfe_pct: 0.0,
+        mae_pct: 0.0,
+        stop_price: None,
+        initial_risk: None,
+        r_multiple: None,
     })
 }
 
-fn compute_max_drawdown(curve: &[(DateTime<Utc>, f64)]) -> f64 {
-    if curve.is_empty() {
-        return 0.0;
-    }
-
-    let mut peak = curve[0].1;
-    let mut max_dd = 0.0;
-
-    for &(_, equity) in curve {
-        if equity > peak {
-            peak = equity;
-        }
-        if peak > 0.0 {
-            let dd = (peak - equity) / peak;
-            if dd > max_dd {
-                max_dd = dd;
-            }
-        }
-    }
-
-    max_dd
-}
-
 fn compute_win_rate(positions: &[Position]) -> f64 {
     if positions.is_empty() {
         return 0.0;
@@ -171,34 +300,215 @@ fn compute_win_rate(positions: &[Position]) -> f64 {
     wins / positions.len() as f64
 }
 
-pub fn buy_and_hold_equity(hourly: &[Sample], initial_cash: f64) -> Option<f64> {
-    if hourly.is_empty() {
+/// Average (MFE, MAE) across positions.
+fn compute_avg_excursions(positions: &[Position]) -> (f64, f64) {
+    if positions.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let n = positions.len() as f64;
+    let mfe = positions.iter().map(|p| p.mfe_pct).sum::<f64>() / n;
+    let mae = positions.iter().map(|p| p.mae_pct).sum::<f64>() / n;
+    (mfe, mae)
+}
+
+/// Trade returns that fall into one hour-of-day or weekday bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SeasonBucket {
+    pub trades: usize,
+    /// Sum of the trades' `return_pct`.
+    pub total_return_pct: f64,
+}
+
+impl SeasonBucket {
+    pub fn avg_return_pct(&self) -> f64 {
+        if self.trades == 0 {
+            0.0
+        } else {
+            self.total_return_pct / self.trades as f64
+        }
+    }
+}
+
+/// Aggregate closed trades' returns by their entry candle's UTC hour (0-23) and
+/// weekday (0 = Monday .. 6 = Sunday).
+pub fn seasonality(result: &PositionBacktestResult) -> ([SeasonBucket; 24], [SeasonBucket; 7]) {
+    let mut by_hour = [SeasonBucket::default(); 24];
+    let mut by_weekday = [SeasonBucket::default(); 7];
+
+    for pos in &result.positions {
+        let Some(ret) = pos.return_pct else {
+            continue;
+        };
+        let hour = pos.entry_time.hour() as usize;
+        let weekday = pos.entry_time.weekday().num_days_from_monday() as usize;
+        for bucket in [&mut by_hour[hour], &mut by_weekday[weekday]] {
+            bucket.trades += 1;
+            bucket.total_return_pct += ret;
+        }
+    }
+
+    (by_hour, by_weekday)
+}
+
+/// Equity curve of putting all of `initial_cash` into the first candle and holding.
+pub fn buy_and_hold_curve(
+    hourly: &[Sample],
+    initial_cash: f64,
+) -> Option<Vec<(DateTime<Utc>, f64)>> {
+    let first = hourly.first()?.price;
+    if first <= 0.0 {
         return None;
     }
-    let first = hourly.first().unwrap().price;
-    let last = hourly.last().unwrap().price;
+    Some(holding_curve(hourly, initial_cash / first))
+}
+
+/// Final equity of buying at the first candle and holding to the last.
+/// With `qty_step` the quantity is rounded down to whole lots and the rest stays in cash.
+pub fn buy_and_hold_equity(
+    hourly: &[Sample],
+    initial_cash: f64,
+    qty_step: Option<f64>,
+) -> Option<f64> {
+    let first = hourly.first()?.price;
+    let last = hourly.last()?.price;
     if first <= 0.0 {
         return None;
     }
 
-    let qty = initial_cash / first;
-    Some(qty * last)
+    let qty = round_down_to_step(initial_cash / first, qty_step);
+    let leftover_cash = initial_cash - qty * first;
+    Some(qty * last + leftover_cash)
 }
 
 /// Simple CLI-style summary you can reuse in a binary.
-pub fn print_summary(result: &PositionBacktestResult) {
-    println!("=== Backtest Summary ===");
-    println!("Initial equity:  {:.2}", result.initial_equity);
-    println!("Final equity:     {:.2}", result.final_equity);
-    println!("Total return:     {:.2}%", result.total_return_pct * 100.0);
-    println!("Max drawdown:     {:.2}%", result.max_drawdown_pct * 100.0);
-    println!("Positions:           {}", result.positions.len());
-    println!("Win rate:         {:.2}%", result.win_rate_pct * 100.0);
+pub fn print_summary(out: &mut impl Write, result: &PositionBacktestResult) -> io::Result<()> {
+    writeln!(out, "=== Backtest Summary ===")?;
+    writeln!(out, "Initial equity:  {:.2}", result.initial_equity)?;
+    writeln!(out, "Final equity:     {:.2}", result.final_equity)?;
+    writeln!(out, "Total return:     {:#}", Pct(result.total_return_pct))?;
+    writeln!(out, "CAGR:             {:#}", Pct(result.cagr()))?;
+    writeln!(out, "Max drawdown:     {:#}", Pct(result.max_drawdown_pct))?;
+    writeln!(
+        out,
+        "DD from initial:  {:#}",
+        Pct(result.max_drawdown_from_initial_pct)
+    )?;
+    writeln!(out, "Max log drawdown: {:.4}", result.max_log_drawdown)?;
+    writeln!(out, "Positions:           {}", result.positions.len())?;
+    writeln!(out, "Win rate:         {:#}", Pct(result.win_rate_pct))?;
+    writeln!(out, "Profit factor:    {:.2}", result.profit_factor)?;
+    writeln!(out, "Avg win:          {:.2}", result.avg_win)?;
+    writeln!(out, "Avg loss:         {:.2}", result.avg_loss)?;
+    print_r_stats(out, result.r_stats)?;
+    if let Some(pnl) = result.unrealized_pnl {
+        writeln!(out, "Unrealized P&L:   {:.2}", pnl)?;
+    }
+    writeln!(out, "Avg MFE:          {:#}", Pct(result.avg_mfe_pct))?;
+    writeln!(out, "Avg MAE:          {:#}", Pct(result.avg_mae_pct))?;
+    if result.partial {
+        writeln!(
+            out,
+            "PARTIAL result:   only {} usable candles past warmup",
+            result.usable_candles
+        )?;
+    }
+    if result.atr_warmup_candles > 0 {
+        writeln!(
+            out,
+            "ATR warmup:       {} of {} usable candles held for lack of ATR data",
+            result.atr_warmup_candles, result.usable_candles
+        )?;
+    }
+    if let Some(benchmark) = &result.benchmark_metrics {
+        writeln!(out)?;
+        print_benchmark_comparison(
+            out,
+            &EquityMetrics::from_curve(&result.equity_curve),
+            benchmark,
+        )?;
+    }
+    Ok(())
+}
+
+/// What happens when a signal opposes the open position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReversalMode {
+    /// Close and open the opposite side on the same candle, at the same price.
+    #[default]
+    Immediate,
+    /// Only close; the opposite side opens on the next qualifying signal.
+    FlatFirst,
+}
+
+/// Exit reason of the position force-closed at the end of the data.
+pub const EOF_EXIT_REASON: &str = "EOF";
+
+/// Exit reasons of positions closed at their stop (`Candidate::stop`) or take-profit.
+pub const STOP_LOSS_EXIT_REASON: &str = "stop-loss";
+pub const TAKE_PROFIT_EXIT_REASON: &str = "take-profit";
+
+/// Take-profit and trailing stop levels, as fractions of the entry price (e.g. 0.05 =
+/// 5% above a long's entry, or below a short's, for the take-profit). The fixed stop
+/// is the candidate's `StopPlacement`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExitTargets {
+    pub take_profit_pct: Option<f64>,
+    /// Stop this fraction behind the best price since entry (the high for a long, the
+    /// low for a short). It follows the best price and never loosens.
+    pub trailing_stop_pct: Option<f64>,
+}
+
+impl ExitTargets {
+    /// Threshold price and exit reason if `price` is through `pos`'s stop or any of
+    /// these levels. Stops win over the take-profit (it can't be known which came first); between the
+    /// two stops, the tighter one was crossed first.
+    fn hit(&self, pos: &Position, price: f64) -> Option<(f64, &'static str)> {
+        let sign = match pos.side {
+            PositionSide::Long => 1.0,
+            PositionSide::Short => -1.0,
+        };
+        // The MFE only ever widens, so the best price never falls back
+        let best_price = pos.entry_price * (1.0 + sign * pos.mfe_pct);
+        let stop = pos.stop_price.map(|level| (level, STOP_LOSS_EXIT_REASON));
+        let trailing = self
+            .trailing_stop_pct
+            .map(|pct| (best_price * (1.0 - sign * pct), TRAILING_STOP_EXIT_REASON));
+        let protective = [stop, trailing]
+            .into_iter()
+            .flatten()
+            .filter(|&(level, _)| sign * (price - level) <= 0.0)
+            .max_by(|a, b| (sign * a.0).total_cmp(&(sign * b.0)));
+        let target = self
+            .take_profit_pct
+            .map(|pct| {
+                (
+                    pos.entry_price * (1.0 + sign * pct),
+                    TAKE_PROFIT_EXIT_REASON,
+                )
+            })
+            .filter(|&(level, _)| sign * (price - level) >= 0.0);
+        protective.or(target)
+    }
 }
 
 pub struct PositionBacktester<L> {
     initial_cash: f64,
     logger: L,
+    execution: ExecutionConfig,
+    reversal_mode: ReversalMode,
+    /// Leave the EOF-forced close out of win rate and MFE/MAE averages.
+    exclude_eof_from_stats: bool,
+    /// Only strong signals close or reverse a position; weak (bias-only) ones can
+    /// still open one from flat.
+    strong_exits_only: bool,
+    exit_targets: ExitTargets,
+    /// Once a position's unrealized gain reaches this fraction, its stop moves up
+    /// to the entry price (there are no fees to add here).
+    move_stop_to_breakeven_at_pct: Option<f64>,
+    /// Close a position this many candles after entry, whatever the signals say.
+    max_hold_candles: Option<usize>,
 }
 
 impl PositionBacktester<NoopLogger> {
@@ -206,6 +516,13 @@ impl PositionBacktester<NoopLogger> {
         Self {
             initial_cash,
             logger: NoopLogger,
+            execution: ExecutionConfig::default(),
+            reversal_mode: ReversalMode::default(),
+            exclude_eof_from_stats: false,
+            strong_exits_only: false,
+            exit_targets: ExitTargets::default(),
+            move_stop_to_breakeven_at_pct: None,
+            max_hold_candles: None,
         }
     }
 }
@@ -218,7 +535,236 @@ impl<L: PositionLogger> PositionBacktester<L> {
         Self {
             initial_cash,
             logger,
+            execution: ExecutionConfig::default(),
+            reversal_mode: ReversalMode::default(),
+            exclude_eof_from_stats: false,
+            strong_exits_only: false,
+            exit_targets: ExitTargets::default(),
+            move_stop_to_breakeven_at_pct: None,
+            max_hold_candles: None,
+        }
+    }
+
+    pub fn with_execution(mut self, execution: ExecutionConfig) -> Self {
+        self.execution = execution;
+        self
+    }
+
+    pub fn with_reversal_mode(mut self, reversal_mode: ReversalMode) -> Self {
+        self.reversal_mode = reversal_mode;
+        self
+    }
+
+    pub fn with_exclude_eof_from_stats(mut self, exclude: bool) -> Self {
+        self.exclude_eof_from_stats = exclude;
+        self
+    }
+
+    pub fn with_strong_exits_only(mut self, strong_only: bool) -> Self {
+        self.strong_exits_only = strong_only;
+        self
+    }
+
+    pub fn with_exit_targets(mut self, exit_targets: ExitTargets) -> Self {
+        self.exit_targets = exit_targets;
+        self
+    }
+
+    pub fn with_move_stop_to_breakeven_at_pct(mut self, pct: Option<f64>) -> Self {
+        self.move_stop_to_breakeven_at_pct = pct;
+        self
+    }
+
+    pub fn with_max_hold_candles(mut self, max_hold: Option<usize>) -> Self {
+        self.max_hold_candles = max_hold;
+        self
+    }
+}
+
+/// Cash plus the (at most one) open position, and everything closed so far.
+struct PositionBook {
+    cash: f64,
+    open: Option<Position>,
+    closed: Vec<Position>,
+    streak: LossStreak,
+    edge: EdgeTracker,
+    /// Candle the open position was entered on.
+    opened_at: usize,
+    /// Collateral of the whole order the open position is being built up by (TWAP);
+    /// None once no later slices should add to it.
+    order_collateral: Option<f64>,
+    /// Lot size position sizes are rounded down to.
+    qty_step: Option<f64>,
+}
+
+impl PositionBook {
+    fn equity(&self, price: f64) -> f64 {
+        self.cash
+            + self
+                .open
+                .as_ref()
+                .map(|p| position_liquidation_value(p, price))
+                .unwrap_or(0.0)
+    }
+
+    /// Whether `signal` points against the open position (if any).
+    fn opposes(&self, signal: Signal) -> bool {
+        self.open
+            .as_ref()
+            .is_some_and(|p| p.side != PositionSide::from(signal))
+    }
+
+    fn close_open<L: PositionLogger>(
+        &mut self,
+        price: f64,
+        ts: DateTime<Utc>,
+        reason: String,
+        logger: &L,
+    ) -> Result<(), String> {
+        if let Some(pos) = self.open.take() {
+            let closed_pos = close_position(pos, price, ts, reason);
+            logger.log(&closed_pos)?;
+            self.cash += closed_pos.entry_collateral_gross + closed_pos.profit.unwrap_or(0.0);
+            self.closed.push(closed_pos);
         }
+        Ok(())
+    }
+
+    /// Close the open position if `signal` points against it, without opening the
+    /// other side.
+    fn exit_against<L: PositionLogger>(
+        &mut self,
+        index: usize,
+        signal: Signal,
+        price: f64,
+        ts: DateTime<Utc>,
+        reason: String,
+        logger: &L,
+    ) -> Result<(), String> {
+        if !self.opposes(signal) {
+            return Ok(());
+        }
+
+        self.order_collateral = None;
+        self.close_open(price, ts, reason, logger)?;
+        self.record_last_close(index);
+        Ok(())
+    }
+
+    /// Close the open position at its stop, trailing stop or take-profit level if
+    /// `price` is through it.
+    fn check_exit_targets<L: PositionLogger>(
+        &mut self,
+        index: usize,
+        price: f64,
+        ts: DateTime<Utc>,
+        targets: &ExitTargets,
+        logger: &L,
+    ) -> Result<(), String> {
+        let Some((level, reason)) = self.open.as_ref().and_then(|pos| targets.hit(pos, price))
+        else {
+            return Ok(());
+        };
+
+        self.close_open(level, ts, reason.to_string(), logger)?;
+        self.record_last_close(index);
+        Ok(())
+    }
+
+    /// Close the open position once it's been held `max_hold` candles.
+    fn check_max_hold<L: PositionLogger>(
+        &mut self,
+        index: usize,
+        price: f64,
+        ts: DateTime<Utc>,
+        max_hold: usize,
+        logger: &L,
+    ) -> Result<(), String> {
+        if self.open.is_none() || index - self.opened_at < max_hold {
+            return Ok(());
+        }
+
+        self.close_open(price, ts, MAX_HOLD_EXIT_REASON.to_string(), logger)?;
+        self.record_last_close(index);
+        Ok(())
+    }
+
+    /// Feed the position just closed at candle `index` to the loss streak and edge tracker.
+    fn record_last_close(&mut self, index: usize) {
+        if let Some(closed) = self.closed.last() {
+            self.streak.record(closed.profit.unwrap_or(0.0), index);
+            self.edge.record(closed.return_pct.unwrap_or(0.0));
+        }
+    }
+
+    /// Reverse (or open) towards the fill's signal; a signal on the same side is a no-op.
+    /// With `ReversalMode::FlatFirst` an opposite signal only closes the open position.
+    /// While the loss streak is cooling down, nothing new is opened. With Kelly sizing
+    /// the recent trades' edge replaces `entry_frac`.
+    /// Only an order's first slice does this, opening its share of the position; later
+    /// slices (TWAP) add to the position it opened.
+    fn apply_fill<L: PositionLogger>(
+        &mut self,
+        index: usize,
+        fill: Fill,
+        ts: DateTime<Utc>,
+        entry_frac: f64,
+        reversal_mode: ReversalMode,
+        logger: &L,
+    ) -> Result<(), String> {
+        let want_side = fill.signal.into();
+        if !fill.is_first() {
+            if let Some(collateral) = self.order_collateral
+                && let Some(pos) = self.open.as_mut()
+                && pos.side == want_side
+            {
+                add_to_position(
+                    pos,
+                    fill.price,
+                    &mut self.cash,
+                    collateral * fill.share,
+                    self.qty_step,
+                );
+            }
+            return Ok(());
+        }
+
+        self.order_collateral = None;
+        let same_side = self
+            .open
+            .as_ref()
+            .map(|p| p.side == want_side)
+            .unwrap_or(false);
+        if same_side {
+            return Ok(());
+        }
+
+        // close old if exists
+        let had_open = self.open.is_some();
+        self.close_open(fill.price, ts, fill.reason.text.clone(), logger)?;
+        if had_open {
+            self.record_last_close(index);
+        }
+        if (had_open && reversal_mode == ReversalMode::FlatFirst) || self.streak.is_paused(index) {
+            return Ok(());
+        }
+        // open new
+        let entry_frac = self.edge.entry_fraction(entry_frac);
+        self.open = open_position(
+            want_side,
+            fill.price,
+            ts,
+            &mut self.cash,
+            entry_frac * fill.share,
+            self.qty_step,
+            fill.reason,
+        );
+        self.order_collateral = self
+            .open
+            .as_ref()
+            .map(|pos| pos.entry_collateral_gross / fill.share);
+        self.opened_at = index;
+        Ok(())
     }
 }
 
@@ -226,107 +772,322 @@ impl<L: PositionLogger> Backtester for PositionBacktester<L> {
     type Output = PositionBacktestResult;
     fn run_backtest(
         &self,
-        samples: &[Sample],
+        source: &mut dyn PriceSource,
         candidate: &Candidate,
     ) -> Result<Self::Output, String> {
-        if samples.len() < candidate.strategy.sma_config.long_window + 1 {
-            return Err("Not enough data".into());
+        let samples = collect_samples(source).map_err(|err| format!("{err:#}"))?;
+        self.run_samples(&samples, candidate)
+    }
+}
+
+/// Several strategies trading the same candles, each on its own share of the capital.
+#[derive(Debug, Clone)]
+pub struct CombinedBacktestResult {
+    pub initial_equity: f64,
+    /// Sum of the strategies' equity curves plus any unallocated cash.
+    pub equity_curve: Vec<(DateTime<Utc>, f64)>,
+    pub final_equity: f64,
+    pub metrics: EquityMetrics,
+    /// Each strategy's own result, in `candidates` order.
+    pub strategies: Vec<PositionBacktestResult>,
+}
+
+impl TradingMetrics for CombinedBacktestResult {
+    fn total_return_pct(&self) -> f64 {
+        self.metrics.total_return_pct
+    }
+
+    fn max_drawdown_pct(&self) -> f64 {
+        self.metrics.max_drawdown_pct
+    }
+
+    fn trade_count(&self) -> usize {
+        self.strategies.iter().map(|r| r.positions.len()).sum()
+    }
+
+    fn sharpe_ratio(&self) -> f64 {
+        self.metrics.sharpe
+    }
+
+    fn cagr(&self) -> f64 {
+        self.metrics.cagr_pct
+    }
+}
+
+impl<L: PositionLogger> PositionBacktester<L> {
+    /// Run every candidate on `alloc[i]` of the initial cash and merge the equity
+    /// curves into one. Allocations are positive fractions summing to at most 1; the
+    /// rest is held as cash.
+    pub fn run_combined(
+        &self,
+        samples: &[Sample],
+        candidates: &[Candidate],
+        alloc: &[f64],
+    ) -> Result<CombinedBacktestResult, String> {
+        if candidates.len() != alloc.len() {
+            return Err(format!(
+                "{} candidates but {} allocations",
+                candidates.len(),
+                alloc.len()
+            ));
+        }
+        if candidates.is_empty() {
+            return Err("no strategies to combine".to_string());
         }
+        // A zero share would run on no cash, and its returns would be 0/0
+        if alloc.iter().any(|a| a.is_nan() || *a <= 0.0) || alloc.iter().sum::<f64>() > 1.0 + 1e-9 {
+            return Err("allocations must be positive and sum to at most 1".to_string());
+        }
+
+        let strategies = candidates
+            .iter()
+            .zip(alloc)
+            .map(|(candidate, &a)| self.run_with_cash(samples, candidate, self.initial_cash * a))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let idle_cash = self.initial_cash * (1.0 - alloc.iter().sum::<f64>()).max(0.0);
+        // Every run records the same candles, so the curves line up point by point
+        let mut equity_curve: Vec<_> = strategies[0]
+            .equity_curve
+            .iter()
+            .map(|&(ts, _)| (ts, idle_cash))
+            .collect();
+        for result in &strategies {
+            for (point, &(_, equity)) in equity_curve.iter_mut().zip(&result.equity_curve) {
+                point.1 += equity;
+            }
+        }
+
+        Ok(CombinedBacktestResult {
+            initial_equity: self.initial_cash,
+            metrics: EquityMetrics::from_curve(&equity_curve),
+            final_equity: idle_cash + strategies.iter().map(|r| r.final_equity).sum::<f64>(),
+            equity_curve,
+            strategies,
+        })
+    }
+
+    /// `run_backtest` over candles already in memory.
+    pub fn run_samples(
+        &self,
+        samples: &[Sample],
+        candidate: &Candidate,
+    ) -> Result<PositionBacktestResult, String> {
+        self.run_with_cash(samples, candidate, self.initial_cash)
+    }
 
-        let initial_equity = self.initial_cash;
+    fn run_with_cash(
+        &self,
+        samples: &[Sample],
+        candidate: &Candidate,
+        initial_cash: f64,
+    ) -> Result<PositionBacktestResult, String> {
+        validate_dataset(samples).map_err(|err| err.to_string())?;
+        let candidate = &*self.execution.fit_candidate(candidate, samples.len());
+        let (usable_candles, partial) = self
+            .execution
+            .check_usable_candles(samples.len(), candidate.strategy.sma_config.long_window)?;
 
-        let mut prices: Vec<f64> = Vec::with_capacity(samples.len());
-        let mut equity_curve: Vec<(DateTime<Utc>, f64)> = Vec::with_capacity(samples.len());
-        let mut open: Option<Position> = None;
-        let mut closed: Vec<Position> = Vec::new();
+        let initial_equity = initial_cash;
+
+        let mut prices = PriceHistory::new(&self.execution, candidate, samples.len());
+        let mut rolling_smas = RollingSma::new(&candidate.strategy.sma_config);
+        let mut recorder = EquityRecorder::new(&self.execution, samples.len());
+        let mut pending = PendingSignals::default();
+        // Only entries wait for confirmation: an unconfirmed signal against the open
+        // position closes it without opening the other side
+        let mut persistence = SignalPersistence::new(&self.execution);
+        let mut pending_exits = PendingSignals::default();
+        let mut fills = FillScheduler::new(&self.execution);
 
         // Initial portfolio state
-        let mut cash = self.initial_cash;
+        let mut book = PositionBook {
+            cash: initial_cash,
+            open: None,
+            closed: Vec::new(),
+            streak: LossStreak::new(&self.execution),
+            edge: EdgeTracker::new(&self.execution),
+            opened_at: 0,
+            order_collateral: None,
+            qty_step: self.execution.qty_step,
+        };
 
         let buy_frac = candidate.buy_sell_fraction.clamp(0.0, 1.0);
+        let mut atr_warmup_candles = 0;
 
         for (i, candle) in samples.iter().enumerate() {
             let price = candle.price;
             prices.push(price);
+            let smas = rolling_smas.push(price);
 
-            let equity = cash
-                + open
-                    .as_ref()
-                    .map(|p| position_liquidation_value(p, price))
-                    .unwrap_or(0.0);
-            equity_curve.push((candle.ts, equity));
-
-            if prices.len() < candidate.strategy.sma_config.long_window + 1 {
-                // Not enough data yet for SMAs
-                continue;
+            recorder.push(candle.ts, book.equity(price));
+            if let Some(pos) = book.open.as_mut() {
+                update_excursions(pos, price);
+                if self
+                    .move_stop_to_breakeven_at_pct
+                    .is_some_and(|pct| pos.mfe_pct >= pct)
+                {
+                    move_stop_to_breakeven(pos);
+                }
+            }
+            book.check_exit_targets(i, price, candle.ts, &self.exit_targets, &self.logger)?;
+            if let Some(max_hold) = self.max_hold_candles {
+                book.check_max_hold(i, price, candle.ts, max_hold, &self.logger)?;
             }
 
-            let Some(smas) = compute_smas(&prices, candidate.strategy.sma_config) else {
-                continue;
-            };
-
-            let analysis = analyze(&samples[..=i], &prices, smas, candidate.strategy);
-            let signal = suggestion_to_signal(&analysis.suggestion);
-
-            match signal {
-                Some(signal) => {
-                    let want_side = signal.into();
-                    let same_side = open.as_ref().map(|p| p.side == want_side).unwrap_or(false);
-                    if !same_side {
-                        // close old if exists
-                        if let Some(pos) = open.take() {
-                            let closed_pos =
-                                close_position(pos, price, candle.ts, analysis.reason.clone());
-                            self.logger.log(&closed_pos)?;
-                            cash += closed_pos.entry_collateral_gross
-                                + closed_pos.profit.unwrap_or(0.0);
-                            closed.push(closed_pos);
-                        }
-                        // open new
-                        if let Some(pos) = open_position(
-                            want_side,
-                            price,
-                            candle.ts,
-                            &mut cash,
-                            buy_frac,
-                            analysis.reason,
-                        ) {
-                            open = Some(pos);
-                        }
-                    }
+            let mut suggestion = None;
+            // Not enough data yet for SMAs
+            if let Some(smas) = smas {
+                let analysis = analyze(
+                    &samples[..=i],
+                    prices.as_slice(),
+                    smas,
+                    &candidate.strategy,
+                    false,
+                )
+                .expect("candles up to i, with SMAs computed");
+                if analysis.held_for_atr_data() {
+                    atr_warmup_candles += 1;
                 }
-                _ => {
-                    // HOLD or suggestion that doesn't change position
+                let signal = suggestion_to_signal(&analysis.suggestion).filter(|&signal| {
+                    // A weak signal against the open position doesn't get to exit it
+                    !(self.strong_exits_only
+                        && analysis.strength == Some(SignalStrength::Weak)
+                        && book.opposes(signal))
+                });
+                // HOLD or suggestion that doesn't change position is dropped here
+                let confirmed = persistence.observe(signal);
+                if let Some(signal) = signal {
+                    let latency = self.execution.latency_candles;
+                    if confirmed {
+                        pending.push(i, latency, signal, SignalReason::of(&analysis));
+                    } else if book.opposes(signal) {
+                        pending_exits.push(i, latency, signal, SignalReason::of(&analysis));
+                    }
                 }
+                suggestion = Some(analysis.suggestion);
+            }
+
+            while let Some((signal, reason)) = pending_exits.pop_due(i) {
+                book.exit_against(i, signal, price, candle.ts, reason.text, &self.logger)?;
+            }
+            let mut due_fills = Vec::new();
+            while let Some((signal, reason)) = pending.pop_due(i) {
+                due_fills.extend(fills.submit(signal, reason, price));
             }
+            due_fills.extend(fills.advance(price));
+            for fill in due_fills {
+                book.apply_fill(
+                    i,
+                    fill,
+                    candle.ts,
+                    buy_frac,
+                    self.reversal_mode,
+                    &self.logger,
+                )?;
+            }
+            // Stops are fixed on the entry candle
+            if let Some(placement) = candidate.stop
+                && let Some(pos) = book.open.as_mut()
+                && pos.entry_time == candle.ts
+            {
+                pos.stop_price =
+                    stop_level(placement, pos.side, pos.entry_price, prices.as_slice());
+                pos.initial_risk = risk_to_stop(pos);
+            }
+            self.logger.log_candle(&CandleEvent {
+                ts: candle.ts,
+                price,
+                suggestion,
+                position: book.open.as_ref().map(|pos| pos.side),
+                cash: book.cash,
+                equity: book.equity(price),
+            })?;
         }
 
         // If a position is open close it
-        if let Some(pos) = open.take() {
-            let last = samples.last().unwrap();
-            let closed_pos = close_position(pos, last.price, last.ts, "EOF".to_string());
-            self.logger.log(&closed_pos)?;
-            cash += closed_pos.entry_collateral_gross + closed_pos.profit.unwrap_or(0.0);
-            closed.push(closed_pos);
-        }
-        let final_equity = cash;
+        let last = samples.last().unwrap();
+        let open_at_end = book.open.is_some();
+        book.close_open(
+            last.price,
+            last.ts,
+            EOF_EXIT_REASON.to_string(),
+            &self.logger,
+        )?;
+        let unrealized_pnl = if open_at_end {
+            book.closed.last().and_then(|p| p.profit)
+        } else {
+            None
+        };
+
+        let final_equity = book.cash;
         let total_return_pct = final_equity / initial_equity - 1.0;
 
-        let max_drawdown_pct = compute_max_drawdown(&equity_curve);
-        let win_rate_pct = compute_win_rate(&closed);
+        let (equity_curve, drawdowns) = recorder.finish();
+        let stats_positions = if self.exclude_eof_from_stats && open_at_end {
+            &book.closed[..book.closed.len() - 1]
+        } else {
+            &book.closed[..]
+        };
+        let win_rate_pct = compute_win_rate(stats_positions);
+        let profits: Vec<f64> = stats_positions.iter().filter_map(|p| p.profit).collect();
+        let (profit_factor, avg_win, avg_loss) = compute_profit_factor(&profits);
+        let r_multiples: Vec<f64> = stats_positions
+            .iter()
+            .filter_map(|p| p.r_multiple)
+            .collect();
+        let (avg_mfe_pct, avg_mae_pct) = compute_avg_excursions(stats_positions);
+        let benchmark_metrics = buy_and_hold_curve(samples, initial_equity)
+            .map(|curve| EquityMetrics::from_curve(&curve));
 
         Ok(PositionBacktestResult {
             initial_equity,
-            positions: closed,
+            positions: book.closed,
             equity_curve,
             final_equity,
             total_return_pct,
-            max_drawdown_pct,
+            max_drawdown_pct: drawdowns.max_drawdown_pct,
+            max_drawdown_from_initial_pct: drawdowns.max_drawdown_from_initial_pct,
+            max_log_drawdown: drawdowns.max_log_drawdown,
             win_rate_pct,
+            profit_factor,
+            avg_win,
+            avg_loss,
+            r_stats: RMultipleStats::from_r_multiples(&r_multiples),
+            unrealized_pnl,
+            avg_mfe_pct,
+            avg_mae_pct,
+            usable_candles,
+            partial,
+            atr_warmup_candles,
+            benchmark_metrics,
+            effective_sma_config: candidate.strategy.sma_config.clone(),
         })
     }
 }
 
+impl PositionBacktestResult {
+    /// What the run executed, in time order: BUY to open a long or close a short, SELL
+    /// to open a short or close a long. The close forced at the end of the data isn't
+    /// an execution, and TWAP entries show up once, at their first slice.
+    pub fn executed_actions(&self) -> Vec<(DateTime<Utc>, Action)> {
+        let mut actions = Vec::new();
+        for pos in &self.positions {
+            let (open, close) = match pos.side {
+                PositionSide::Long => (Action::Buy, Action::Sell),
+                PositionSide::Short => (Action::Sell, Action::Buy),
+            };
+            actions.push((pos.entry_time, open));
+            if let Some(exit_time) = pos.exit_time
+                && pos.exit_reason.as_deref() != Some(EOF_EXIT_REASON)
+            {
+                actions.push((exit_time, close));
+            }
+        }
+        actions
+    }
+}
+
 impl TradingMetrics for PositionBacktestResult {
     fn total_return_pct(&self) -> f64 {
         self.total_return_pct
@@ -335,10 +1096,54 @@ impl TradingMetrics for PositionBacktestResult {
     fn max_drawdown_pct(&self) -> f64 {
         self.max_drawdown_pct
     }
+
+    fn trade_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn sharpe_ratio(&self) -> f64 {
+        compute_sharpe(&self.equity_curve)
+    }
+
+    fn cagr(&self) -> f64 {
+        compute_cagr(&self.equity_curve)
+    }
 }
 
 pub trait PositionLogger: Sync {
     fn log(&self, position: &Position) -> Result<(), String>;
+
+    /// Called once per candle, after that candle's fills. Off unless overridden.
+    fn log_candle(&self, _event: &CandleEvent) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// State of a position backtest at the close of one candle.
+#[derive(Debug, Clone, Serialize)]
+pub struct CandleEvent {
+    pub ts: DateTime<Utc>,
+    pub price: f64,
+    /// The analysis's suggestion; None while the SMAs are warming up.
+    pub suggestion: Option<String>,
+    /// Side of the position held after this candle's fills.
+    pub position: Option<PositionSide>,
+    pub cash: f64,
+    pub equity: f64,
+}
+
+/// Sends every `CandleEvent` to a callback; positions aren't logged.
+pub struct CandleCallback<F>(pub F);
+
+impl<F: Fn(&CandleEvent) + Sync> PositionLogger for CandleCallback<F> {
+    fn log(&self, _pos: &Position) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn log_candle(&self, event: &CandleEvent) -> Result<(), String> {
+        (self.0)(event);
+        Ok(())
+    }
 }
 
 pub struct NdjsonLogger {
@@ -371,3 +1176,929 @@ impl PositionLogger for NoopLogger {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    use crate::backtest::ExecutionStyle;
+    use crate::backtest::common::test_utils::{bias_only_strategy, hourly_samples};
+    use crate::indicators::{AtrFilter, RegimeFilter};
+    use crate::signal::{
+        BreakoutConfig, BreakoutTrigger, FilterConfig, PullbackConfig, PullbackToleranceMode,
+        StrategyConfig,
+    };
+    use crate::stats::stddev;
+
+    fn candidate() -> Candidate {
+        Candidate {
+            buy_sell_fraction: 0.5,
+            strategy: bias_only_strategy(),
+            stop: None,
+        }
+    }
+
+    #[test]
+    fn test_latency_shifts_fills_forward_by_one_candle() {
+        // SMA(2) > SMA(3) from index 3 onwards -> BUY signals from index 3.
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0];
+        let samples = hourly_samples(&prices);
+
+        let immediate = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        let delayed = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                latency_candles: 1,
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let first = &immediate.positions[0];
+        let first_delayed = &delayed.positions[0];
+
+        assert_eq!(first.entry_time, samples[3].ts);
+        assert_eq!(first.entry_price, 11.0);
+        assert_eq!(first_delayed.entry_time, samples[4].ts);
+        assert_eq!(first_delayed.entry_price, 12.0);
+    }
+
+    #[test]
+    fn test_run_backtest_reads_a_price_source() {
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 9.0, 8.0]);
+        let backtester = PositionBacktester::new(1_000.0);
+        let in_memory = backtester.run_samples(&samples, &candidate()).unwrap();
+
+        let mut source = crate::source::VecSource::new(samples.clone());
+        let streamed = backtester.run_backtest(&mut source, &candidate()).unwrap();
+        assert_eq!(streamed.positions.len(), in_memory.positions.len());
+        assert_eq!(streamed.final_equity, in_memory.final_equity);
+        assert_eq!(streamed.equity_curve, in_memory.equity_curve);
+
+        struct FailingSource(usize);
+        impl PriceSource for FailingSource {
+            fn next_sample(&mut self) -> Option<Result<Sample>> {
+                self.0 += 1;
+                Some(if self.0 < 3 {
+                    Ok(hourly_samples(&[10.0; 3]).swap_remove(self.0))
+                } else {
+                    Err(anyhow::anyhow!("feed dropped"))
+                })
+            }
+        }
+        let err = backtester
+            .run_backtest(&mut FailingSource(0), &candidate())
+            .unwrap_err();
+        assert_eq!(err, "feed dropped");
+    }
+
+    #[test]
+    fn test_fit_long_window_shrinks_a_window_the_data_cant_warm_up() {
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0]);
+        let candidate = Candidate {
+            buy_sell_fraction: 0.5,
+            strategy: StrategyConfig {
+                sma_config: SmaConfig::new(2, 100),
+                ..bias_only_strategy()
+            },
+            stop: None,
+        };
+
+        assert!(
+            PositionBacktester::new(1_000.0)
+                .run_samples(&samples, &candidate)
+                .is_err()
+        );
+
+        let result = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                fit_long_window: true,
+                min_usable_candles: 2,
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate)
+            .unwrap();
+        // Largest window leaving two usable candles
+        assert_eq!(result.effective_sma_config.long_window, 4);
+        assert_eq!(result.effective_sma_config.short_window, 2);
+        assert_eq!(result.usable_candles, 2);
+        assert!(!result.partial);
+        assert_eq!(result.positions.len(), 1);
+    }
+
+    #[test]
+    fn test_empty_and_single_candle_inputs_are_clean_errors() {
+        let backtester = PositionBacktester::new(1_000.0);
+        assert_eq!(
+            backtester.run_samples(&[], &candidate()).unwrap_err(),
+            "no samples"
+        );
+        assert_eq!(
+            backtester
+                .run_samples(&hourly_samples(&[10.0]), &candidate())
+                .unwrap_err(),
+            "not enough data: 1 samples, need at least 4"
+        );
+        assert_eq!(buy_and_hold_equity(&[], 1_000.0, None), None);
+        assert_eq!(buy_and_hold_curve(&[], 1_000.0), None);
+    }
+
+    #[test]
+    fn test_partial_result_when_just_past_warmup() {
+        // long_window = 3 -> 4 candles leave exactly one usable candle
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0]);
+        let execution = ExecutionConfig {
+            min_usable_candles: 10,
+            ..Default::default()
+        };
+
+        let strict = PositionBacktester::new(1_000.0)
+            .with_execution(execution)
+            .run_samples(&samples, &candidate());
+        assert!(strict.is_err());
+
+        let result = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                allow_partial: true,
+                ..execution
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        assert!(result.partial);
+        assert_eq!(result.usable_candles, 1);
+        assert_eq!(result.positions.len(), 1);
+    }
+
+    #[test]
+    fn test_benchmark_metrics_return_matches_buy_and_hold_equity() {
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0];
+        let samples = hourly_samples(&prices);
+
+        let result = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let benchmark = result.benchmark_metrics.expect("non-empty samples");
+        let hold_equity = buy_and_hold_equity(&samples, 1_000.0, None).unwrap();
+        assert!((benchmark.total_return_pct - (hold_equity / 1_000.0 - 1.0)).abs() < 1e-12);
+        assert_eq!(benchmark.max_drawdown_pct, 0.0);
+        assert!(benchmark.sharpe > 0.0);
+    }
+
+    #[test]
+    fn test_sub_cent_prices_trade_like_their_scaled_up_series() {
+        // Same shape as a normal series, scaled down to ~0.00000123
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 10.0, 9.0, 8.0];
+        let scale = 0.000_000_123;
+        let micro: Vec<f64> = prices.iter().map(|p| p * scale).collect();
+
+        let normal = PositionBacktester::new(1_000.0)
+            .run_samples(&hourly_samples(&prices), &candidate())
+            .unwrap();
+        let tiny = PositionBacktester::new(1_000.0)
+            .run_samples(&hourly_samples(&micro), &candidate())
+            .unwrap();
+
+        assert!(!tiny.positions.is_empty());
+        assert_eq!(tiny.positions.len(), normal.positions.len());
+        for (t, n) in tiny.positions.iter().zip(&normal.positions) {
+            assert!(t.size.is_finite() && t.size > 0.0);
+            assert!((t.size * scale / n.size - 1.0).abs() < 1e-9);
+
+            let profit = t.profit.unwrap();
+            assert!(profit != 0.0);
+            assert!((profit - n.profit.unwrap()).abs() < 1e-6);
+            assert!((t.return_pct.unwrap() - n.return_pct.unwrap()).abs() < 1e-9);
+        }
+        assert!((tiny.total_return_pct - normal.total_return_pct).abs() < 1e-9);
+        assert!((tiny.win_rate_pct - normal.win_rate_pct).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_stop_loss_closes_a_long_at_the_stop_level() {
+        // Long at 3 (price 11); the dip to 10.5 at 6 is through a 3% stop (10.67)
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5, 16.0, 17.0]);
+        let result = PositionBacktester::new(1_000.0)
+            .with_exit_targets(ExitTargets {
+                take_profit_pct: Some(0.5),
+                trailing_stop_pct: None,
+            })
+            .run_samples(
+                &samples,
+                &Candidate {
+                    stop: Some(StopPlacement::Percent(0.03)),
+                    ..candidate()
+                },
+            )
+            .unwrap();
+
+        let first = &result.positions[0];
+        assert_eq!(first.side, PositionSide::Long);
+        assert_eq!(first.exit_time, Some(samples[6].ts));
+        assert_eq!(first.exit_reason.as_deref(), Some(STOP_LOSS_EXIT_REASON));
+        assert!((first.exit_price.unwrap() - 11.0 * 0.97).abs() < 1e-12);
+        assert!(first.profit.unwrap() < 0.0);
+        // The SELL on the same candle still opens a short from flat
+        assert_eq!(result.positions[1].side, PositionSide::Short);
+        assert_eq!(result.positions[1].entry_time, samples[6].ts);
+    }
+
+    #[test]
+    fn test_trailing_stop_follows_the_best_price_and_never_loosens() {
+        // Long at 3 (11), high of 16 at 5: a 10% trail sits at 14.4 from then on.
+        // 15 at 6 holds, the bounce to 15.5 doesn't lower it, 14 at 8 is through it.
+        // Strong exits only, so the opposite bias signals leave the trail to it.
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 13.0, 16.0, 15.0, 15.5, 14.0]);
+        let targets = ExitTargets {
+            trailing_stop_pct: Some(0.1),
+            ..Default::default()
+        };
+        let result = PositionBacktester::new(1_000.0)
+            .with_exit_targets(targets)
+            .with_strong_exits_only(true)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let first = &result.positions[0];
+        assert_eq!(first.side, PositionSide::Long);
+        assert_eq!(first.exit_time, Some(samples[8].ts));
+        assert_eq!(
+            first.exit_reason.as_deref(),
+            Some(TRAILING_STOP_EXIT_REASON)
+        );
+        assert!((first.exit_price.unwrap() - 14.4).abs() < 1e-9);
+
+        // Short at 3 (9), low of 6 at 5: the trail at 6.6 is crossed at 7
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 9.0, 7.0, 6.0, 6.5, 7.0]);
+        let result = PositionBacktester::new(1_000.0)
+            .with_exit_targets(targets)
+            .with_strong_exits_only(true)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        let first = &result.positions[0];
+        assert_eq!(first.side, PositionSide::Short);
+        assert_eq!(first.exit_time, Some(samples[7].ts));
+        assert!((first.exit_price.unwrap() - 6.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_r_multiple_is_profit_over_the_risk_to_the_stop() {
+        // Long at 3 (price 11) with a 10% stop (9.9): 1.1 of risk a unit. The 10%
+        // take-profit at 12.1 gains exactly that.
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0]);
+        let with_stop = Candidate {
+            stop: Some(StopPlacement::Percent(0.1)),
+            ..candidate()
+        };
+        let result = PositionBacktester::new(1_000.0)
+            .with_exit_targets(ExitTargets {
+                take_profit_pct: Some(0.1),
+                trailing_stop_pct: None,
+            })
+            .run_samples(&samples, &with_stop)
+            .unwrap();
+
+        let first = &result.positions[0];
+        assert_eq!(first.exit_reason.as_deref(), Some(TAKE_PROFIT_EXIT_REASON));
+        assert!((first.initial_risk.unwrap() - 1.1 * first.size).abs() < 1e-9);
+        assert!((first.r_multiple.unwrap() - 1.0).abs() < 1e-9);
+        let stats = result.r_stats.unwrap();
+        assert_eq!(stats.trades, result.positions.len());
+        assert!((stats.avg_win_r - 1.0).abs() < 1e-9);
+
+        // No stop, no R
+        let without = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        assert_eq!(without.positions[0].r_multiple, None);
+        assert!(without.r_stats.is_none());
+    }
+
+    #[test]
+    fn test_take_profit_closes_a_short_below_entry() {
+        // Short at 3 (price 9); the target 10% below entry (8.1) is reached at 4
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 9.0, 8.0, 8.5]);
+        let result = PositionBacktester::new(1_000.0)
+            .with_exit_targets(ExitTargets {
+                take_profit_pct: Some(0.1),
+                trailing_stop_pct: None,
+            })
+            .run_samples(
+                &samples,
+                &Candidate {
+                    stop: Some(StopPlacement::Percent(0.05)),
+                    ..candidate()
+                },
+            )
+            .unwrap();
+
+        let first = &result.positions[0];
+        assert_eq!(first.side, PositionSide::Short);
+        assert_eq!(first.exit_time, Some(samples[4].ts));
+        assert_eq!(first.exit_reason.as_deref(), Some(TAKE_PROFIT_EXIT_REASON));
+        assert!((first.exit_price.unwrap() - 8.1).abs() < 1e-12);
+        // 0.1 * 500 collateral
+        assert!((first.profit.unwrap() - 50.0).abs() < 1e-9);
+        assert!(result.win_rate_pct > 0.0);
+    }
+
+    #[test]
+    fn test_flat_first_reversal_waits_for_next_signal() {
+        // SMA(2) vs SMA(3): BUY from index 3, a single SELL at index 6, BUY again from index 7.
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5, 16.0, 17.0];
+        let samples = hourly_samples(&prices);
+
+        let immediate = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        let flat_first = PositionBacktester::new(1_000.0)
+            .with_reversal_mode(ReversalMode::FlatFirst)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        // Immediate: long 3->6, short 6->7, long 7->EOF
+        let sides: Vec<PositionSide> = immediate.positions.iter().map(|p| p.side).collect();
+        assert_eq!(
+            sides,
+            [PositionSide::Long, PositionSide::Short, PositionSide::Long]
+        );
+        assert_eq!(immediate.positions[1].entry_time, samples[6].ts);
+
+        // FlatFirst: long 3->6, flat on 6, long again 7->EOF
+        let sides: Vec<PositionSide> = flat_first.positions.iter().map(|p| p.side).collect();
+        assert_eq!(sides, [PositionSide::Long, PositionSide::Long]);
+        assert_eq!(flat_first.positions[0].exit_time, Some(samples[6].ts));
+        assert_eq!(flat_first.positions[1].entry_time, samples[7].ts);
+    }
+
+    #[test]
+    fn test_loss_streak_pauses_entries_for_cooldown() {
+        // SMA(2) vs SMA(3) whipsaw: long 3->6, short 6->8, long 8->10, short 10->12 all lose,
+        // then a clean uptrend with a BUY at index 12.
+        let prices = [
+            10.0, 10.0, 10.0, 11.0, 12.0, 10.0, 9.0, 11.0, 12.0, 10.0, 9.0, 11.0, 12.0, 13.0, 14.0,
+            15.0, 16.0, 17.0,
+        ];
+        let samples = hourly_samples(&prices);
+
+        let unguarded = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        assert_eq!(unguarded.positions.len(), 5);
+        assert!(
+            unguarded.positions[..4]
+                .iter()
+                .all(|p| p.profit.unwrap() < 0.0)
+        );
+
+        // Two losses (closed at index 8) -> no entries on candles 8..=11
+        let guarded = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                max_consecutive_losses: 2,
+                cooldown_candles: 3,
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let entries: Vec<_> = guarded.positions.iter().map(|p| p.entry_time).collect();
+        assert_eq!(entries, [samples[3].ts, samples[6].ts, samples[12].ts]);
+        assert_eq!(guarded.positions[1].exit_time, Some(samples[8].ts));
+        assert_eq!(guarded.positions[2].side, PositionSide::Long);
+        assert!(guarded.positions[2].profit.unwrap() > 0.0);
+        assert!(guarded.final_equity > unguarded.final_equity);
+    }
+
+    #[test]
+    fn test_confirm_candles_ignores_single_candle_blips() {
+        // SMA(2) vs SMA(3): BUY@3, HOLD@4, SELL@5, then BUY from index 6 on
+        let prices = [10.0, 10.0, 10.0, 11.0, 9.0, 9.0, 11.0, 12.0, 13.0];
+        let samples = hourly_samples(&prices);
+
+        let immediate = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        assert_eq!(immediate.positions.len(), 3);
+
+        let confirmed = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                confirm_candles: 2,
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        // Only the BUY seen on 6 and 7 goes through, filled on the second candle
+        assert_eq!(confirmed.positions.len(), 1);
+        assert_eq!(confirmed.positions[0].side, PositionSide::Long);
+        assert_eq!(confirmed.positions[0].entry_time, samples[7].ts);
+        assert_eq!(confirmed.positions[0].entry_price, 12.0);
+    }
+
+    #[test]
+    fn test_confirm_candles_exits_on_an_unconfirmed_opposite_signal() {
+        // BUY@3 and 4, a one-candle SELL@5, then BUY again from 6 on
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 9.0, 16.0, 17.0, 18.0];
+        let samples = hourly_samples(&prices);
+
+        let result = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                confirm_candles: 2,
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        // The SELL closes the long right away but never opens a short; the next long
+        // waits for its BUY to be confirmed on 7
+        assert_eq!(result.positions.len(), 2);
+        let (first, second) = (&result.positions[0], &result.positions[1]);
+        assert_eq!(first.side, PositionSide::Long);
+        assert_eq!(first.entry_price, 12.0);
+        assert_eq!(first.exit_time, Some(samples[5].ts));
+        assert_eq!(first.exit_price, Some(9.0));
+        assert_eq!(second.side, PositionSide::Long);
+        assert_eq!(second.entry_time, samples[7].ts);
+    }
+
+    #[test]
+    fn test_position_records_mfe_and_mae() {
+        // Long opens at 11 (index 3), dips to 10.5 while still a BUY, then runs to 14
+        let prices = [10.0, 10.0, 10.0, 11.0, 10.5, 13.0, 14.0];
+        let samples = hourly_samples(&prices);
+
+        let result = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        assert_eq!(result.positions.len(), 1);
+        let long = &result.positions[0];
+        assert_eq!(long.entry_price, 11.0);
+        assert_eq!(long.exit_price, Some(14.0));
+        assert!(long.profit.unwrap() > 0.0);
+        assert!((long.mae_pct - 0.5 / 11.0).abs() < 1e-12);
+        assert!((long.mfe_pct - 3.0 / 11.0).abs() < 1e-12);
+        assert!((result.avg_mae_pct - long.mae_pct).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_excluding_eof_close_from_win_rate() {
+        // long 3->6 and short 6->7 lose, the long from 7 is only closed by EOF (in profit)
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5, 16.0, 17.0];
+        let samples = hourly_samples(&prices);
+
+        let counted = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        let excluded = PositionBacktester::new(1_000.0)
+            .with_exclude_eof_from_stats(true)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        assert!((counted.win_rate_pct - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(excluded.win_rate_pct, 0.0);
+
+        // The EOF position is still reported, and its P&L shows up as unrealized
+        assert_eq!(excluded.positions.len(), 3);
+        let eof = excluded.positions.last().unwrap();
+        assert_eq!(eof.exit_reason.as_deref(), Some(EOF_EXIT_REASON));
+        assert_eq!(excluded.unrealized_pnl, eof.profit);
+        assert!(excluded.unrealized_pnl.unwrap() > 0.0);
+        assert_eq!(excluded.final_equity, counted.final_equity);
+    }
+
+    #[test]
+    fn test_buy_and_hold_equity_respects_qty_step() {
+        let samples = hourly_samples(&[10.0, 12.0, 14.0]);
+
+        // 1000 / 10 = 100 units unrounded; lots of 30 -> 90 units and 100 left in cash
+        let fractional = buy_and_hold_equity(&samples, 1_000.0, None).unwrap();
+        let lots = buy_and_hold_equity(&samples, 1_000.0, Some(30.0)).unwrap();
+
+        assert!((fractional - 1_400.0).abs() < 1e-9);
+        assert!((lots - (90.0 * 14.0 + 100.0)).abs() < 1e-9);
+        assert!(lots < fractional);
+    }
+
+    #[test]
+    fn test_qty_step_opens_whole_lots_and_keeps_the_rest_in_cash() {
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0]);
+
+        let result = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                qty_step: Some(10.0),
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        // Half the cash buys 500 / 11 = 45.5 units, rounded down to 4 lots of 10
+        let pos = &result.positions[0];
+        assert_eq!(pos.entry_price, 11.0);
+        assert_eq!(pos.size, 40.0);
+        assert!((pos.entry_collateral_gross - 440.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_twap_entry_fills_each_slice_at_its_own_candle() {
+        // BUY due at index 3; TWAP-3 adds a third of the order at 11, 12 and 13
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0];
+        let samples = hourly_samples(&prices);
+
+        let result = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                style: ExecutionStyle::Twap { candles: 3 },
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let entry = &result.positions[0];
+        assert_eq!(entry.side, PositionSide::Long);
+        assert_eq!(entry.entry_time, samples[3].ts);
+        let slice = 500.0 / 3.0;
+        let size = slice / 11.0 + slice / 12.0 + slice / 13.0;
+        assert!((entry.size - size).abs() < 1e-9);
+        assert!((entry.entry_collateral_gross - 500.0).abs() < 1e-9);
+        assert!((entry.entry_price - 500.0 / size).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_twap_opposite_signal_cancels_the_rest_of_the_order() {
+        // BUY due at 3 fills slices at 11 and 12; the SELL due at 5 cancels the third,
+        // closes the two-slice long and works a short over 9, 8 and 7
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 9.0, 8.0, 7.0];
+        let samples = hourly_samples(&prices);
+
+        let result = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                style: ExecutionStyle::Twap { candles: 3 },
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        assert_eq!(result.positions.len(), 2);
+        let long = &result.positions[0];
+        let slice = 500.0 / 3.0;
+        assert!((long.size - (slice / 11.0 + slice / 12.0)).abs() < 1e-9);
+        assert_eq!(long.exit_time, Some(samples[5].ts));
+        assert_eq!(long.exit_price, Some(9.0));
+
+        let short = &result.positions[1];
+        assert_eq!(short.side, PositionSide::Short);
+        assert_eq!(short.entry_time, samples[5].ts);
+        let slice = short.entry_collateral_gross / 3.0;
+        assert!((short.size - (slice / 9.0 + slice / 8.0 + slice / 7.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_seasonality_buckets_by_entry_hour_and_weekday() {
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5, 16.0, 17.0];
+        let mut result = PositionBacktester::new(1_000.0)
+            .run_samples(&hourly_samples(&prices), &candidate())
+            .unwrap();
+        assert_eq!(result.positions.len(), 3);
+
+        // Two Monday 09:00 entries (a week apart) and one Friday 15:00 entry
+        let monday = Utc.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap();
+        result.positions[0].entry_time = monday;
+        result.positions[1].entry_time = monday + chrono::Duration::weeks(1);
+        result.positions[2].entry_time = Utc.with_ymd_and_hms(2025, 1, 10, 15, 0, 0).unwrap();
+        let ret = |i: usize| result.positions[i].return_pct.unwrap();
+
+        let (by_hour, by_weekday) = seasonality(&result);
+
+        assert_eq!(by_hour[9].trades, 2);
+        assert!((by_hour[9].total_return_pct - (ret(0) + ret(1))).abs() < 1e-12);
+        assert!((by_hour[9].avg_return_pct() - (ret(0) + ret(1)) / 2.0).abs() < 1e-12);
+        assert_eq!(by_hour[15].trades, 1);
+        assert_eq!(by_hour.iter().map(|b| b.trades).sum::<usize>(), 3);
+
+        assert_eq!(by_weekday[0].trades, 2);
+        assert_eq!(by_weekday[4].trades, 1);
+        assert!((by_weekday[4].total_return_pct - ret(2)).abs() < 1e-12);
+        assert_eq!(by_weekday[2], SeasonBucket::default());
+    }
+
+    #[test]
+    fn test_strong_exits_only_ignores_opposite_bias_signal() {
+        // SMA(2) vs SMA(3): crosses up at 3, down at 6 and up again at 7
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5, 16.0, 17.0];
+        let samples = hourly_samples(&prices);
+        let backtester = PositionBacktester::new(1_000.0).with_strong_exits_only(true);
+
+        // Bias only: the long opened at 3 rides through the SELL at 6 until EOF
+        let bias_only = backtester.run_samples(&samples, &candidate()).unwrap();
+        assert_eq!(bias_only.positions.len(), 1);
+        assert_eq!(bias_only.positions[0].entry_time, samples[3].ts);
+        assert_eq!(
+            bias_only.positions[0].exit_reason.as_deref(),
+            Some(EOF_EXIT_REASON)
+        );
+
+        // The death cross at 6 is strong and reverses into a short
+        let mut with_crossovers = candidate();
+        with_crossovers.strategy.enable_crossovers = true;
+        let crossed = backtester.run_samples(&samples, &with_crossovers).unwrap();
+        assert_eq!(crossed.positions.len(), 3);
+        assert_eq!(crossed.positions[1].side, PositionSide::Short);
+        assert_eq!(crossed.positions[1].entry_time, samples[6].ts);
+    }
+
+    #[test]
+    fn test_bounded_history_matches_full_retention() {
+        let prices: Vec<f64> = (0..400)
+            .map(|i| {
+                let t = i as f64;
+                100.0 + 10.0 * (t / 15.0).sin() + 5.0 * (t / 4.0).sin() + 0.05 * t
+            })
+            .collect();
+        let samples = hourly_samples(&prices);
+        let mut candidate = candidate();
+        candidate.strategy.sma_config = SmaConfig::new(5, 20);
+        candidate.strategy.enable_crossovers = true;
+        candidate.strategy.breakouts = Some(BreakoutConfig {
+            breakout_lookback: 8,
+            min_trend_bars: 3,
+            require_retest: None,
+            breakout_trigger: BreakoutTrigger::Close,
+        });
+        candidate.strategy.pullbacks = Some(PullbackConfig {
+            bounce_tolerance_pct: 0.003,
+            reject_tolerance_pct: 0.003,
+            tolerance_mode: PullbackToleranceMode::Atr,
+        });
+        candidate.strategy.filters.regime = Some(RegimeFilter {
+            long_window: 30,
+            slope_window: 10,
+            min_trend_strength: 0.0,
+            min_range: 0.0,
+            smooth_slope: false,
+        });
+        assert_eq!(candidate.strategy.required_history(), Some(31));
+
+        let full = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate)
+            .unwrap();
+        let bounded = PositionBacktester::new(1_000.0)
+            .with_execution(ExecutionConfig {
+                bounded_history: true,
+                equity_curve_stride: 24,
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate)
+            .unwrap();
+
+        assert!(full.positions.len() > 5);
+        assert_eq!(bounded.positions.len(), full.positions.len());
+        for (a, b) in bounded.positions.iter().zip(&full.positions) {
+            assert_eq!(
+                (a.side, a.entry_time, a.exit_time),
+                (b.side, b.entry_time, b.exit_time)
+            );
+        }
+        assert_eq!(bounded.final_equity, full.final_equity);
+        assert_eq!(bounded.max_drawdown_pct, full.max_drawdown_pct);
+        assert_eq!(bounded.max_log_drawdown, full.max_log_drawdown);
+
+        // One point per day plus the last candle
+        assert_eq!(bounded.equity_curve.len(), 400 / 24 + 2);
+        assert_eq!(bounded.equity_curve.last(), full.equity_curve.last());
+    }
+
+    #[test]
+    fn test_swing_stop_sits_at_prior_low_and_exits_when_broken() {
+        // Long at 3 (price 11) with the stop at the low of the 3 candles before it
+        let with_stop = Candidate {
+            stop: Some(StopPlacement::SwingLookback(3)),
+            ..candidate()
+        };
+
+        // The dip to 10.5 holds above the swing low: the bias flip closes it
+        let held = PositionBacktester::new(1_000.0)
+            .run_samples(
+                &hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5]),
+                &with_stop,
+            )
+            .unwrap();
+        let first = &held.positions[0];
+        assert_eq!(first.stop_price, Some(10.0));
+        assert_ne!(first.exit_reason.as_deref(), Some(STOP_LOSS_EXIT_REASON));
+
+        // 9.5 breaks it: the stop closes the long, at its level, before the signal is
+        // acted on
+        let broken = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 9.5]);
+        let stopped = PositionBacktester::new(1_000.0)
+            .run_samples(&broken, &with_stop)
+            .unwrap();
+        let first = &stopped.positions[0];
+        assert_eq!(first.side, PositionSide::Long);
+        assert_eq!(first.exit_time, Some(broken[6].ts));
+        assert_eq!(first.exit_price, Some(10.0));
+        assert_eq!(first.exit_reason.as_deref(), Some(STOP_LOSS_EXIT_REASON));
+
+        // A percent stop is measured from the entry price
+        let pct = PositionBacktester::new(1_000.0)
+            .run_samples(
+                &broken,
+                &Candidate {
+                    stop: Some(StopPlacement::Percent(0.1)),
+                    ..candidate()
+                },
+            )
+            .unwrap();
+        assert!((pct.positions[0].stop_price.unwrap() - 9.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_breakeven_stop_exits_at_entry_after_a_rally() {
+        // Long at 11 (swing stop 10); the rally to 13 is +18%, then price gives it back.
+        // Bias flips are ignored so only the stop can close the long.
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 11.0, 9.5]);
+        let candidate = Candidate {
+            stop: Some(StopPlacement::SwingLookback(3)),
+            ..candidate()
+        };
+        let run = |breakeven_at| {
+            PositionBacktester::new(1_000.0)
+                .with_strong_exits_only(true)
+                .with_move_stop_to_breakeven_at_pct(breakeven_at)
+                .run_samples(&samples, &candidate)
+                .unwrap()
+        };
+
+        let original = run(None);
+        let first = &original.positions[0];
+        assert_eq!(first.exit_time, Some(samples[7].ts));
+        assert_eq!(first.exit_price, Some(10.0));
+        assert_eq!(first.exit_reason.as_deref(), Some(STOP_LOSS_EXIT_REASON));
+        assert!(first.profit.unwrap() < 0.0);
+
+        let locked = run(Some(0.1));
+        let first = &locked.positions[0];
+        assert_eq!(first.exit_time, Some(samples[6].ts));
+        assert_eq!(first.exit_price, Some(11.0));
+        assert_eq!(first.exit_reason.as_deref(), Some(STOP_LOSS_EXIT_REASON));
+        assert_eq!(first.profit, Some(0.0));
+
+        // Not reached: the original stop stays in charge
+        assert_eq!(run(Some(0.5)).positions[0].exit_time, Some(samples[7].ts));
+    }
+
+    #[test]
+    fn test_run_combined_is_smoother_than_either_strategy() {
+        // A short price cycle on a slight uptrend: the fast crossover rides each swing,
+        // the lagging one turns late and ends up trading against them
+        let prices: Vec<f64> = (0..200)
+            .map(|i| {
+                let i = i as f64;
+                100.0 + i * 0.05 + (i * std::f64::consts::TAU / 12.0).sin() * 4.0
+            })
+            .collect();
+        let samples = hourly_samples(&prices);
+        let with_windows = |short, long| Candidate {
+            buy_sell_fraction: 1.0,
+            strategy: StrategyConfig {
+                sma_config: SmaConfig::new(short, long),
+                ..bias_only_strategy()
+            },
+            stop: None,
+        };
+        let volatility = |curve: &[(DateTime<Utc>, f64)]| {
+            let returns: Vec<f64> = curve.windows(2).map(|w| w[1].1 / w[0].1 - 1.0).collect();
+            stddev(&returns, 1).unwrap()
+        };
+
+        let combined = PositionBacktester::new(1_000.0)
+            .run_combined(
+                &samples,
+                &[with_windows(2, 3), with_windows(4, 8)],
+                &[0.5, 0.5],
+            )
+            .unwrap();
+
+        let [swing, lagging] = &combined.strategies[..] else {
+            panic!("expected two strategy results");
+        };
+        assert_eq!(swing.initial_equity, 500.0);
+        assert_eq!(combined.equity_curve.len(), samples.len());
+        assert!((combined.final_equity - swing.final_equity - lagging.final_equity).abs() < 1e-9);
+        let vol = volatility(&combined.equity_curve);
+        assert!(vol < volatility(&swing.equity_curve));
+        assert!(vol < volatility(&lagging.equity_curve));
+    }
+
+    #[test]
+    fn test_run_combined_rejects_bad_allocations() {
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0]);
+        let backtester = PositionBacktester::new(1_000.0);
+        let candidates = [candidate(), candidate()];
+        assert!(
+            backtester
+                .run_combined(&samples, &candidates, &[1.0])
+                .is_err()
+        );
+        assert!(
+            backtester
+                .run_combined(&samples, &candidates, &[0.7, 0.7])
+                .is_err()
+        );
+        assert_eq!(
+            backtester
+                .run_combined(&samples, &candidates, &[0.0, 0.0])
+                .unwrap_err(),
+            "allocations must be positive and sum to at most 1"
+        );
+        assert_eq!(
+            backtester.run_combined(&samples, &[], &[]).unwrap_err(),
+            "no strategies to combine"
+        );
+
+        // Unallocated cash sits flat in the combined curve
+        let half = backtester
+            .run_combined(&samples, &candidates[..1], &[0.5])
+            .unwrap();
+        assert_eq!(half.equity_curve[0].1, 1_000.0);
+        assert_eq!(half.final_equity, 500.0 + half.strategies[0].final_equity);
+    }
+
+    #[test]
+    fn test_counts_candles_held_for_atr_warmup() {
+        // SMA(3) is ready from candle 3, ATR(6) only from candle 6
+        let samples = hourly_samples(&[
+            10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 13.0, 12.0, 11.0, 12.0, 13.0,
+        ]);
+        let with_atr = Candidate {
+            strategy: StrategyConfig {
+                filters: FilterConfig {
+                    atr: Some(AtrFilter::new_fixed(6, 0.0)),
+                    ..bias_only_strategy().filters
+                },
+                ..bias_only_strategy()
+            },
+            ..candidate()
+        };
+
+        let result = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &with_atr)
+            .unwrap();
+        assert_eq!(result.atr_warmup_candles, 3);
+        assert_eq!(result.positions[0].entry_time, samples[6].ts);
+
+        let without = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        assert_eq!(without.atr_warmup_candles, 0);
+        assert_eq!(without.positions[0].entry_time, samples[3].ts);
+    }
+
+    #[test]
+    fn test_max_hold_closes_a_long_after_max_hold_candles() {
+        // Rising all the way: the long opened at 3 never sees a SELL
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0]);
+        let result = PositionBacktester::new(1_000.0)
+            .with_max_hold_candles(Some(4))
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let first = &result.positions[0];
+        assert_eq!(first.entry_time, samples[3].ts);
+        assert_eq!(first.exit_time, Some(samples[7].ts));
+        assert_eq!(first.exit_reason.as_deref(), Some(MAX_HOLD_EXIT_REASON));
+        assert_eq!(first.exit_price, Some(15.0));
+
+        let untimed = PositionBacktester::new(1_000.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        assert_eq!(untimed.positions.len(), 1);
+        assert_eq!(
+            untimed.positions[0].exit_reason.as_deref(),
+            Some(EOF_EXIT_REASON)
+        );
+    }
+
+    #[test]
+    fn test_candle_callback_fires_once_per_candle() {
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5, 16.0, 17.0]);
+        let events = std::sync::Mutex::new(Vec::new());
+        let result = PositionBacktester::with_logger(
+            1_000.0,
+            CandleCallback(|event: &CandleEvent| events.lock().unwrap().push(event.clone())),
+        )
+        .run_samples(&samples, &candidate())
+        .unwrap();
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.len(), samples.len());
+        assert_eq!(events[0].suggestion, None);
+        assert_eq!(events[3].suggestion.as_deref(), Some("BUY"));
+        assert_eq!(events[3].position, Some(PositionSide::Long));
+        assert_eq!(events[6].position, Some(PositionSide::Short));
+        let last = events.last().unwrap();
+        assert_eq!(last.ts, samples[8].ts);
+        assert!((last.equity - result.final_equity).abs() < 1e-9);
+    }
+}
@@ -1,11 +1,27 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
 use chrono::{DateTime, Utc};
+use serde::Deserialize;
 
-use crate::backtest::{Backtester, Candidate, TradingMetrics};
-use crate::data::Sample;
-use crate::indicators::compute_smas;
+use crate::backtest::{
+    Backtester, Candidate, ExecutionConfig, MAX_HOLD_EXIT_REASON, TRAILING_STOP_EXIT_REASON,
+    TradingMetrics,
+};
+use crate::data::{Sample, validate_dataset};
+use crate::indicators::RollingSma;
+use crate::indicators::sma::SmaConfig;
 use crate::signal::analyze;
+use crate::source::{PriceSource, collect_samples};
+use crate::units::{Bps, Pct};
 
-use super::common::{Signal, compute_max_drawdown, suggestion_to_signal};
+use super::common::{
+    EdgeTracker, EquityMetrics, EquityRecorder, FillScheduler, InvariantViolation, LossStreak,
+    PendingSignals, PriceHistory, RMultipleStats, Signal, SignalPersistence, SignalReason,
+    ValidationReport, check_fill_invariants, compute_cagr, compute_profit_factor, compute_sharpe,
+    holding_curve, print_benchmark_comparison, print_r_stats, round_down_to_step,
+    suggestion_to_signal,
+};
 
 #[derive(Debug, Clone)]
 pub struct Trade {
@@ -17,6 +33,16 @@ pub struct Trade {
     pub exit_value: f64,
     pub profit: f64,
     pub return_pct: f64,
+    /// Maximum favorable excursion: highest price while held, relative to entry.
+    pub mfe_pct: f64,
+    /// Maximum adverse excursion: how far (positive) price fell below entry while held.
+    pub mae_pct: f64,
+    /// The signal (or stop) that sold.
+    pub exit_reason: String,
+    /// Profit in multiples of the risk taken at entry, i.e. of the loss at the trailing
+    /// stop as first placed (`trailing_stop_pct` below the entry). None without a
+    /// trailing stop.
+    pub r_multiple: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -27,7 +53,33 @@ pub struct SpotBacktestResult {
     pub final_equity: f64,
     pub total_return_pct: f64,
     pub max_drawdown_pct: f64,
+    /// Largest fall below the initial equity (see `DrawdownKind::FromInitial`).
+    pub max_drawdown_from_initial_pct: f64,
+    /// Max drawdown on log-equity (see `compute_max_log_drawdown`).
+    pub max_log_drawdown: f64,
     pub win_rate_pct: f64,
+    /// Gross profit over gross loss (infinite without losses), and the mean profit of
+    /// winning and losing trades (the loss as a positive amount).
+    pub profit_factor: f64,
+    pub avg_win: f64,
+    pub avg_loss: f64,
+    /// R-multiple summary of the trades (only with a trailing stop).
+    pub r_stats: Option<RMultipleStats>,
+    /// Mean MFE / MAE over trades.
+    pub avg_mfe_pct: f64,
+    pub avg_mae_pct: f64,
+    /// Candles past the SMA warmup (i.e. where the strategy could act).
+    pub usable_candles: usize,
+    /// Fewer usable candles than `min_usable_candles`; interpret with care.
+    pub partial: bool,
+    /// Usable candles the ATR filter held for lack of data (its period is longer
+    /// than the SMA warmup), so no rule could act on them.
+    pub atr_warmup_candles: usize,
+    /// Buy & hold over the same candles, run through the same metrics.
+    pub benchmark_metrics: Option<EquityMetrics>,
+    /// SMA windows the strategy ran with; a shorter long window than the candidate's
+    /// when `fit_long_window` had to shrink it.
+    pub effective_sma_config: SmaConfig,
 }
 
 fn compute_win_rate(trades: &[Trade]) -> f64 {
@@ -40,36 +92,129 @@ fn compute_win_rate(trades: &[Trade]) -> f64 {
     wins / trades.len() as f64
 }
 
-pub fn buy_and_hold_equity(hourly: &[Sample], initial_cash: f64, initial_coin: f64) -> Option<f64> {
-    if hourly.is_empty() {
+/// Average (MFE, MAE) across trades.
+fn compute_avg_excursions(trades: &[Trade]) -> (f64, f64) {
+    if trades.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let n = trades.len() as f64;
+    let mfe = trades.iter().map(|t| t.mfe_pct).sum::<f64>() / n;
+    let mae = trades.iter().map(|t| t.mae_pct).sum::<f64>() / n;
+    (mfe, mae)
+}
+
+/// Equity curve of converting `initial_cash` to coin at the first candle and holding everything.
+pub fn buy_and_hold_curve(
+    hourly: &[Sample],
+    initial_cash: f64,
+    initial_coin: f64,
+) -> Option<Vec<(DateTime<Utc>, f64)>> {
+    let first = hourly.first()?.price;
+    if first <= 0.0 {
         return None;
     }
-    let first = hourly.first().unwrap().price;
-    let last = hourly.last().unwrap().price;
+    Some(holding_curve(hourly, initial_cash / first + initial_coin))
+}
+
+/// Final equity of spending the cash at the first candle and holding everything to the last.
+/// With `qty_step` the bought quantity is rounded down to whole lots and the rest stays in cash.
+pub fn buy_and_hold_equity(
+    hourly: &[Sample],
+    initial_cash: f64,
+    initial_coin: f64,
+    qty_step: Option<f64>,
+) -> Option<f64> {
+    let first = hourly.first()?.price;
+    let last = hourly.last()?.price;
     if first <= 0.0 {
         return None;
     }
 
-    let qty = initial_cash / first + initial_coin;
-    Some(qty * last)
+    let bought = round_down_to_step(initial_cash / first, qty_step);
+    let leftover_cash = initial_cash - bought * first;
+    Some((bought + initial_coin) * last + leftover_cash)
 }
 
 /// Simple CLI-style summary you can reuse in a binary.
-pub fn print_summary(result: &SpotBacktestResult) {
-    println!("=== Backtest Summary ===");
-    println!("Initial equity:  {:.2}", result.initial_equity);
-    println!("Final equity:     {:.2}", result.final_equity);
-    println!("Total return:     {:.2}%", result.total_return_pct * 100.0);
-    println!("Max drawdown:     {:.2}%", result.max_drawdown_pct * 100.0);
-    println!("Trades:           {}", result.trades.len());
-    println!("Win rate:         {:.2}%", result.win_rate_pct * 100.0);
+pub fn print_summary(out: &mut impl Write, result: &SpotBacktestResult) -> io::Result<()> {
+    writeln!(out, "=== Backtest Summary ===")?;
+    writeln!(out, "Initial equity:  {:.2}", result.initial_equity)?;
+    writeln!(out, "Final equity:     {:.2}", result.final_equity)?;
+    writeln!(out, "Total return:     {:#}", Pct(result.total_return_pct))?;
+    writeln!(out, "CAGR:             {:#}", Pct(result.cagr()))?;
+    writeln!(out, "Max drawdown:     {:#}", Pct(result.max_drawdown_pct))?;
+    writeln!(
+        out,
+        "DD from initial:  {:#}",
+        Pct(result.max_drawdown_from_initial_pct)
+    )?;
+    writeln!(out, "Max log drawdown: {:.4}", result.max_log_drawdown)?;
+    writeln!(out, "Trades:           {}", result.trades.len())?;
+    writeln!(out, "Win rate:         {:#}", Pct(result.win_rate_pct))?;
+    writeln!(out, "Profit factor:    {:.2}", result.profit_factor)?;
+    writeln!(out, "Avg win:          {:.2}", result.avg_win)?;
+    writeln!(out, "Avg loss:         {:.2}", result.avg_loss)?;
+    print_r_stats(out, result.r_stats)?;
+    writeln!(out, "Avg MFE:          {:#}", Pct(result.avg_mfe_pct))?;
+    writeln!(out, "Avg MAE:          {:#}", Pct(result.avg_mae_pct))?;
+    if result.partial {
+        writeln!(
+            out,
+            "PARTIAL result:   only {} usable candles past warmup",
+            result.usable_candles
+        )?;
+    }
+    if result.atr_warmup_candles > 0 {
+        writeln!(
+            out,
+            "ATR warmup:       {} of {} usable candles held for lack of ATR data",
+            result.atr_warmup_candles, result.usable_candles
+        )?;
+    }
+    if let Some(benchmark) = &result.benchmark_metrics {
+        writeln!(out)?;
+        print_benchmark_comparison(
+            out,
+            &EquityMetrics::from_curve(&result.equity_curve),
+            benchmark,
+        )?;
+    }
+    Ok(())
+}
+
+/// Which buys a sell is matched against when reporting trades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LotMatching {
+    /// One trade per sell at the position's average cost, timed from the first buy
+    /// since flat.
+    #[default]
+    Average,
+    /// Sell the oldest buy lots first; one trade per lot (or part of a lot) sold.
+    Fifo,
+    /// Sell the newest buy lots first.
+    Lifo,
 }
 
 #[derive(Clone, Copy)]
 pub struct SpotBacktester {
     initial_cash: f64,
     initial_coin: f64,
-    fee_bps: f64,
+    fee: Bps,
+    /// Fixed fee in cash charged on every fill, on top of `fee`.
+    flat_fee: f64,
+    /// Market impact: buys fill this much above the price, sells below it, before fees.
+    slippage: Bps,
+    /// Average price paid for `initial_coin`; the first candle's price if unset.
+    initial_coin_cost_basis: Option<f64>,
+    lot_matching: LotMatching,
+    execution: ExecutionConfig,
+    /// Sell the whole holding once price falls this fraction below its high since
+    /// going long.
+    trailing_stop_pct: Option<f64>,
+    /// Sell the whole holding this many candles after going long, whatever the signals say.
+    max_hold_candles: Option<usize>,
 }
 
 impl SpotBacktester {
@@ -77,168 +222,621 @@ impl SpotBacktester {
         Self {
             initial_cash,
             initial_coin,
-            fee_bps,
+            fee: Bps(fee_bps),
+            flat_fee: 0.0,
+            slippage: Bps(0.0),
+            initial_coin_cost_basis: None,
+            lot_matching: LotMatching::default(),
+            execution: ExecutionConfig::default(),
+            trailing_stop_pct: None,
+            max_hold_candles: None,
         }
     }
+
+    pub fn with_execution(mut self, execution: ExecutionConfig) -> Self {
+        self.execution = execution;
+        self
+    }
+
+    pub fn with_flat_fee(mut self, flat_fee: f64) -> Self {
+        self.flat_fee = flat_fee;
+        self
+    }
+
+    pub fn with_slippage_bps(mut self, slippage_bps: f64) -> Self {
+        self.slippage = Bps(slippage_bps);
+        self
+    }
+
+    pub fn with_trailing_stop_pct(mut self, trailing_stop_pct: Option<f64>) -> Self {
+        self.trailing_stop_pct = trailing_stop_pct;
+        self
+    }
+
+    pub fn with_max_hold_candles(mut self, max_hold: Option<usize>) -> Self {
+        self.max_hold_candles = max_hold;
+        self
+    }
+
+    pub fn with_initial_coin_cost_basis(mut self, cost_basis: Option<f64>) -> Self {
+        self.initial_coin_cost_basis = cost_basis;
+        self
+    }
+
+    pub fn with_lot_matching(mut self, lot_matching: LotMatching) -> Self {
+        self.lot_matching = lot_matching;
+        self
+    }
 }
 
-impl Backtester for SpotBacktester {
-    type Output = SpotBacktestResult;
-    fn run_backtest(
+/// Coin bought in one fill and not sold yet.
+#[derive(Debug, Clone, Copy)]
+struct Lot {
+    time: DateTime<Utc>,
+    qty: f64,
+    /// Net cash paid for `qty`, fees included.
+    cost: f64,
+    /// Highest / lowest price seen since the lot was bought.
+    high: f64,
+    low: f64,
+}
+
+/// Cash/coin holdings plus the bookkeeping needed to report trades.
+struct SpotBook {
+    cash: f64,
+    coin: f64,
+    cost_basis_total: f64,
+    in_position: bool,
+    entry_time: DateTime<Utc>,
+    /// Candle index of `entry_time`.
+    opened_at: usize,
+    avg_entry_price: f64,
+    /// Highest / lowest price seen since going long.
+    high_since_entry: f64,
+    low_since_entry: f64,
+    flat_fee: f64,
+    /// Slippage as a fraction of the price.
+    slippage: f64,
+    /// Sum of closed trades' profits.
+    realized_profit: f64,
+    trades: Vec<Trade>,
+    lot_matching: LotMatching,
+    /// Open buy lots, oldest first. Only kept for FIFO/LIFO matching.
+    lots: VecDeque<Lot>,
+    /// Trailing stop distance, which sets each trade's risk at entry.
+    trailing_stop_pct: Option<f64>,
+    /// Lot size quantities are rounded down to.
+    qty_step: Option<f64>,
+}
+
+impl SpotBook {
+    fn track_extremes(&mut self, price: f64) {
+        if self.in_position {
+            self.high_since_entry = self.high_since_entry.max(price);
+            self.low_since_entry = self.low_since_entry.min(price);
+        }
+        for lot in &mut self.lots {
+            lot.high = lot.high.max(price);
+            lot.low = lot.low.min(price);
+        }
+    }
+
+    /// Deploy `invest_gross` cash (before fees) into the coin.
+    fn buy(
+        &mut self,
+        price: f64,
+        ts: DateTime<Utc>,
+        index: usize,
+        invest_gross: f64,
+        fee_mult: f64,
+    ) {
+        if invest_gross <= 0.0 || self.cash <= 0.0 || price <= 0.0 {
+            return;
+        }
+
+        // Net after fee, filled above the price
+        let fill_price = price * (1.0 + self.slippage);
+        let unrounded = invest_gross * fee_mult / fill_price;
+        let qty = round_down_to_step(unrounded, self.qty_step);
+        if qty <= 0.0 {
+            return;
+        }
+        // Only whole lots are bought; the rest stays in cash
+        let invest_gross = if qty < unrounded {
+            qty * fill_price / fee_mult
+        } else {
+            invest_gross
+        };
+        let invest_net = invest_gross * fee_mult;
+
+        // If this is the first time we go from flat -> long, set entry time
+        if !self.in_position && self.coin == 0.0 {
+            self.in_position = true;
+            self.entry_time = ts;
+            self.opened_at = index;
+            self.high_since_entry = price;
+            self.low_since_entry = price;
+        };
+
+        // Update state
+        self.cash -= invest_gross + self.flat_fee; // gross amount (bps fee embedded) plus flat fee
+        self.coin += qty;
+        self.cost_basis_total += invest_net + self.flat_fee; // net invested, flat fee included
+        if self.lot_matching != LotMatching::Average {
+            self.lots.push_back(Lot {
+                time: ts,
+                qty,
+                cost: invest_net + self.flat_fee,
+                high: price,
+                low: price,
+            });
+        }
+
+        // Update average entry price just for reporting
+        self.avg_entry_price = if self.coin > 0.0 {
+            self.cost_basis_total / self.coin
+        } else {
+            0.0
+        };
+    }
+
+    /// Trailing stop level: `pct` below the high since going long. The high only
+    /// ratchets up while in position, so the level never loosens.
+    fn trailing_stop_level(&self, pct: f64) -> Option<f64> {
+        (self.in_position && self.coin > 0.0).then_some(self.high_since_entry * (1.0 - pct))
+    }
+
+    /// Whether the holding has been held `max_hold` candles by candle `index`.
+    fn is_expired(&self, index: usize, max_hold: usize) -> bool {
+        self.in_position && self.coin > 0.0 && index - self.opened_at >= max_hold
+    }
+
+    fn sell(
+        &mut self,
+        price: f64,
+        ts: DateTime<Utc>,
+        buy_sell_frac: f64,
+        fee_mult: f64,
+        reason: &str,
+    ) {
+        if buy_sell_frac <= 0.0 || self.coin <= 0.0 || price <= 0.0 {
+            return;
+        }
+
+        let pos_before = self.coin;
+        let sell_qty = if buy_sell_frac >= 1.0 {
+            pos_before
+        } else {
+            round_down_to_step(pos_before * buy_sell_frac, self.qty_step)
+        };
+        if sell_qty <= 0.0 {
+            return;
+        }
+
+        let fill_price = price * (1.0 - self.slippage);
+        let gross = sell_qty * fill_price;
+        let exit_value = gross * fee_mult - self.flat_fee;
+
+        self.cash += exit_value;
+        self.coin = pos_before - sell_qty;
+
+        if self.lot_matching == LotMatching::Average {
+            // Allocate a *fraction* of cost basis to the sold chunk
+            let (entry_value_for_chunk, avg_entry_for_chunk) =
+                if self.cost_basis_total > 0.0 && pos_before > 0.0 {
+                    let fraction_sold = sell_qty / pos_before;
+                    let chunk_basis = self.cost_basis_total * fraction_sold;
+                    self.cost_basis_total -= chunk_basis;
+
+                    let avg_entry = chunk_basis / sell_qty;
+                    (chunk_basis, avg_entry)
+                } else {
+                    (0.0, self.avg_entry_price)
+                };
+
+            // Record this partial trade
+            self.record_trade(
+                Lot {
+                    time: self.entry_time,
+                    qty: sell_qty,
+                    cost: entry_value_for_chunk,
+                    high: self.high_since_entry,
+                    low: self.low_since_entry,
+                },
+                avg_entry_for_chunk,
+                ts,
+                fill_price,
+                exit_value,
+                reason,
+            );
+        } else {
+            self.sell_lots(sell_qty, fill_price, ts, exit_value, reason);
+        }
+
+        if self.coin <= 0.0 {
+            self.in_position = false;
+            self.cost_basis_total = 0.0;
+            self.avg_entry_price = 0.0;
+            self.lots.clear();
+        }
+    }
+
+    /// Match `sell_qty` against the open lots in FIFO/LIFO order, one trade per lot
+    /// touched. The sell's `exit_value` is split by quantity.
+    fn sell_lots(
+        &mut self,
+        sell_qty: f64,
+        price: f64,
+        ts: DateTime<Utc>,
+        exit_value: f64,
+        reason: &str,
+    ) {
+        let mut remaining = sell_qty;
+        while remaining > 0.0 {
+            let lot = match self.lot_matching {
+                LotMatching::Lifo => self.lots.back_mut(),
+                _ => self.lots.front_mut(),
+            };
+            let Some(lot) = lot else {
+                break;
+            };
+            let take = remaining.min(lot.qty);
+            let sold = Lot {
+                qty: take,
+                cost: lot.cost * take / lot.qty,
+                ..*lot
+            };
+            lot.qty -= take;
+            lot.cost -= sold.cost;
+            if lot.qty <= 0.0 {
+                match self.lot_matching {
+                    LotMatching::Lifo => self.lots.pop_back(),
+                    _ => self.lots.pop_front(),
+                };
+            }
+            remaining -= take;
+            self.cost_basis_total -= sold.cost;
+            let avg_entry = sold.cost / sold.qty;
+            let exit_share = exit_value * take / sell_qty;
+            self.record_trade(sold, avg_entry, ts, price, exit_share, reason);
+        }
+    }
+
+    /// Book the sale of `sold` (entry time, quantity, cost and price extremes).
+    fn record_trade(
+        &mut self,
+        sold: Lot,
+        entry_price: f64,
+        exit_time: DateTime<Utc>,
+        exit_price: f64,
+        exit_value: f64,
+        reason: &str,
+    ) {
+        let profit = exit_value - sold.cost;
+        self.realized_profit += profit;
+        let ret = if sold.cost > 0.0 {
+            exit_value / sold.cost - 1.0
+        } else {
+            0.0
+        };
+
+        let (mfe_pct, mae_pct) = if entry_price > 0.0 {
+            (
+                (sold.high / entry_price - 1.0).max(0.0),
+                (1.0 - sold.low / entry_price).max(0.0),
+            )
+        } else {
+            (0.0, 0.0)
+        };
+
+        // The trailing stop starts `pct` below the (fee-inclusive) entry
+        let r_multiple = self
+            .trailing_stop_pct
+            .map(|pct| sold.cost * pct)
+            .filter(|&risk| risk > 0.0)
+            .map(|risk| profit / risk);
+
+        self.trades.push(Trade {
+            entry_time: sold.time,
+            exit_time,
+            entry_price,
+            exit_price,
+            entry_value: sold.cost,
+            exit_value,
+            profit,
+            return_pct: ret,
+            mfe_pct,
+            mae_pct,
+            exit_reason: reason.to_string(),
+            r_multiple,
+        });
+    }
+}
+
+/// Loop state after replaying the strategy over the samples.
+struct SpotRun {
+    book: SpotBook,
+    recorder: EquityRecorder,
+    initial_equity: f64,
+    violation: Option<InvariantViolation>,
+    candles_checked: usize,
+    atr_warmup_candles: usize,
+}
+
+impl SpotBacktester {
+    /// Dry run: replay the strategy and check that every fill keeps cash, holdings
+    /// and equity sane. Stops at the first violation; no metrics are computed.
+    pub fn validate(
         &self,
         samples: &[Sample],
         candidate: &Candidate,
-    ) -> Result<Self::Output, String> {
-        if samples.len() < candidate.strategy.sma_config.long_window + 1 {
-            return Err("Not enough data".to_string());
-        }
+    ) -> Result<ValidationReport, String> {
+        validate_dataset(samples).map_err(|err| err.to_string())?;
+        let candidate = &*self.execution.fit_candidate(candidate, samples.len());
+        self.execution
+            .check_usable_candles(samples.len(), candidate.strategy.sma_config.long_window)?;
+
+        let run = self.simulate(samples, candidate, true);
+        Ok(ValidationReport {
+            candles_checked: run.candles_checked,
+            violation: run.violation,
+        })
+    }
+
+    /// Rerun the backtest at every fee level in `fee_bps_grid` (everything else as
+    /// configured), as (fee_bps, total_return_pct) pairs in grid order. Shows the fee at
+    /// which the strategy's edge disappears.
+    pub fn fee_sensitivity(
+        &self,
+        samples: &[Sample],
+        candidate: &Candidate,
+        fee_bps_grid: &[f64],
+    ) -> Result<Vec<(f64, f64)>, String> {
+        fee_bps_grid
+            .iter()
+            .map(|&fee_bps| {
+                let backtester = Self {
+                    fee: Bps(fee_bps),
+                    ..*self
+                };
+                let result = backtester.run_samples(samples, candidate)?;
+                Ok((fee_bps, result.total_return_pct))
+            })
+            .collect()
+    }
 
-        // TODO: This doesn't have to be the first price available in my sample
-        // For example, I can run my backtest with other much "newer" data
+    /// The backtest loop. With `validate`, returns early on the first broken invariant.
+    /// `samples` must be non-empty.
+    fn simulate(&self, samples: &[Sample], candidate: &Candidate, validate: bool) -> SpotRun {
         let first_price = samples[0].price.max(0.0);
         let initial_equity = self.initial_cash + self.initial_coin * first_price;
+        // What the existing coin cost, for trade P&L; equity is still marked at first_price
+        let initial_entry_price = self
+            .initial_coin_cost_basis
+            .map_or(first_price, |basis| basis.max(0.0));
 
-        let mut prices: Vec<f64> = Vec::with_capacity(samples.len());
-        let mut equity_curve: Vec<(DateTime<Utc>, f64)> = Vec::with_capacity(samples.len());
-        let mut trades: Vec<Trade> = Vec::new();
+        let mut prices = PriceHistory::new(&self.execution, candidate, samples.len());
+        let mut rolling_smas = RollingSma::new(&candidate.strategy.sma_config);
+        let mut recorder = EquityRecorder::new(&self.execution, samples.len());
+        let mut pending = PendingSignals::default();
+        let mut streak = LossStreak::new(&self.execution);
+        let mut edge = EdgeTracker::new(&self.execution);
+        let mut persistence = SignalPersistence::new(&self.execution);
+        let mut fills = FillScheduler::new(&self.execution);
 
         // Initial portfolio state
-        let mut cash = self.initial_cash;
-        let mut coin = self.initial_coin;
-
-        // Treat existing coin as if it was "bought" at the first price (no fee)
-        let mut cost_basis_total = self.initial_coin * first_price;
-
-        let mut in_position = self.initial_coin > 0.0;
-        let mut entry_time = samples[0].ts;
-        let mut avg_entry_price = if coin > 0.0 { first_price } else { 0.0 };
+        let mut book = SpotBook {
+            cash: self.initial_cash,
+            coin: self.initial_coin,
+            // Treat existing coin as bought at its cost basis (no fee)
+            cost_basis_total: self.initial_coin * initial_entry_price,
+            in_position: self.initial_coin > 0.0,
+            entry_time: samples[0].ts,
+            opened_at: 0,
+            avg_entry_price: if self.initial_coin > 0.0 {
+                initial_entry_price
+            } else {
+                0.0
+            },
+            high_since_entry: first_price,
+            low_since_entry: first_price,
+            flat_fee: self.flat_fee,
+            slippage: self.slippage.as_fraction(),
+            realized_profit: 0.0,
+            trades: Vec::new(),
+            lot_matching: self.lot_matching,
+            lots: VecDeque::new(),
+            trailing_stop_pct: self.trailing_stop_pct,
+            qty_step: self.execution.qty_step,
+        };
+        if self.lot_matching != LotMatching::Average && self.initial_coin > 0.0 {
+            book.lots.push_back(Lot {
+                time: samples[0].ts,
+                qty: self.initial_coin,
+                cost: book.cost_basis_total,
+                high: first_price,
+                low: first_price,
+            });
+        }
 
-        let fee = self.fee_bps / 10_000.0; // e.g. 10bp => 0.001
+        let fee = self.fee.as_fraction(); // e.g. 10bp => 0.001
         let fee_mult = 1.0 - fee;
 
         let buy_sell_frac = candidate.buy_sell_fraction.clamp(0.0, 1.0);
+        let mut atr_warmup_candles = 0;
+        // Cash the buy order being filled commits in all
+        let mut buy_budget = 0.0;
 
         for (i, candle) in samples.iter().enumerate() {
             let price = candle.price;
             prices.push(price);
+            let smas = rolling_smas.push(price);
 
             // Mark current equity (mark-to-market); no fee on unrealized
-            let equity = cash + coin * price;
-            equity_curve.push((candle.ts, equity));
-
-            if prices.len() < candidate.strategy.sma_config.long_window + 1 {
-                // Not enough data yet for SMAs
-                continue;
+            recorder.push(candle.ts, book.cash + book.coin * price);
+            book.track_extremes(price);
+            if let Some(level) = self
+                .trailing_stop_pct
+                .and_then(|pct| book.trailing_stop_level(pct))
+                && price <= level
+            {
+                let before = book.trades.len();
+                book.sell(level, candle.ts, 1.0, fee_mult, TRAILING_STOP_EXIT_REASON);
+                for trade in &book.trades[before..] {
+                    streak.record(trade.profit, i);
+                    edge.record(trade.return_pct);
+                }
+            }
+            if self
+                .max_hold_candles
+                .is_some_and(|max_hold| book.is_expired(i, max_hold))
+            {
+                let before = book.trades.len();
+                book.sell(price, candle.ts, 1.0, fee_mult, MAX_HOLD_EXIT_REASON);
+                for trade in &book.trades[before..] {
+                    streak.record(trade.profit, i);
+                    edge.record(trade.return_pct);
+                }
             }
 
-            let Some(smas) = compute_smas(&prices, candidate.strategy.sma_config) else {
-                continue;
-            };
-
-            let analysis = analyze(&samples[..=i], &prices, smas, candidate.strategy);
-            let signal = suggestion_to_signal(&analysis.suggestion);
-
-            match signal {
-                Some(Signal::Buy) => {
-                    if buy_sell_frac <= 0.0 || cash <= 0.0 || price <= 0.0 {
-                        continue;
-                    }
-
-                    // Amount of cash we plan to deploy *before* fees
-                    let invest_gross = cash * buy_sell_frac;
-                    if invest_gross <= 0.0 {
-                        continue;
-                    }
-
-                    // Net after fee
-                    let invest_net = invest_gross * fee_mult;
-                    let qty = invest_net / price;
-                    if qty <= 0.0 {
-                        continue;
-                    }
-
-                    // If this is the first time we go from flat -> long, set entry time
-                    if !in_position && coin == 0.0 {
-                        in_position = true;
-                        entry_time = candle.ts;
-                    };
-
-                    // Update state
-                    cash -= invest_gross; // we spend the gross amount (fee is embedded)
-                    coin += qty;
-                    cost_basis_total += invest_net; // our cost basis increases by net invested
-
-                    // Update average entry price just for reporting
-                    avg_entry_price = if coin > 0.0 {
-                        cost_basis_total / coin
-                    } else {
-                        0.0
-                    };
+            // Not enough data yet for SMAs
+            if let Some(smas) = smas {
+                let analysis = analyze(
+                    &samples[..=i],
+                    prices.as_slice(),
+                    smas,
+                    &candidate.strategy,
+                    false,
+                )
+                .expect("candles up to i, with SMAs computed");
+                if analysis.held_for_atr_data() {
+                    atr_warmup_candles += 1;
                 }
-                Some(Signal::Sell) => {
-                    if buy_sell_frac <= 0.0 || coin <= 0.0 || price <= 0.0 {
-                        continue;
-                    }
+                let signal = suggestion_to_signal(&analysis.suggestion);
+                let confirmed = persistence.observe(signal);
+                // HOLD is dropped here; only buys (entries) wait for confirmation
+                if let Some(signal) = signal
+                    && (confirmed || signal == Signal::Sell)
+                {
+                    pending.push(
+                        i,
+                        self.execution.latency_candles,
+                        signal,
+                        SignalReason::of(&analysis),
+                    );
+                }
+            }
 
-                    let pos_before = coin;
-                    let sell_qty = pos_before * buy_sell_frac;
-                    if sell_qty <= 0.0 {
-                        continue;
+            let mut due_fills = Vec::new();
+            while let Some((signal, reason)) = pending.pop_due(i) {
+                due_fills.extend(fills.submit(signal, reason, price));
+            }
+            due_fills.extend(fills.advance(price));
+            for fill in due_fills {
+                match fill.signal {
+                    // Sells still go through while cooling down; only new buys are blocked
+                    Signal::Buy if streak.is_paused(i) => {}
+                    Signal::Buy => {
+                        // The first slice sizes the whole order
+                        if fill.is_first() {
+                            buy_budget = self.execution.sizing.entry_budget(
+                                edge.entry_fraction(buy_sell_frac),
+                                book.cash,
+                                book.cash + book.coin * fill.price,
+                                initial_equity + book.realized_profit,
+                            );
+                        }
+                        let budget = buy_budget * fill.share;
+                        book.buy(fill.price, candle.ts, i, budget, fee_mult);
                     }
-
-                    let gross = sell_qty * price;
-                    let exit_value = gross * fee_mult;
-
-                    // Allocate a *fraction* of cost basis to the sold chunk
-                    let (entry_value_for_chunk, avg_entry_for_chunk) =
-                        if cost_basis_total > 0.0 && pos_before > 0.0 {
-                            let fraction_sold = sell_qty / pos_before;
-                            let chunk_basis = cost_basis_total * fraction_sold;
-                            cost_basis_total -= chunk_basis;
-
-                            let avg_entry = chunk_basis / sell_qty;
-                            (chunk_basis, avg_entry)
-                        } else {
-                            (0.0, avg_entry_price)
-                        };
-
-                    cash += exit_value;
-                    coin = pos_before - sell_qty;
-
-                    // Record this partial trade
-                    let profit = exit_value - entry_value_for_chunk;
-                    let ret = if entry_value_for_chunk > 0.0 {
-                        exit_value / entry_value_for_chunk - 1.0
-                    } else {
-                        0.0
-                    };
-
-                    trades.push(Trade {
-                        entry_time,
-                        exit_time: candle.ts,
-                        entry_price: avg_entry_for_chunk,
-                        exit_price: price,
-                        entry_value: entry_value_for_chunk,
-                        exit_value,
-                        profit,
-                        return_pct: ret,
-                    });
-
-                    if coin <= 0.0 {
-                        in_position = false;
-                        cost_basis_total = 0.0;
-                        avg_entry_price = 0.0;
+                    Signal::Sell => {
+                        // `buy_sell_frac` of the holding when the order started, in
+                        // even slices: this one's share of what's still held
+                        let sell_frac =
+                            buy_sell_frac * fill.share / (1.0 - buy_sell_frac * fill.filled);
+                        let before = book.trades.len();
+                        book.sell(
+                            fill.price,
+                            candle.ts,
+                            sell_frac.min(1.0),
+                            fee_mult,
+                            &fill.reason.text,
+                        );
+                        for trade in &book.trades[before..] {
+                            streak.record(trade.profit, i);
+                            edge.record(trade.return_pct);
+                        }
                     }
                 }
-                _ => {
-                    // HOLD or suggestion that doesn't change position
-                }
+            }
+
+            if validate
+                && let Err(message) =
+                    check_fill_invariants(book.cash, book.coin, book.cash + book.coin * price)
+            {
+                return SpotRun {
+                    book,
+                    recorder,
+                    initial_equity,
+                    violation: Some(InvariantViolation {
+                        index: i,
+                        ts: candle.ts,
+                        message,
+                    }),
+                    candles_checked: i + 1,
+                    atr_warmup_candles,
+                };
             }
         }
 
+        SpotRun {
+            book,
+            recorder,
+            initial_equity,
+            violation: None,
+            candles_checked: samples.len(),
+            atr_warmup_candles,
+        }
+    }
+}
+
+impl Backtester for SpotBacktester {
+    type Output = SpotBacktestResult;
+    fn run_backtest(
+        &self,
+        source: &mut dyn PriceSource,
+        candidate: &Candidate,
+    ) -> Result<Self::Output, String> {
+        let samples = collect_samples(source).map_err(|err| format!("{err:#}"))?;
+        self.run_samples(&samples, candidate)
+    }
+}
+
+impl SpotBacktester {
+    /// `run_backtest` over candles already in memory.
+    pub fn run_samples(
+        &self,
+        samples: &[Sample],
+        candidate: &Candidate,
+    ) -> Result<SpotBacktestResult, String> {
+        validate_dataset(samples).map_err(|err| err.to_string())?;
+        let candidate = &*self.execution.fit_candidate(candidate, samples.len());
+        let (usable_candles, partial) = self
+            .execution
+            .check_usable_candles(samples.len(), candidate.strategy.sma_config.long_window)?;
+
+        let SpotRun {
+            book,
+            recorder,
+            initial_equity,
+            atr_warmup_candles,
+            ..
+        } = self.simulate(samples, candidate, false);
+        let (equity_curve, drawdowns) = recorder.finish();
+
         // If still in a trade at the end, mark to market but don't close trade
         let last_price = samples.last().unwrap().price;
-        let final_equity = cash + coin * last_price;
+        let final_equity = book.cash + book.coin * last_price;
         // If user gave nonsense initial values (0 everything), avoid divide-by-zero
         let effective_initial_equity = if initial_equity > 0.0 {
             initial_equity
@@ -248,17 +846,35 @@ impl Backtester for SpotBacktester {
 
         let total_return_pct = final_equity / effective_initial_equity - 1.0;
 
-        let max_drawdown_pct = compute_max_drawdown(&equity_curve);
-        let win_rate_pct = compute_win_rate(&trades);
+        let win_rate_pct = compute_win_rate(&book.trades);
+        let profits: Vec<f64> = book.trades.iter().map(|t| t.profit).collect();
+        let (profit_factor, avg_win, avg_loss) = compute_profit_factor(&profits);
+        let r_multiples: Vec<f64> = book.trades.iter().filter_map(|t| t.r_multiple).collect();
+        let (avg_mfe_pct, avg_mae_pct) = compute_avg_excursions(&book.trades);
+        let benchmark_metrics = buy_and_hold_curve(samples, self.initial_cash, self.initial_coin)
+            .map(|curve| EquityMetrics::from_curve(&curve));
 
         Ok(SpotBacktestResult {
             initial_equity,
-            trades,
+            trades: book.trades,
             equity_curve,
             final_equity,
             total_return_pct,
-            max_drawdown_pct,
+            max_drawdown_pct: drawdowns.max_drawdown_pct,
+            max_drawdown_from_initial_pct: drawdowns.max_drawdown_from_initial_pct,
+            max_log_drawdown: drawdowns.max_log_drawdown,
             win_rate_pct,
+            profit_factor,
+            avg_win,
+            avg_loss,
+            r_stats: RMultipleStats::from_r_multiples(&r_multiples),
+            avg_mfe_pct,
+            avg_mae_pct,
+            usable_candles,
+            partial,
+            atr_warmup_candles,
+            benchmark_metrics,
+            effective_sma_config: candidate.strategy.sma_config.clone(),
         })
     }
 }
@@ -271,4 +887,444 @@ impl TradingMetrics for SpotBacktestResult {
     fn max_drawdown_pct(&self) -> f64 {
         self.max_drawdown_pct
     }
+
+    fn trade_count(&self) -> usize {
+        self.trades.len()
+    }
+
+    fn sharpe_ratio(&self) -> f64 {
+        compute_sharpe(&self.equity_curve)
+    }
+
+    fn cagr(&self) -> f64 {
+        compute_cagr(&self.equity_curve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    use crate::backtest::SizingBase;
+    use crate::backtest::common::test_utils::{bias_only_strategy, hourly_samples};
+
+    fn candidate() -> Candidate {
+        Candidate {
+            buy_sell_fraction: 1.0,
+            strategy: bias_only_strategy(),
+            stop: None,
+        }
+    }
+
+    #[test]
+    fn test_empty_and_single_candle_inputs_are_clean_errors() {
+        let backtester = SpotBacktester::new(1_000.0, 0.0, 0.0);
+        assert_eq!(
+            backtester.run_samples(&[], &candidate()).unwrap_err(),
+            "no samples"
+        );
+        assert!(backtester.validate(&[], &candidate()).is_err());
+        assert_eq!(
+            backtester
+                .run_samples(&hourly_samples(&[10.0]), &candidate())
+                .unwrap_err(),
+            "not enough data: 1 samples, need at least 4"
+        );
+        assert_eq!(buy_and_hold_equity(&[], 1_000.0, 0.0, None), None);
+        assert_eq!(buy_and_hold_curve(&[], 1_000.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_latency_shifts_fills_forward_by_one_candle() {
+        // BUY from index 3 (rising), SELL from index 7 (falling).
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 10.0, 9.0, 8.0];
+        let samples = hourly_samples(&prices);
+
+        let immediate = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        let delayed = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .with_execution(ExecutionConfig {
+                latency_candles: 1,
+                ..Default::default()
+            })
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let trade = &immediate.trades[0];
+        assert_eq!(trade.entry_time, samples[3].ts);
+        assert_eq!(trade.entry_price, 11.0);
+        assert_eq!(trade.exit_time, samples[7].ts);
+        assert_eq!(trade.exit_price, 10.0);
+
+        let trade_delayed = &delayed.trades[0];
+        assert_eq!(trade_delayed.entry_time, samples[4].ts);
+        assert_eq!(trade_delayed.entry_price, 12.0);
+        assert_eq!(trade_delayed.exit_time, samples[8].ts);
+        assert_eq!(trade_delayed.exit_price, 9.0);
+    }
+
+    #[test]
+    fn test_max_hold_sells_the_holding_after_max_hold_candles() {
+        // Rising all the way: the bias never flips to SELL
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0]);
+        let result = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .with_max_hold_candles(Some(4))
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let trade = &result.trades[0];
+        assert_eq!(trade.entry_time, samples[3].ts);
+        assert_eq!(trade.exit_time, samples[7].ts);
+        assert_eq!(trade.exit_reason, MAX_HOLD_EXIT_REASON);
+        assert_eq!(trade.exit_price, 15.0);
+
+        let untimed = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        assert!(untimed.trades.is_empty());
+    }
+
+    #[test]
+    fn test_trailing_stop_sells_the_holding_before_the_sell_signal() {
+        // Long from 3 (11), high of 16 at 5: the 10% trail at 14.4 is crossed at 7,
+        // on the same candle the bias turns to SELL
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 13.0, 16.0, 15.0, 14.3]);
+
+        let result = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .with_trailing_stop_pct(Some(0.1))
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        assert_eq!(result.trades.len(), 1);
+        let trade = &result.trades[0];
+        assert_eq!(trade.exit_time, samples[7].ts);
+        assert_eq!(trade.exit_reason, TRAILING_STOP_EXIT_REASON);
+        assert!((trade.exit_price - 14.4).abs() < 1e-9);
+        assert!((result.final_equity - 1_000.0 / 11.0 * 14.4).abs() < 1e-9);
+        // Risked 1.1 a coin (to the first trail at 9.9), made 3.4
+        assert!((trade.r_multiple.unwrap() - 3.4 / 1.1).abs() < 1e-9);
+
+        let untrailed = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+        assert_eq!(untrailed.trades[0].exit_price, 14.3);
+        assert_ne!(untrailed.trades[0].exit_reason, TRAILING_STOP_EXIT_REASON);
+        assert_eq!(untrailed.trades[0].r_multiple, None);
+        assert!(untrailed.r_stats.is_none());
+    }
+
+    #[test]
+    fn test_cagr_uses_elapsed_time_between_first_and_last_point() {
+        let mut result = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .run_samples(&hourly_samples(&[10.0; 8]), &candidate())
+            .unwrap();
+        let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+
+        // 21% over two (365-day) years, with an uneven point in between
+        result.equity_curve = vec![
+            (start, 100.0),
+            (start + Duration::days(3), 150.0),
+            (start + Duration::days(730), 121.0),
+        ];
+        assert!((result.cagr() - 0.1).abs() < 1e-12);
+
+        result.equity_curve = vec![(start, 100.0), (start, 121.0)];
+        assert_eq!(result.cagr(), 0.0);
+        result.equity_curve.truncate(1);
+        assert_eq!(result.cagr(), 0.0);
+    }
+
+    #[test]
+    fn test_print_summary_writes_into_any_sink() {
+        let result = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .run_samples(&hourly_samples(&[10.0; 8]), &candidate())
+            .unwrap();
+
+        let mut out = Vec::new();
+        print_summary(&mut out, &result).unwrap();
+
+        let expected = [
+            "=== Backtest Summary ===",
+            "Initial equity:  1000.00",
+            "Final equity:     1000.00",
+            "Total return:     0.00%",
+            "CAGR:             0.00%",
+            "Max drawdown:     0.00%",
+            "DD from initial:  0.00%",
+            "Max log drawdown: 0.0000",
+            "Trades:           0",
+            "Win rate:         0.00%",
+            "Profit factor:    0.00",
+            "Avg win:          0.00",
+            "Avg loss:         0.00",
+            "Avg MFE:          0.00%",
+            "Avg MAE:          0.00%",
+            "",
+            "                    Strategy   Buy&hold",
+            "Total return:          0.00%      0.00%",
+            "Max drawdown:          0.00%      0.00%",
+            "Sharpe:                 0.00       0.00",
+            "CAGR:                  0.00%      0.00%",
+        ];
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            expected.map(|line| format!("{line}\n")).concat()
+        );
+    }
+
+    #[test]
+    fn test_benchmark_metrics_return_matches_buy_and_hold_equity() {
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 10.0, 9.0, 8.0];
+        let samples = hourly_samples(&prices);
+
+        let result = SpotBacktester::new(1_000.0, 5.0, 0.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        let benchmark = result.benchmark_metrics.expect("non-empty samples");
+        let hold_equity = buy_and_hold_equity(&samples, 1_000.0, 5.0, None).unwrap();
+        assert!(
+            (benchmark.total_return_pct - (hold_equity / result.initial_equity - 1.0)).abs()
+                < 1e-12
+        );
+        // Peak 14 -> trough 8
+        assert!((benchmark.max_drawdown_pct - 6.0 / 14.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sharpe_ratio_is_zero_for_flat_equity() {
+        let samples = hourly_samples(&[10.0; 8]);
+
+        let result = SpotBacktester::new(1_000.0, 5.0, 0.0)
+            .run_samples(&samples, &candidate())
+            .unwrap();
+
+        assert_eq!(result.sharpe_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_sub_cent_prices_trade_like_their_scaled_up_series() {
+        // Same shape as a normal series, scaled down to ~0.00000123
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 10.0, 9.0, 8.0];
+        let scale = 0.000_000_123;
+        let micro: Vec<f64> = prices.iter().map(|p| p * scale).collect();
+
+        let normal = SpotBacktester::new(1_000.0, 0.0, 10.0)
+            .run_samples(&hourly_samples(&prices), &candidate())
+            .unwrap();
+        let tiny = SpotBacktester::new(1_000.0, 0.0, 10.0)
+            .run_samples(&hourly_samples(&micro), &candidate())
+            .unwrap();
+
+        assert!(!tiny.trades.is_empty());
+        assert_eq!(tiny.trades.len(), normal.trades.len());
+        for (t, n) in tiny.trades.iter().zip(&normal.trades) {
+            let qty = t.entry_value / t.entry_price;
+            assert!(qty.is_finite() && qty > 0.0);
+            assert!((qty * scale / (n.entry_value / n.entry_price) - 1.0).abs() < 1e-9);
+            assert!(t.profit != 0.0);
+            assert!((t.profit - n.profit).abs() < 1e-6);
+            assert!((t.return_pct - n.return_pct).abs() < 1e-9);
+        }
+        assert!((tiny.total_return_pct - normal.total_return_pct).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_validate_flags_flat_fee_overspend() {
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 10.0, 9.0, 8.0];
+        let samples = hourly_samples(&prices);
+
+        // A fraction above 1 is clamped, so it still never spends more than the cash
+        let oversized = Candidate {
+            buy_sell_fraction: 2.0,
+            strategy: bias_only_strategy(),
+            stop: None,
+        };
+        let report = SpotBacktester::new(1_000.0, 0.0, 10.0)
+            .validate(&samples, &oversized)
+            .unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.candles_checked, samples.len());
+
+        // All-in buys plus a flat fee per fill push cash below zero on the first BUY
+        let report = SpotBacktester::new(1_000.0, 0.0, 10.0)
+            .with_flat_fee(5.0)
+            .validate(&samples, &candidate())
+            .unwrap();
+        let violation = report.violation.expect("overspend must be reported");
+        assert_eq!(violation.index, 3);
+        assert_eq!(violation.ts, samples[3].ts);
+        assert!(violation.message.contains("cash"), "{}", violation.message);
+        assert_eq!(report.candles_checked, 4);
+    }
+
+    #[test]
+    fn test_realized_equity_sizing_ignores_unrealized_rally() {
+        // BUY on 3, 4 and 5 while the price rallies; nothing is sold
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 11.0, 12.0, 13.0]);
+        let candidate = Candidate {
+            buy_sell_fraction: 0.3,
+            strategy: bias_only_strategy(),
+            stop: None,
+        };
+        let coin_bought = |sizing| {
+            SpotBacktester::new(1_000.0, 0.0, 0.0)
+                .with_execution(ExecutionConfig {
+                    sizing,
+                    ..Default::default()
+                })
+                .simulate(&samples, &candidate, false)
+                .book
+                .coin
+        };
+
+        let realized = coin_bought(SizingBase::RealizedEquity);
+        let equity = coin_bought(SizingBase::Equity);
+
+        // Realized equity stays at 1000, so every buy is 300
+        assert!((realized - (300.0 / 11.0 + 300.0 / 12.0 + 300.0 / 13.0)).abs() < 1e-9);
+        // Marked to market the later buys grow with the rally
+        assert!(equity > realized);
+    }
+
+    #[test]
+    fn test_slippage_reduces_realized_profit_on_the_same_signals() {
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 10.0, 9.0, 8.0];
+        let samples = hourly_samples(&prices);
+        let run = |slippage_bps| {
+            SpotBacktester::new(1_000.0, 0.0, 10.0)
+                .with_slippage_bps(slippage_bps)
+                .run_samples(&samples, &candidate())
+                .unwrap()
+        };
+
+        let exact = run(0.0);
+        let slipped = run(50.0);
+        assert_eq!(exact.trades.len(), slipped.trades.len());
+        assert!(!exact.trades.is_empty());
+        for (a, b) in exact.trades.iter().zip(&slipped.trades) {
+            assert_eq!(a.exit_time, b.exit_time);
+            assert!((b.exit_price - a.exit_price * 0.995).abs() < 1e-12);
+            // Paid more per coin on entry, received less on exit
+            assert!(b.entry_price > a.entry_price);
+            assert!(b.profit < a.profit);
+        }
+        assert!(slipped.total_return_pct < exact.total_return_pct);
+    }
+
+    #[test]
+    fn test_fee_sensitivity_return_falls_as_fees_rise() {
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0, 10.0, 9.0, 8.0];
+        let samples = hourly_samples(&prices);
+        let grid = [0.0, 10.0, 50.0, 100.0, 500.0];
+
+        let sensitivity = SpotBacktester::new(1_000.0, 0.0, 0.0)
+            .fee_sensitivity(&samples, &candidate(), &grid)
+            .unwrap();
+
+        let fees: Vec<f64> = sensitivity.iter().map(|&(fee, _)| fee).collect();
+        assert_eq!(fees, grid);
+        assert!(sensitivity.windows(2).all(|w| w[1].1 < w[0].1));
+    }
+
+    #[test]
+    fn test_initial_coin_cost_basis_sets_profit_on_existing_holdings() {
+        // Holding 1 coin from the start; the first SELL (bias flips at 6) sells all of it
+        let samples = hourly_samples(&[10.0, 10.0, 10.0, 9.0, 8.0, 7.0, 9.5]);
+        let sell_all = |cost_basis| {
+            SpotBacktester::new(0.0, 1.0, 0.0)
+                .with_initial_coin_cost_basis(cost_basis)
+                .run_samples(&samples, &candidate())
+                .unwrap()
+        };
+
+        let default = sell_all(None);
+        let paid = sell_all(Some(6.0));
+        assert_eq!(default.trades.len(), 1);
+        assert_eq!(default.trades[0].entry_price, 10.0);
+        assert!(default.trades[0].profit < 0.0);
+        assert_eq!(paid.trades[0].entry_price, 6.0);
+        assert!(paid.trades[0].profit > 0.0);
+        assert!((paid.trades[0].profit - default.trades[0].profit - 4.0).abs() < 1e-9);
+
+        // Equity is marked at market either way
+        assert_eq!(paid.final_equity, default.final_equity);
+    }
+
+    fn book(lot_matching: LotMatching) -> SpotBook {
+        let start = hourly_samples(&[1.0])[0].ts;
+        SpotBook {
+            cash: 1_000.0,
+            coin: 0.0,
+            cost_basis_total: 0.0,
+            in_position: false,
+            entry_time: start,
+            opened_at: 0,
+            avg_entry_price: 0.0,
+            high_since_entry: 0.0,
+            low_since_entry: 0.0,
+            flat_fee: 0.0,
+            slippage: 0.0,
+            realized_profit: 0.0,
+            trades: Vec::new(),
+            lot_matching,
+            lots: VecDeque::new(),
+            trailing_stop_pct: None,
+            qty_step: None,
+        }
+    }
+
+    #[test]
+    fn test_qty_step_buys_and_sells_whole_lots() {
+        let ts = hourly_samples(&[1.0, 1.0]);
+        let mut book = SpotBook {
+            qty_step: Some(4.0),
+            ..book(LotMatching::Average)
+        };
+
+        // 100 / 9 = 11.1 coins, rounded down to 8; the 28 left over stays in cash
+        book.buy(9.0, ts[0].ts, 0, 100.0, 1.0);
+        assert_eq!(book.coin, 8.0);
+        assert!((book.cash - 928.0).abs() < 1e-9);
+
+        // 60% of 8 coins is 4.8, rounded down to one lot
+        book.sell(10.0, ts[1].ts, 0.6, 1.0, "SELL");
+        assert_eq!(book.coin, 4.0);
+        assert!((book.trades[0].exit_value - 40.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_lot_matching_times_partial_sells_from_the_matched_buys() {
+        let ts = hourly_samples(&[1.0, 1.0, 1.0]);
+        let sell_half = |lot_matching| {
+            let mut book = book(lot_matching);
+            // 10 coins at 10, then 5 coins at 20; sell 7.5 at 30
+            book.buy(10.0, ts[0].ts, 0, 100.0, 1.0);
+            book.buy(20.0, ts[1].ts, 1, 100.0, 1.0);
+            book.sell(30.0, ts[2].ts, 0.5, 1.0, "SELL");
+            book.trades
+        };
+
+        let fifo = sell_half(LotMatching::Fifo);
+        assert_eq!(fifo.len(), 1);
+        assert_eq!(fifo[0].entry_time, ts[0].ts);
+        assert!((fifo[0].entry_price - 10.0).abs() < 1e-12);
+        assert!((fifo[0].profit - 7.5 * 20.0).abs() < 1e-9);
+
+        // The whole second lot, then 2.5 coins of the first
+        let lifo = sell_half(LotMatching::Lifo);
+        assert_eq!(lifo.len(), 2);
+        assert_eq!(lifo[0].entry_time, ts[1].ts);
+        assert!((lifo[0].entry_price - 20.0).abs() < 1e-12);
+        assert_eq!(lifo[1].entry_time, ts[0].ts);
+        assert!((lifo[1].entry_value - 25.0).abs() < 1e-9);
+
+        // Average: one trade at the blended cost, timed from the first buy
+        let average = sell_half(LotMatching::Average);
+        assert_eq!(average.len(), 1);
+        assert_eq!(average[0].entry_time, ts[0].ts);
+        assert!((average[0].entry_price - 200.0 / 15.0).abs() < 1e-9);
+    }
 }
@@ -1,23 +1,37 @@
 use std::path::PathBuf;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use chrono::Duration;
 use clap::Parser;
 use serde::Deserialize;
 use trade_signal::indicators::sma::SmaConfig;
-use trade_signal::indicators::{AtrFilter, RegimeFilter};
-use trade_signal::signal::{BreakoutConfig, FilterConfig, PullbackConfig, StrategyConfig};
+use trade_signal::indicators::{AtrFilter, RegimeFilter, RsiFilter};
+use trade_signal::signal::{
+    BollingerConfig, BreakoutConfig, BreakoutTrigger, FilterConfig, MacdConfig, PullbackConfig,
+    PullbackToleranceMode, RetestConfig, RoundNumberFilter, RuleKind, StrategyConfig, TrendSource,
+    VolumeFilter,
+};
 
 use trade_signal::backtest::position::{
-    NdjsonLogger, PositionBacktester, buy_and_hold_equity, print_summary,
+    ExitTargets, NdjsonLogger, PositionBacktester, ReversalMode, buy_and_hold_equity, print_summary,
+};
+use trade_signal::backtest::{
+    Candidate, ExecutionConfig, ExecutionStyle, KellySizing, StopPlacement,
+};
+use trade_signal::data::{
+    describe_gaps, get_samples_from_input_file, resample_by_count, resample_to_n_hours_checked,
+    validate_dataset,
 };
-use trade_signal::backtest::{Backtester, Candidate};
-use trade_signal::data::{get_samples_from_input_file, resample_to_n_hours};
 
 #[derive(Debug, Parser)]
 struct Args {
     /// config-file path
     #[arg(long)]
     config: PathBuf,
+
+    /// Optional path to dump the per-candle chart data (SMAs, executed actions, equity) as CSV
+    #[arg(long)]
+    export_chart: Option<PathBuf>,
 }
 
 #[derive(Deserialize)]
@@ -28,6 +42,9 @@ struct Config {
     /// Resample input to <sample_hours> hours (i.e. 1h, 4h, 6h, ...)
     sample_hours: i64,
 
+    /// Build candles from every N ticks instead of by time (overrides sample_hours)
+    ticks_per_candle: Option<usize>,
+
     /// Initial cash for the backtest
     initial_cash: f64,
 
@@ -40,33 +57,179 @@ struct Config {
     /// Whether regime filter should be used
     regime_enabled: bool,
 
+    /// RSI period of the overbought/oversold gate; do not set to disable
+    rsi_period: Option<usize>,
+
+    /// RSI above which longs are vetoed (defaults to 70)
+    rsi_overbought: Option<f64>,
+
+    /// RSI below which shorts are vetoed (defaults to 30)
+    rsi_oversold: Option<f64>,
+
+    /// Candles of average volume a breakout candle's volume is compared with; do not
+    /// set to disable volume confirmation (it does nothing on input without volume)
+    volume_lookback: Option<usize>,
+
+    /// Multiple of that average a breakout candle's volume must reach (defaults to 1.0)
+    volume_min_ratio: Option<f64>,
+
     /// How many candles to lookback for a breakdown
     /// Do not set to not use breakout patterns
     breakout_lookback: Option<usize>,
 
+    /// Bars the SMA trend must have held before a breakout counts (defaults to 0)
+    breakout_min_trend_bars: Option<usize>,
+
+    /// Only act on a breakout once price has retested the broken level and held,
+    /// within this many candles. Do not set to act on the breakout itself
+    breakout_retest_lookahead: Option<usize>,
+
+    /// How close to the broken level counts as a retest (defaults to 0.002 = 0.2%)
+    breakout_retest_tolerance_pct: Option<f64>,
+
+    /// What has to clear the recent range: "close" (default) or "high" (the candle's
+    /// high/low, on input that has them)
+    breakout_trigger: Option<BreakoutTrigger>,
+
+    /// Skip entries near multiples of this price (e.g. 1000); do not set to disable
+    round_number_grid: Option<f64>,
+
+    /// Width of the no-trade zone around each round number (e.g. 0.001 = 0.1%)
+    round_number_band_pct: Option<f64>,
+
+    /// Breakouts and crossovers must move at least this many ATRs (breakout clearance,
+    /// SMA spread) to count, e.g. 0.5. Do not set to take every signal
+    min_signal_atr_mult: Option<f64>,
+
+    /// Hold when candles behind the SMA/ATR windows are more than this many hours
+    /// apart. Do not set to ignore gaps in the data
+    max_candle_gap_hours: Option<i64>,
+
     /// Do not set to not use pullback patterns
     pullback_bounce_tolerance_pct: Option<f64>,
 
     /// Do not set to not use pullback patterns
     pullback_rejection_tolerance_pct: Option<f64>,
 
+    /// Unit of the pullback tolerances: "percent" (default) or "atr"
+    pullback_tolerance_mode: Option<PullbackToleranceMode>,
+
     /// Whether sma crossover signals should be used
     enable_crossovers: bool,
 
     /// Whether bias_only signals should be used
     enable_bias_only: bool,
 
+    /// (fast, slow, signal) periods of a MACD-cross rule, e.g. [12, 26, 9].
+    /// Do not set to not use MACD
+    macd_periods: Option<(usize, usize, usize)>,
+
+    /// (period, num_std) of a Bollinger mean-reversion rule, e.g. [20, 2.0].
+    /// Do not set to not use Bollinger Bands
+    bollinger: Option<(usize, f64)>,
+
+    /// Rules to try first, e.g. ["pullback", "breakout"]; the first firing rule wins.
+    /// Other enabled rules follow in the standard order (breakout, pullback,
+    /// crossover, macd, bollinger, bias_only). Do not set for the standard order
+    rule_order: Option<Vec<RuleKind>>,
+
     /// SMA short window
     sma_short_window: usize,
 
     /// SMA long window
     sma_long_window: usize,
 
+    /// Minimum relative gap between the SMAs for an up/down trend (e.g. 0.001 = 0.1%);
+    /// closer SMAs read as flat (defaults to 0)
+    min_sma_separation_pct: Option<f64>,
+
     /// Whether price confirmation is required
     require_price_confirmation: bool,
 
     /// Whether trend filter is required
     require_trend_filter: bool,
+
+    /// What the trend filter looks at: "sma_slope" (default) or "regime"
+    trend_source: Option<TrendSource>,
+
+    /// Ordered SMA windows (fastest first) that must be stacked in trade direction,
+    /// e.g. [10, 20, 50]. Do not set to not use the MA-stack filter
+    ma_stack_windows: Option<Vec<usize>>,
+
+    /// Delay every fill by this many candles (defaults to 0)
+    latency_candles: Option<usize>,
+
+    /// Losing trades in a row before pausing entries (defaults to 0 = never)
+    max_consecutive_losses: Option<usize>,
+
+    /// Candles to sit out after a losing streak (defaults to 0)
+    cooldown_candles: Option<usize>,
+
+    /// Candles an entry signal must persist before acting (defaults to 0 = immediately)
+    confirm_candles: Option<usize>,
+
+    /// Split each order into this many slices, one per candle at that candle's price
+    /// (TWAP); an opposite signal cancels the slices left. Do not set to fill at once
+    twap_candles: Option<usize>,
+
+    /// Only keep the price history the strategy looks back over (defaults to false)
+    bounded_history: Option<bool>,
+
+    /// Shrink a long SMA window the data is too short for instead of failing
+    /// (defaults to false)
+    fit_long_window: Option<bool>,
+
+    /// Lot size: trade quantities, and the buy & hold benchmark's, are rounded down to
+    /// whole multiples of it (defaults to any fraction)
+    qty_step: Option<f64>,
+
+    /// Keep every Nth point of the reported equity curve (defaults to 1 = all)
+    equity_curve_stride: Option<usize>,
+
+    /// Size entries at a fraction of the Kelly criterion measured over the last
+    /// kelly_window closed trades. Do not set to use the fixed fraction
+    kelly_window: Option<usize>,
+
+    /// Share of full Kelly to bet (defaults to 0.5)
+    kelly_fraction: Option<f64>,
+
+    /// Largest entry fraction Kelly sizing may use (defaults to 1.0)
+    kelly_max_fraction: Option<f64>,
+
+    /// Leave the position force-closed at the end of the data out of win rate (defaults to false)
+    exclude_eof_from_stats: Option<bool>,
+
+    /// Only let breakout/pullback/crossover signals close or reverse a position; bias-only
+    /// signals can still open one (defaults to false)
+    strong_exits_only: Option<bool>,
+
+    /// "immediate" (default) or "flat_first": go flat for a signal before reversing
+    reversal_mode: Option<ReversalMode>,
+
+    /// Stop each position this fraction from its entry price (e.g. 0.02 = 2%), filled at
+    /// that level; do not set to hold until a signal exits it
+    stop_loss_pct: Option<f64>,
+
+    /// Close a position this fraction in its favor (e.g. 0.05 = 5%); do not set to
+    /// hold until a signal exits it
+    take_profit_pct: Option<f64>,
+
+    /// Close a position once price gives back this fraction from its best level since
+    /// entry (e.g. 0.05 = 5%); do not set to disable
+    trailing_stop_pct: Option<f64>,
+
+    /// Stop each position at the swing low (long) / high (short) of this many candles
+    /// before entry instead, filled at that level; can't be combined with stop_loss_pct
+    stop_swing_lookback: Option<usize>,
+
+    /// Move the stop to the entry price once a position is up this fraction
+    /// (e.g. 0.03 = 3%). Do not set to leave stops where they were placed
+    move_stop_to_breakeven_at_pct: Option<f64>,
+
+    /// Close a position this many candles after entry regardless of signals.
+    /// Do not set to hold until an exit signal
+    #[serde(alias = "max_age_candles")]
+    max_hold_candles: Option<usize>,
 }
 
 fn main() -> Result<()> {
@@ -84,20 +247,30 @@ fn main() -> Result<()> {
     let samples = get_samples_from_input_file(&config.input)
         .with_context(|| format!("failed to load samples from {:?}", config.input))?;
 
-    if samples.is_empty() {
-        println!("No data found in CSV.");
-        return Ok(());
-    }
-
-    let resampled = resample_to_n_hours(&samples, config.sample_hours);
+    let ((resampled, gaps), candle_label) = match config.ticks_per_candle {
+        // Tick bars have no time buckets to leave empty
+        Some(n) => (
+            (resample_by_count(&samples, n)?, Vec::new()),
+            format!("{n}-tick"),
+        ),
+        None => (
+            resample_to_n_hours_checked(&samples, config.sample_hours),
+            format!("{}h", config.sample_hours),
+        ),
+    };
 
     println!(
-        "Loaded {} raw points, {} {}h-candles after resampling.",
+        "Loaded {} raw points, {} {}-candles after resampling.",
         samples.len(),
         resampled.len(),
-        config.sample_hours,
+        candle_label,
     );
+    if let Some(gaps) = describe_gaps(&gaps) {
+        println!("Warning: {gaps} in the resampled data");
+    }
+    validate_dataset(&resampled)?;
 
+    let tolerance_mode = config.pullback_tolerance_mode.unwrap_or_default();
     let pullbacks = match (
         config.pullback_bounce_tolerance_pct,
         config.pullback_rejection_tolerance_pct,
@@ -105,6 +278,7 @@ fn main() -> Result<()> {
         (Some(bounce_tolerance_pct), Some(reject_tolerance_pct)) => Some(PullbackConfig {
             bounce_tolerance_pct,
             reject_tolerance_pct,
+            tolerance_mode,
         }),
         (None, None) => None,
         (Some(v), None) => {
@@ -112,6 +286,7 @@ fn main() -> Result<()> {
             Some(PullbackConfig {
                 bounce_tolerance_pct: v,
                 reject_tolerance_pct: v,
+                tolerance_mode,
             })
         }
         (None, Some(v)) => {
@@ -119,6 +294,7 @@ fn main() -> Result<()> {
             Some(PullbackConfig {
                 bounce_tolerance_pct: v,
                 reject_tolerance_pct: v,
+                tolerance_mode,
             })
         }
     };
@@ -126,50 +302,132 @@ fn main() -> Result<()> {
     let strategy = StrategyConfig {
         breakouts: config.breakout_lookback.map(|v| BreakoutConfig {
             breakout_lookback: v,
+            min_trend_bars: config.breakout_min_trend_bars.unwrap_or(0),
+            require_retest: config
+                .breakout_retest_lookahead
+                .map(|lookahead| RetestConfig {
+                    lookahead,
+                    tolerance_pct: config.breakout_retest_tolerance_pct.unwrap_or(0.002),
+                }),
+            breakout_trigger: config.breakout_trigger.unwrap_or_default(),
         }),
         pullbacks,
         enable_crossovers: config.enable_crossovers,
         enable_bias_only: config.enable_bias_only,
+        enable_macd: config.macd_periods.map(|(fast, slow, signal)| MacdConfig {
+            fast,
+            slow,
+            signal,
+        }),
+        rule_order: config.rule_order.unwrap_or_default(),
+        bollinger: config
+            .bollinger
+            .map(|(period, num_std)| BollingerConfig { period, num_std }),
         sma_config: SmaConfig {
-            short_window: config.sma_short_window,
-            long_window: config.sma_long_window,
+            stack_windows: config.ma_stack_windows.clone().unwrap_or_default(),
+            min_separation_pct: config.min_sma_separation_pct.unwrap_or(0.0),
+            ..SmaConfig::new(config.sma_short_window, config.sma_long_window)
         },
         filters: FilterConfig {
             require_price_confirmation: config.require_price_confirmation,
             require_trend_filter: config.require_trend_filter,
+            trend_source: config.trend_source.unwrap_or_default(),
+            require_ma_stack: config.ma_stack_windows.is_some(),
             atr: if config.atr_enabled {
                 Some(AtrFilter::backtest())
             } else {
                 None
             },
+            rsi: config.rsi_period.map(|period| RsiFilter {
+                period,
+                overbought: config.rsi_overbought.unwrap_or(70.0),
+                oversold: config.rsi_oversold.unwrap_or(30.0),
+            }),
+            volume: config.volume_lookback.map(|lookback| VolumeFilter {
+                lookback,
+                min_ratio: config.volume_min_ratio.unwrap_or(1.0),
+            }),
             regime: if config.regime_enabled {
                 Some(RegimeFilter::backtest())
             } else {
                 None
             },
+            round_number: config.round_number_grid.map(|grid| RoundNumberFilter {
+                grid,
+                band_pct: config.round_number_band_pct.unwrap_or(0.001),
+            }),
+            min_signal_atr_mult: config.min_signal_atr_mult,
+            max_candle_gap: config.max_candle_gap_hours.map(Duration::hours),
         },
     };
+    strategy.validate().map_err(anyhow::Error::msg)?;
 
     let candidate = Candidate {
         buy_sell_fraction: config.buy_fraction,
         strategy,
+        stop: match (config.stop_loss_pct, config.stop_swing_lookback) {
+            (Some(_), Some(_)) => {
+                bail!("set either stop_loss_pct or stop_swing_lookback, not both")
+            }
+            (Some(pct), None) => Some(StopPlacement::Percent(pct)),
+            (None, Some(lookback)) => Some(StopPlacement::SwingLookback(lookback)),
+            (None, None) => None,
+        },
     };
 
     println!("Initial cash:      {}", config.initial_cash);
     println!("Buy fraction:      {}", config.buy_fraction);
-    println!("Strategy:          {}", strategy.describe_config());
+    println!(
+        "Strategy:          {}",
+        candidate.strategy.describe_config()
+    );
 
     let log_path = log_path_unix("position_backtest");
     let position_logger = NdjsonLogger::new(log_path);
-    let backtester = PositionBacktester::with_logger(config.initial_cash, position_logger);
-    let result = backtester.run_backtest(&resampled, &candidate).unwrap();
+    let backtester = PositionBacktester::with_logger(config.initial_cash, position_logger)
+        .with_execution(ExecutionConfig {
+            latency_candles: config.latency_candles.unwrap_or(0),
+            max_consecutive_losses: config.max_consecutive_losses.unwrap_or(0),
+            cooldown_candles: config.cooldown_candles.unwrap_or(0),
+            confirm_candles: config.confirm_candles.unwrap_or(0),
+            style: match config.twap_candles {
+                Some(candles) => ExecutionStyle::Twap { candles },
+                None => ExecutionStyle::Immediate,
+            },
+            bounded_history: config.bounded_history.unwrap_or(false),
+            fit_long_window: config.fit_long_window.unwrap_or(false),
+            qty_step: config.qty_step,
+            equity_curve_stride: config.equity_curve_stride.unwrap_or(1),
+            kelly: config.kelly_window.map(|window| KellySizing {
+                window,
+                fraction: config.kelly_fraction.unwrap_or(0.5),
+                max_fraction: config.kelly_max_fraction.unwrap_or(1.0),
+            }),
+            ..Default::default()
+        })
+        .with_reversal_mode(config.reversal_mode.unwrap_or_default())
+        .with_exclude_eof_from_stats(config.exclude_eof_from_stats.unwrap_or(false))
+        .with_strong_exits_only(config.strong_exits_only.unwrap_or(false))
+        .with_exit_targets(ExitTargets {
+            take_profit_pct: config.take_profit_pct,
+            trailing_stop_pct: config.trailing_stop_pct,
+        })
+        .with_move_stop_to_breakeven_at_pct(config.move_stop_to_breakeven_at_pct)
+        .with_max_hold_candles(config.max_hold_candles);
+    let result = backtester.run_samples(&resampled, &candidate).unwrap();
 
-    print_summary(&result);
-    if let Some(hold_equity) = buy_and_hold_equity(&resampled, config.initial_cash) {
+    print_summary(&mut std::io::stdout(), &result)?;
+    if let Some(hold_equity) = buy_and_hold_equity(&resampled, config.initial_cash, config.qty_step)
+    {
         println!();
         println!("Buy & hold final equity: {:.2}", hold_equity);
     }
 
+    if let Some(path) = &args.export_chart {
+        trade_signal::export::write_chart_csv(&resampled, &result, path)?;
+        println!("Chart written to {:?}", path);
+    }
+
     Ok(())
 }
 
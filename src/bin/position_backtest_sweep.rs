@@ -1,14 +1,23 @@
+use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::{Context, Result};
 use clap::Parser;
 use serde::Deserialize;
 use trade_signal::backtest::{
-    find_best_strategy, generate_backtest_sweep_jobs, generate_pullback_pairs, generate_strategies,
+    Benchmark, Candidate, EarlyStop, ExecutionConfig, StrategyRanges, SweepMetric, SweepOptions,
+    SweepOutcome, crossover_strategy, find_best_strategy, generate_backtest_sweep_jobs,
+    generate_pullback_pairs, generate_random_strategies, generate_strategies,
+    generate_strategies_for_windows,
 };
 
 use trade_signal::backtest::position::{PositionBacktester, buy_and_hold_equity, print_summary};
-use trade_signal::data::{get_samples_from_input_file, resample_to_n_hours};
+use trade_signal::data::{
+    describe_gaps, get_samples_from_input_file, resample_to_n_hours_checked, validate_dataset,
+};
+use trade_signal::indicators::sma::SmaConfig;
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -34,6 +43,10 @@ struct Config {
     /// Max breakout lookback window (e.g. 10)
     max_lookback: usize,
 
+    /// (short, long) SMA window pairs to sweep, e.g. [[10, 40], [20, 60]].
+    /// Do not set to use the built-in grid.
+    sma_windows: Option<Vec<(usize, usize)>>,
+
     /// Min pullback tolerances (e.g. 0.001)
     min_pullback_pct: f64,
 
@@ -46,6 +59,57 @@ struct Config {
     /// Number of steps for buy/sell fraction (0–1).
     /// E.g. 100 => 0.01, 0.02, ..., 1.00
     buy_sell_frac_steps: usize,
+
+    /// Stop the sweep at the first configuration reaching this value of
+    /// `early_stop_metric` (e.g. 0.2 = 20% total return, or a Sharpe of 2.0).
+    /// Do not set to run the full sweep.
+    #[serde(alias = "early_stop_return")]
+    early_stop_target: Option<f64>,
+
+    /// Metric `early_stop_target` applies to: "total_return" (default) or "sharpe"
+    early_stop_metric: Option<SweepMetric>,
+
+    /// Minimum number of trades for a configuration to be eligible (defaults to 0)
+    min_trades: Option<usize>,
+
+    /// (short, long) SMA windows of a plain crossover strategy to compare every
+    /// configuration against, e.g. [20, 50]. Do not set to skip the benchmark.
+    benchmark_sma_windows: Option<(usize, usize)>,
+
+    /// Only accept configurations beating the benchmark's return, and measure
+    /// `early_stop_target` as excess over it (defaults to false)
+    benchmark_relative: Option<bool>,
+
+    /// What the best configuration maximizes: "total_return" (default) or "sharpe"
+    /// (annualized assuming hourly candles)
+    rank_by: Option<SweepMetric>,
+
+    /// Shrink long SMA windows the data is too short for instead of skipping
+    /// those configurations (defaults to false)
+    fit_long_window: Option<bool>,
+
+    /// Lot size: trade quantities, and the buy & hold benchmark's, are rounded down to
+    /// whole multiples of it (defaults to any fraction)
+    qty_step: Option<f64>,
+
+    /// Evaluate random configurations instead of the full grid.
+    /// Lookback and pullback ranges come from the min/max settings above.
+    random_search: Option<RandomSearch>,
+}
+
+#[derive(Deserialize)]
+struct RandomSearch {
+    /// Number of configurations to sample
+    count: usize,
+
+    /// Seed for reproducible samples (defaults to 0)
+    seed: Option<u64>,
+
+    /// Inclusive (min, max) SMA(short) window, e.g. [5, 50]
+    short_window: (usize, usize),
+
+    /// Inclusive (min, max) SMA(long) window, e.g. [20, 200]
+    long_window: (usize, usize),
 }
 
 fn main() -> Result<()> {
@@ -63,55 +127,131 @@ fn main() -> Result<()> {
     let samples = get_samples_from_input_file(&config.input)
         .with_context(|| format!("failed to load samples from {:?}", config.input))?;
 
-    if samples.is_empty() {
-        println!("No data found in CSV.");
-        return Ok(());
-    }
+    let (resampled, gaps) = resample_to_n_hours_checked(&samples, config.sample_hours);
 
-    let resampled = resample_to_n_hours(&samples, config.sample_hours);
-
-    println!(
+    let mut out = std::io::stdout();
+    writeln!(
+        out,
         "Loaded {} raw points, {} {}h-candles after resampling.",
         samples.len(),
         resampled.len(),
         config.sample_hours,
-    );
+    )?;
+    if let Some(gaps) = describe_gaps(&gaps) {
+        writeln!(out, "Warning: {gaps} in the resampled data")?;
+    }
+    validate_dataset(&resampled)?;
 
     let pullback_pairs =
         generate_pullback_pairs(config.min_pullback_pct, config.max_pullback_pct, 0.001);
 
-    let strategies = generate_strategies(config.min_lookback, config.max_lookback, pullback_pairs);
+    let strategies = match (&config.random_search, &config.sma_windows) {
+        (Some(search), _) => {
+            let ranges = StrategyRanges {
+                short_window: search.short_window,
+                long_window: search.long_window,
+                breakout_lookback: (config.min_lookback, config.max_lookback),
+                pullback_pct: (config.min_pullback_pct, config.max_pullback_pct),
+            };
+            generate_random_strategies(
+                &ranges,
+                search.count,
+                search.seed.unwrap_or(0),
+                &mut std::io::stderr(),
+            )?
+        }
+        (None, Some(windows)) => generate_strategies_for_windows(
+            windows,
+            config.min_lookback,
+            config.max_lookback,
+            pullback_pairs,
+            &mut std::io::stderr(),
+        )?,
+        (None, None) => {
+            generate_strategies(config.min_lookback, config.max_lookback, pullback_pairs)
+        }
+    };
 
     let buy_sell_frac_steps = config.buy_sell_frac_steps;
 
     let jobs = generate_backtest_sweep_jobs(strategies, buy_sell_frac_steps);
 
+    let options = SweepOptions {
+        early_stop: config.early_stop_target.map(|target| EarlyStop {
+            metric: config.early_stop_metric.unwrap_or_default(),
+            target,
+        }),
+        min_trades: config.min_trades.unwrap_or(0),
+        cancel: Some(cancel_on_ctrl_c()?),
+        benchmark: config.benchmark_sma_windows.map(|(short, long)| Benchmark {
+            candidate: Candidate {
+                buy_sell_fraction: config.max_buy_sell_fraction,
+                strategy: crossover_strategy(SmaConfig::new(short, long)),
+                stop: None,
+            },
+            relative: config.benchmark_relative.unwrap_or(false),
+        }),
+        rank_by: config.rank_by.unwrap_or_default(),
+    };
+
     let best = find_best_strategy(
         jobs,
         config.max_buy_sell_fraction,
         buy_sell_frac_steps,
         &samples,
-        || PositionBacktester::new(config.initial_cash),
-    );
-
-    println!();
-    if let Some((candidate, result)) = best {
-        println!("=== Best configuration ===");
-        println!(
-            "strategy:          {}",
-            candidate.strategy.describe_config()
-        );
-        println!("buy_fraction:      {:.2}", candidate.buy_sell_fraction);
-        println!();
-        print_summary(&result);
-
-        if let Some(hold_equity) = buy_and_hold_equity(&samples, result.initial_equity) {
-            println!();
-            println!("Buy & hold final equity: {:.2}", hold_equity);
+        &options,
+        &mut out,
+        || {
+            PositionBacktester::new(config.initial_cash).with_execution(ExecutionConfig {
+                fit_long_window: config.fit_long_window.unwrap_or(false),
+                qty_step: config.qty_step,
+                ..Default::default()
+            })
+        },
+    )?;
+
+    writeln!(out)?;
+    match best {
+        SweepOutcome::Best(candidate, result, excess) => {
+            writeln!(out, "=== Best configuration ===")?;
+            writeln!(
+                out,
+                "strategy:          {}",
+                candidate.strategy.describe_config()
+            )?;
+            writeln!(out, "buy_fraction:      {:.2}", candidate.buy_sell_fraction)?;
+            writeln!(out)?;
+            print_summary(&mut out, &result)?;
+
+            if let Some(excess) = excess {
+                writeln!(out)?;
+                writeln!(out, "Best vs benchmark: {excess}")?;
+            }
+
+            if let Some(hold_equity) =
+                buy_and_hold_equity(&samples, result.initial_equity, config.qty_step)
+            {
+                writeln!(out)?;
+                writeln!(out, "Buy & hold final equity: {:.2}", hold_equity)?;
+            }
         }
-    } else {
-        println!("No valid backtest result produced.");
+        SweepOutcome::NoTradingStrategy => writeln!(
+            out,
+            "No trading strategy found: the best candidate made no trades."
+        )?,
+        SweepOutcome::NoResult => writeln!(out, "No valid backtest result produced.")?,
     }
 
     Ok(())
 }
+
+/// Flag raised on Ctrl-C so the sweep stops and reports its best result so far.
+fn cancel_on_ctrl_c() -> Result<Arc<AtomicBool>> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    ctrlc::set_handler(move || {
+        println!("Ctrl-C received, finishing running jobs...");
+        flag.store(true, Ordering::Relaxed);
+    })?;
+    Ok(cancel)
+}
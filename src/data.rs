@@ -1,50 +1,223 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use chrono::{DateTime, Duration, TimeZone, Utc};
-use csv::ReaderBuilder;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use std::collections::BTreeMap;
-use std::fs::File;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::Path;
 
+use crate::source::{CsvSource, collect_samples};
+
+/// One CSV row: `timestamp,price[,volume]`, or OHLC
+/// `timestamp,open,high,low,close[,volume]` (the open isn't used).
 #[derive(Debug, Deserialize)]
 pub struct PriceRow {
     pub timestamp: String,
+    #[serde(alias = "close")]
     pub price: f64,
+    #[serde(default)]
+    pub high: Option<f64>,
+    #[serde(default)]
+    pub low: Option<f64>,
+    /// Optional `volume` column.
+    #[serde(default)]
+    pub volume: Option<f64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Sample {
     pub ts: DateTime<Utc>,
+    /// Close of the sample.
     pub price: f64,
+    /// Highest / lowest price over the sample, if the source has OHLC data.
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    /// Volume traded over the sample, if the source has it.
+    pub volume: Option<f64>,
 }
 
-pub fn get_samples_from_input_file(input: &PathBuf) -> Result<Vec<Sample>> {
-    let file =
-        File::open(input).with_context(|| format!("failed to open input file: {:?}", input))?;
+impl Sample {
+    /// The sample's high, or its close for close-only data.
+    pub fn high_or_close(&self) -> f64 {
+        self.high.unwrap_or(self.price)
+    }
 
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+    /// The sample's low, or its close for close-only data.
+    pub fn low_or_close(&self) -> f64 {
+        self.low.unwrap_or(self.price)
+    }
 
-    let mut samples: Vec<Sample> = Vec::new();
+    /// Widen this sample's high/low to cover `other`'s. Stays None if neither has one.
+    fn merge_range(&mut self, other: &Sample) {
+        if self.high.is_some() || self.low.is_some() || other.high.is_some() || other.low.is_some()
+        {
+            self.high = Some(self.high_or_close().max(other.high_or_close()));
+            self.low = Some(self.low_or_close().min(other.low_or_close()));
+        }
+    }
 
-    for result in rdr.deserialize::<PriceRow>() {
-        let row: PriceRow = result.with_context(|| "failed to deserialize CSV row")?;
-        let ts = DateTime::parse_from_rfc3339(&row.timestamp)
-            .with_context(|| format!("failed to parse timestamp: {}", row.timestamp))?
-            .with_timezone(&Utc);
-        samples.push(Sample {
-            ts,
-            price: row.price,
-        });
+    /// Fold a later tick of the same candle into it: widen high/low, add its volume
+    /// and close on its price and timestamp.
+    pub(crate) fn absorb(&mut self, later: &Sample) {
+        self.merge_range(later);
+        self.volume = add_volume(self.volume, later.volume);
+        self.ts = later.ts;
+        self.price = later.price;
     }
-    Ok(samples)
+}
+
+/// Why a dataset can't go through a pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataError {
+    Empty,
+    TooShort {
+        have: usize,
+        need: usize,
+    },
+    /// The sample at `index` isn't later than the one before it.
+    OutOfOrder {
+        index: usize,
+    },
+    /// The sample at `index` has no volume.
+    MissingVolume {
+        index: usize,
+    },
+    /// A resampler was asked for bars it can't build, e.g. a volume target that isn't
+    /// a positive number.
+    InvalidBarSize(String),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no samples"),
+            Self::TooShort { have, need } => {
+                write!(f, "not enough data: {have} samples, need at least {need}")
+            }
+            Self::OutOfOrder { index } => write!(
+                f,
+                "samples are not in increasing time order: sample {index} doesn't follow sample {}",
+                index - 1
+            ),
+            Self::MissingVolume { index } => write!(f, "sample {index} has no volume"),
+            Self::InvalidBarSize(reason) => write!(f, "invalid bar size: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+/// Checks every pipeline runs on its input first: at least one sample, and
+/// strictly increasing timestamps.
+pub fn validate_dataset(samples: &[Sample]) -> Result<(), DataError> {
+    if samples.is_empty() {
+        return Err(DataError::Empty);
+    }
+    match samples.windows(2).position(|w| w[1].ts <= w[0].ts) {
+        Some(i) => Err(DataError::OutOfOrder { index: i + 1 }),
+        None => Ok(()),
+    }
+}
+
+pub fn get_samples_from_input_file(input: &Path) -> Result<Vec<Sample>> {
+    let mut source = CsvSource::open(input)?;
+    collect_samples(&mut source)
+}
+
+/// First bytes of a `write_candles_bin` file.
+#[cfg(feature = "binary")]
+const CANDLES_BIN_MAGIC: &[u8; 4] = b"TSCB";
+/// Bumped whenever the record layout changes. Version 1 (unversioned, close only)
+/// files are rejected.
+#[cfg(feature = "binary")]
+const CANDLES_BIN_VERSION: u32 = 2;
+
+/// Timestamp in microseconds since epoch, close, high, low, volume.
+#[cfg(feature = "binary")]
+type CandleRecord = (i64, f64, Option<f64>, Option<f64>, Option<f64>);
+
+/// Store candles as `CANDLES_BIN_MAGIC`, then a bincode `CANDLES_BIN_VERSION` and
+/// length-prefixed list of `CandleRecord`s.
+/// Much faster to load than CSV for repeated sweeps over the same data.
+#[cfg(feature = "binary")]
+pub fn write_candles_bin(candles: &[Sample], path: &Path) -> Result<()> {
+    use anyhow::Context;
+    use std::io::Write;
+
+    let rows: Vec<CandleRecord> = candles
+        .iter()
+        .map(|c| (c.ts.timestamp_micros(), c.price, c.high, c.low, c.volume))
+        .collect();
+
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("failed to create candle file: {:?}", path))?;
+    let mut writer = std::io::BufWriter::new(file);
+    writer
+        .write_all(CANDLES_BIN_MAGIC)
+        .with_context(|| format!("failed to write candle file: {:?}", path))?;
+    bincode::serialize_into(&mut writer, &(CANDLES_BIN_VERSION, &rows))
+        .with_context(|| format!("failed to encode candles: {:?}", path))?;
+    writer
+        .flush()
+        .with_context(|| format!("failed to write candle file: {:?}", path))?;
+    Ok(())
+}
+
+/// Load candles written by `write_candles_bin`. Files from another format version
+/// are an error; re-export them from CSV.
+#[cfg(feature = "binary")]
+pub fn read_candles_bin(path: &Path) -> Result<Vec<Sample>> {
+    use anyhow::{Context, bail};
+    use std::io::Read;
+
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open candle file: {:?}", path))?;
+    let mut reader = std::io::BufReader::new(file);
+    let mut magic = [0u8; 4];
+    if reader.read_exact(&mut magic).is_err() || &magic != CANDLES_BIN_MAGIC {
+        bail!(
+            "not a candle file, or one from before format version {CANDLES_BIN_VERSION}: {:?}",
+            path
+        );
+    }
+    let version: u32 = bincode::deserialize_from(&mut reader)
+        .with_context(|| format!("failed to decode candles: {:?}", path))?;
+    if version != CANDLES_BIN_VERSION {
+        bail!(
+            "unsupported candle file version {version} (expected {CANDLES_BIN_VERSION}): {:?}",
+            path
+        );
+    }
+    let rows: Vec<CandleRecord> = bincode::deserialize_from(reader)
+        .with_context(|| format!("failed to decode candles: {:?}", path))?;
+
+    rows.into_iter()
+        .map(|(micros, price, high, low, volume)| {
+            let ts = DateTime::from_timestamp_micros(micros)
+                .with_context(|| format!("timestamp out of range: {micros}"))?;
+            Ok(Sample {
+                ts,
+                price,
+                high,
+                low,
+                volume,
+            })
+        })
+        .collect()
 }
 
 /// Resample raw samples into fixed-size buckets (1h, 2h, 4h, ...),
 /// keeping the *last* price available in each bucket.
 /// - Bucket alignment is to Unix epoch (1970-01-01T00:00:00Z), so 4h buckets start at 00:00, 04:00, 08:00, ...
 /// - The output Sample.ts is the timestamp of the last observation in that bucket (not the bucket start).
+/// - The output high/low span the bucket's highs and lows, if any of its samples has them.
+/// - The output volume is the bucket's total, if any of its samples has one.
 fn resample_to_close(samples: &[Sample], step: Duration) -> Vec<Sample> {
+    close_buckets(samples, step).into_values().collect()
+}
+
+/// `resample_to_close` keyed by bucket start.
+fn close_buckets(samples: &[Sample], step: Duration) -> BTreeMap<DateTime<Utc>, Sample> {
     assert!(step > Duration::zero(), "step must be positive");
     let step_secs = step.num_seconds();
     assert!(step_secs > 0, "step is too small (must be >= 1 second)");
@@ -63,21 +236,99 @@ fn resample_to_close(samples: &[Sample], step: Duration) -> Vec<Sample> {
         buckets
             .entry(bucket_start)
             .and_modify(|prev| {
+                prev.merge_range(s);
+                prev.volume = add_volume(prev.volume, s.volume);
                 // Keep the latest observation within the bucket
                 if s.ts > prev.ts {
-                    *prev = Sample {
-                        ts: s.ts,
-                        price: s.price,
-                    };
+                    prev.ts = s.ts;
+                    prev.price = s.price;
                 }
             })
-            .or_insert_with(|| Sample {
-                ts: s.ts,
-                price: s.price,
-            });
+            .or_insert_with(|| s.clone());
     }
 
-    buckets.into_values().collect()
+    buckets
+}
+
+/// A run of consecutive empty buckets between two resampled candles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    /// Start of the first empty bucket.
+    pub start: DateTime<Utc>,
+    /// End of the last empty bucket, i.e. the start of the next candle's bucket.
+    pub end: DateTime<Utc>,
+    pub missing_buckets: usize,
+}
+
+impl Gap {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// `resample_to_n_hours`, also listing the runs of buckets without any sample.
+/// Indicators computed across a gap treat the candles on both sides as adjacent.
+pub fn resample_to_n_hours_checked(samples: &[Sample], hours: i64) -> (Vec<Sample>, Vec<Gap>) {
+    assert!(hours > 0, "hours must be >= 1");
+    let step = Duration::hours(hours);
+    let buckets = close_buckets(samples, step);
+
+    let starts: Vec<DateTime<Utc>> = buckets.keys().copied().collect();
+    let gaps = starts
+        .windows(2)
+        .filter(|w| w[1] - w[0] > step)
+        .map(|w| Gap {
+            start: w[0] + step,
+            end: w[1],
+            missing_buckets: ((w[1] - w[0]).num_seconds() / step.num_seconds()) as usize - 1,
+        })
+        .collect();
+
+    (buckets.into_values().collect(), gaps)
+}
+
+/// `resample_to_n_hours`, with every empty bucket between the first and last candle
+/// filled by a synthetic one at the bucket start, carrying the previous close (and no
+/// volume traded). Nothing is added before the first or after the last observation.
+pub fn resample_to_n_hours_filled(samples: &[Sample], hours: i64) -> Vec<Sample> {
+    assert!(hours > 0, "hours must be >= 1");
+    let step = Duration::hours(hours);
+
+    let mut filled: Vec<Sample> = Vec::new();
+    let mut next_start: Option<DateTime<Utc>> = None;
+    for (start, candle) in close_buckets(samples, step) {
+        if let Some(mut ts) = next_start
+            && let Some(prev) = filled.last().cloned()
+        {
+            while ts < start {
+                filled.push(Sample {
+                    ts,
+                    price: prev.price,
+                    high: None,
+                    low: None,
+                    volume: prev.volume.map(|_| 0.0),
+                });
+                ts += step;
+            }
+        }
+        next_start = Some(start + step);
+        filled.push(candle);
+    }
+    filled
+}
+
+/// Warning line for the CLIs, e.g. "2 gaps totaling 5 hours". None without gaps.
+pub fn describe_gaps(gaps: &[Gap]) -> Option<String> {
+    if gaps.is_empty() {
+        return None;
+    }
+    let hours: i64 = gaps.iter().map(|g| g.duration().num_hours()).sum();
+    Some(format!(
+        "{} gap{} totaling {hours} hour{}",
+        gaps.len(),
+        if gaps.len() == 1 { "" } else { "s" },
+        if hours == 1 { "" } else { "s" },
+    ))
 }
 
 /// Convenience wrapper for 1h / 2h / 4h / ...
@@ -90,6 +341,103 @@ pub fn resample_to_hourly(samples: &[Sample]) -> Vec<Sample> {
     resample_to_n_hours(samples, 1)
 }
 
+fn add_volume(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Tick bars: every `n` consecutive samples become one candle, regardless of time.
+/// The candle is the group's last sample (close and its timestamp) with the group's
+/// high/low and total volume; a trailing group with fewer than `n` samples is kept.
+/// `n` must be at least 1.
+pub fn resample_by_count(samples: &[Sample], n: usize) -> Result<Vec<Sample>, DataError> {
+    if n == 0 {
+        return Err(DataError::InvalidBarSize(
+            "a tick bar needs at least 1 sample".to_string(),
+        ));
+    }
+    Ok(samples
+        .chunks(n)
+        .filter_map(|group| {
+            let mut bar = Sample {
+                volume: group.iter().map(|s| s.volume).fold(None, add_volume),
+                ..group.last()?.clone()
+            };
+            for s in group {
+                bar.merge_range(s);
+            }
+            Some(bar)
+        })
+        .collect())
+}
+
+/// Volume bars: samples accumulate into a candle until its volume reaches
+/// `target_volume`, so active periods give more candles than quiet ones.
+/// The candle is the last accumulated sample (close and its timestamp) with the
+/// accumulated high/low and volume; a sample that overshoots the target isn't split. A trailing
+/// candle below the target is kept. Every sample needs a volume, and the target must
+/// be positive.
+pub fn resample_by_volume(
+    samples: &[Sample],
+    target_volume: f64,
+) -> Result<Vec<Sample>, DataError> {
+    if target_volume.is_nan() || target_volume <= 0.0 {
+        return Err(DataError::InvalidBarSize(format!(
+            "target volume must be positive, got {target_volume}"
+        )));
+    }
+    let mut bars = Vec::new();
+    let mut accumulated = 0.0;
+    let mut range: Option<Sample> = None;
+    for (index, s) in samples.iter().enumerate() {
+        accumulated += s.volume.ok_or(DataError::MissingVolume { index })?;
+        let mut bar = Sample {
+            volume: Some(accumulated),
+            ..s.clone()
+        };
+        if let Some(range) = &range {
+            bar.merge_range(range);
+        }
+        if accumulated >= target_volume || index == samples.len() - 1 {
+            bars.push(bar);
+            accumulated = 0.0;
+            range = None;
+        } else {
+            range = Some(bar);
+        }
+    }
+    Ok(bars)
+}
+
+/// Inner-join two series on their timestamps: the prices of `a` and `b` at every
+/// timestamp both have, plus those timestamps, all in time order and of equal length.
+/// Both inputs must be sorted by time (as the resamplers return them). Timestamps
+/// have to match exactly, so put both series on the same grid first.
+pub fn align(a: &[Sample], b: &[Sample]) -> (Vec<f64>, Vec<f64>, Vec<DateTime<Utc>>) {
+    let mut prices_a = Vec::new();
+    let mut prices_b = Vec::new();
+    let mut timestamps = Vec::new();
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].ts.cmp(&b[j].ts) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                prices_a.push(a[i].price);
+                prices_b.push(b[j].price);
+                timestamps.push(a[i].ts);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    (prices_a, prices_b, timestamps)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,7 +448,31 @@ mod tests {
             .with_ymd_and_hms(y, m, d, h, min, s)
             .single()
             .expect("valid datetime");
-        Sample { ts, price }
+        Sample {
+            ts,
+            price,
+            high: None,
+            low: None,
+            volume: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_dataset_rejects_empty_and_unordered_input() {
+        let s1 = sample(2025, 11, 28, 10, 0, 0, 100.0);
+        let s2 = sample(2025, 11, 28, 11, 0, 0, 101.0);
+
+        assert_eq!(validate_dataset(&[]), Err(DataError::Empty));
+        assert_eq!(validate_dataset(std::slice::from_ref(&s1)), Ok(()));
+        assert_eq!(
+            validate_dataset(&[s2.clone(), s1.clone()]),
+            Err(DataError::OutOfOrder { index: 1 })
+        );
+        assert_eq!(DataError::Empty.to_string(), "no samples");
+        assert_eq!(
+            DataError::TooShort { have: 1, need: 2 }.to_string(),
+            "not enough data: 1 samples, need at least 2"
+        );
     }
 
     #[test]
@@ -112,7 +484,7 @@ mod tests {
     #[test]
     fn test_resample_to_hourly_single_sample_is_preserved() {
         let s = sample(2025, 11, 28, 10, 15, 0, 100.0);
-        let out = resample_to_hourly(&[s.clone()]);
+        let out = resample_to_hourly(std::slice::from_ref(&s));
 
         assert_eq!(out.len(), 1);
         assert_eq!(out[0].ts, s.ts);
@@ -122,7 +494,7 @@ mod tests {
     #[test]
     fn test_resample_to_hourly_multiple_samples_in_same_hour_keep_last_price_and_timestamp() {
         // All in the 10:00–10:59 hour
-        let s1 = sample(2025, 11, 28, 10, 05, 00, 100.0);
+        let s1 = sample(2025, 11, 28, 10, 5, 0, 100.0);
         let s2 = sample(2025, 11, 28, 10, 30, 00, 101.0);
         let s3 = sample(2025, 11, 28, 10, 59, 59, 102.0);
 
@@ -137,7 +509,7 @@ mod tests {
     #[test]
     fn test_resample_to_hourly_multiple_hours_keep_last_sample_per_hour_and_order_by_hour() {
         // Hour 10
-        let h10_early = sample(2025, 11, 28, 10, 05, 00, 100.0);
+        let h10_early = sample(2025, 11, 28, 10, 5, 0, 100.0);
         let h10_last = sample(2025, 11, 28, 10, 55, 00, 101.0);
 
         // Hour 11
@@ -183,7 +555,7 @@ mod tests {
 
     #[test]
     fn test_resample_to_n_hours() {
-        let s1 = sample(2025, 11, 28, 10, 05, 00, 100.0);
+        let s1 = sample(2025, 11, 28, 10, 5, 0, 100.0);
         let s2 = sample(2025, 11, 28, 10, 30, 00, 101.0);
         let s3 = sample(2025, 11, 28, 10, 59, 59, 103.0);
         let s4 = sample(2025, 11, 28, 11, 59, 59, 104.0);
@@ -197,4 +569,365 @@ mod tests {
         assert_eq!(out[0].ts, s4.ts); // original timestamp of last tick in that hour
         assert_eq!(out[0].price, 104.0); // close price
     }
+
+    #[test]
+    fn test_resample_to_n_hours_checked_lists_runs_of_empty_buckets() {
+        let samples = vec![
+            sample(2025, 1, 1, 0, 10, 0, 1.0),
+            sample(2025, 1, 1, 1, 10, 0, 2.0),
+            sample(2025, 1, 1, 5, 10, 0, 3.0),
+            sample(2025, 1, 1, 6, 59, 0, 4.0),
+            sample(2025, 1, 1, 9, 0, 0, 5.0),
+        ];
+        let hour = |h| Utc.with_ymd_and_hms(2025, 1, 1, h, 0, 0).unwrap();
+
+        let (hourly, gaps) = resample_to_n_hours_checked(&samples, 1);
+        assert_eq!(hourly.len(), resample_to_hourly(&samples).len());
+        assert_eq!(
+            gaps,
+            [
+                Gap {
+                    start: hour(2),
+                    end: hour(5),
+                    missing_buckets: 3,
+                },
+                Gap {
+                    start: hour(7),
+                    end: hour(9),
+                    missing_buckets: 2,
+                },
+            ]
+        );
+        assert_eq!(describe_gaps(&gaps).unwrap(), "2 gaps totaling 5 hours");
+
+        // 4h buckets 00:00 and 04:00 are filled, 08:00 too: nothing missing
+        let (four_hourly, gaps) = resample_to_n_hours_checked(&samples, 4);
+        assert_eq!(four_hourly.len(), 3);
+        assert!(gaps.is_empty());
+        assert_eq!(describe_gaps(&gaps), None);
+    }
+
+    #[test]
+    fn test_resample_to_n_hours_filled_carries_the_last_close() {
+        let hour = |h| Utc.with_ymd_and_hms(2025, 1, 1, h, 0, 0).unwrap();
+
+        // One empty bucket (01:00) between two candles
+        let samples = vec![
+            sample(2025, 1, 1, 0, 10, 0, 1.0),
+            sample(2025, 1, 1, 0, 50, 0, 2.0),
+            sample(2025, 1, 1, 2, 30, 0, 3.0),
+        ];
+        let filled = resample_to_n_hours_filled(&samples, 1);
+        assert_eq!(filled.len(), 3);
+        assert_eq!(filled[1].ts, hour(1));
+        assert_eq!(filled[1].price, 2.0);
+        assert_eq!(filled[1].volume, None);
+        assert_eq!(filled[2].price, 3.0);
+        // The default keeps the gap
+        assert_eq!(resample_to_hourly(&samples).len(), 2);
+
+        // Three empty buckets right before the last candle; nothing past it
+        let mut samples = vec![
+            sample(2025, 1, 1, 0, 10, 0, 1.0),
+            sample(2025, 1, 1, 4, 5, 0, 5.0),
+        ];
+        for s in &mut samples {
+            s.volume = Some(10.0);
+        }
+        let filled = resample_to_n_hours_filled(&samples, 1);
+        let prices: Vec<f64> = filled.iter().map(|s| s.price).collect();
+        assert_eq!(prices, [1.0, 1.0, 1.0, 1.0, 5.0]);
+        assert_eq!(filled[3].ts, hour(3));
+        assert_eq!(filled[3].volume, Some(0.0));
+        assert_eq!(filled.last().unwrap().ts, samples[1].ts);
+
+        assert!(resample_to_n_hours_filled(&[], 1).is_empty());
+    }
+
+    #[test]
+    fn test_resample_to_n_hours_aggregates_high_low() {
+        let bar = |h: u32, min: u32, hi: f64, lo: f64, c: f64, v: Option<f64>| Sample {
+            high: Some(hi),
+            low: Some(lo),
+            volume: v,
+            ..sample(2025, 11, 28, h, min, 0, c)
+        };
+        // Out of order within the first 2h bucket on purpose
+        let bars = vec![
+            bar(11, 0, 106.0, 101.0, 104.0, Some(2.0)),
+            bar(10, 0, 102.0, 99.0, 101.0, Some(1.0)),
+            bar(11, 30, 105.0, 98.0, 100.0, None),
+            bar(12, 0, 100.5, 99.5, 100.0, None),
+        ];
+
+        let out = resample_to_n_hours(&bars, 2);
+
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].ts, bars[2].ts);
+        assert_eq!(
+            (out[0].high, out[0].low, out[0].price),
+            (Some(106.0), Some(98.0), 100.0)
+        );
+        assert_eq!(out[0].volume, Some(3.0));
+        assert_eq!((out[1].high, out[1].low), (Some(100.5), Some(99.5)));
+
+        // A close-only sample in the bucket counts at its close
+        let mixed = vec![
+            bar(10, 0, 102.0, 99.0, 101.0, None),
+            sample(2025, 11, 28, 10, 30, 0, 97.0),
+        ];
+        let out = resample_to_n_hours(&mixed, 1);
+        assert_eq!((out[0].high, out[0].low), (Some(102.0), Some(97.0)));
+
+        // Close-only data stays close-only
+        let closes = vec![
+            sample(2025, 11, 28, 10, 0, 0, 101.0),
+            sample(2025, 11, 28, 10, 30, 0, 97.0),
+        ];
+        let out = resample_to_n_hours(&closes, 1);
+        assert_eq!((out[0].high, out[0].low), (None, None));
+        assert_eq!(out[0].price, 97.0);
+
+        // Tick bars span their group too
+        let out = resample_by_count(&bars[1..], 2).unwrap();
+        assert_eq!((out[0].high, out[0].low), (Some(105.0), Some(98.0)));
+    }
+
+    #[test]
+    fn test_get_samples_from_input_file_reads_ohlc_and_close_only() {
+        let dir = std::env::temp_dir();
+        let ohlc_path = dir.join(format!("trade_signal_{}_ohlc.csv", std::process::id()));
+        let close_path = dir.join(format!("trade_signal_{}_close.csv", std::process::id()));
+        std::fs::write(
+            &ohlc_path,
+            "timestamp,open,high,low,close,volume\n\
+             2025-11-28T10:00:00Z,100,102,99,101,5.5\n\
+             2025-11-28T11:00:00Z,101,103,100,102,\n",
+        )
+        .unwrap();
+        std::fs::write(&close_path, "timestamp,price\n2025-11-28T10:00:00Z,100.5\n").unwrap();
+
+        let ohlc = get_samples_from_input_file(&ohlc_path).unwrap();
+        assert_eq!(ohlc.len(), 2);
+        assert_eq!(
+            (ohlc[0].high, ohlc[0].low, ohlc[0].price),
+            (Some(102.0), Some(99.0), 101.0)
+        );
+        assert_eq!(ohlc[0].volume, Some(5.5));
+        assert_eq!(ohlc[1].volume, None);
+
+        let close_only = get_samples_from_input_file(&close_path).unwrap();
+        assert_eq!(close_only.len(), 1);
+        assert_eq!((close_only[0].high, close_only[0].low), (None, None));
+        assert_eq!(close_only[0].high_or_close(), 100.5);
+        assert_eq!(close_only[0].price, 100.5);
+
+        std::fs::remove_file(&ohlc_path).unwrap();
+        std::fs::remove_file(&close_path).unwrap();
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn test_candles_bin_round_trip_is_exact_and_smaller_than_csv() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let candles: Vec<Sample> = (0..1000)
+            .map(|i| Sample {
+                ts: start + Duration::hours(i) + Duration::microseconds(i * 7),
+                price: 70000.0 + (i as f64 * 0.37).sin() * 1234.567891,
+                // Close-only candles mixed in
+                high: (i % 3 != 0).then_some(71500.0 + i as f64 * 0.1),
+                low: (i % 3 != 0).then_some(68500.0 - i as f64 * 0.1),
+                volume: (i % 5 != 0).then_some(12.5 + i as f64),
+            })
+            .collect();
+
+        let dir = std::env::temp_dir();
+        let bin_path = dir.join(format!("trade_signal_{}_candles.bin", std::process::id()));
+        let csv_path = dir.join(format!("trade_signal_{}_candles.csv", std::process::id()));
+
+        write_candles_bin(&candles, &bin_path).unwrap();
+        let loaded = read_candles_bin(&bin_path).unwrap();
+
+        assert_eq!(loaded.len(), candles.len());
+        for (a, b) in loaded.iter().zip(&candles) {
+            assert_eq!(a.ts, b.ts);
+            assert_eq!(a.price.to_bits(), b.price.to_bits());
+            assert_eq!(a.high, b.high);
+            assert_eq!(a.low, b.low);
+            assert_eq!(a.volume, b.volume);
+        }
+
+        let mut wtr = csv::Writer::from_path(&csv_path).unwrap();
+        wtr.write_record(["timestamp", "price"]).unwrap();
+        for c in &candles {
+            wtr.write_record([c.ts.to_rfc3339(), c.price.to_string()])
+                .unwrap();
+        }
+        wtr.flush().unwrap();
+
+        let bin_size = std::fs::metadata(&bin_path).unwrap().len();
+        let csv_size = std::fs::metadata(&csv_path).unwrap().len();
+        assert!(bin_size < csv_size, "bin {bin_size} >= csv {csv_size}");
+
+        std::fs::remove_file(&bin_path).unwrap();
+        std::fs::remove_file(&csv_path).unwrap();
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn test_read_candles_bin_rejects_the_unversioned_format() {
+        let path = std::env::temp_dir().join(format!(
+            "trade_signal_{}_candles_v1.bin",
+            std::process::id()
+        ));
+        let rows: Vec<(i64, f64)> = vec![(1_735_689_600_000_000, 100.0)];
+        std::fs::write(&path, bincode::serialize(&rows).unwrap()).unwrap();
+
+        let err = read_candles_bin(&path).unwrap_err();
+        assert!(err.to_string().starts_with("not a candle file"), "{err}");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_resample_by_count_groups_ticks_and_keeps_last_close() {
+        // 10 irregularly spaced ticks priced 100..=109
+        let minutes = [0, 1, 2, 30, 31, 120, 121, 122, 400, 401];
+        let samples: Vec<Sample> = minutes
+            .iter()
+            .enumerate()
+            .map(|(i, &m)| sample(2025, 11, 28, 10 + m / 60, m % 60, 0, 100.0 + i as f64))
+            .collect();
+
+        let out = resample_by_count(&samples, 3).unwrap();
+
+        // 3 + 3 + 3 + a trailing bar of 1
+        assert_eq!(out.len(), 4);
+        let closes: Vec<f64> = out.iter().map(|s| s.price).collect();
+        assert_eq!(closes, [102.0, 105.0, 108.0, 109.0]);
+        assert_eq!(out[0].ts, samples[2].ts);
+        assert_eq!(out[1].ts, samples[5].ts);
+        assert_eq!(out[3].ts, samples[9].ts);
+        assert!(matches!(
+            resample_by_count(&samples, 0),
+            Err(DataError::InvalidBarSize(_))
+        ));
+    }
+
+    #[test]
+    fn test_resample_by_volume_closes_bars_at_target_volume() {
+        let volumes = [4.0, 3.0, 5.0, 1.0, 1.0, 1.0, 7.0, 12.0, 2.0];
+        let samples: Vec<Sample> = volumes
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| Sample {
+                volume: Some(v),
+                ..sample(2025, 11, 28, 10, i as u32, 0, 100.0 + i as f64)
+            })
+            .collect();
+
+        let out = resample_by_volume(&samples, 10.0).unwrap();
+
+        // 4+3+5 | 1+1+1+7 | 12 (overshoot kept whole) | trailing 2
+        let bar_volumes: Vec<f64> = out.iter().map(|s| s.volume.unwrap()).collect();
+        assert_eq!(bar_volumes, [12.0, 10.0, 12.0, 2.0]);
+        let closes: Vec<f64> = out.iter().map(|s| s.price).collect();
+        assert_eq!(closes, [102.0, 106.0, 107.0, 108.0]);
+        assert_eq!(out[1].ts, samples[6].ts);
+
+        let mut missing = samples.clone();
+        missing[3].volume = None;
+        assert_eq!(
+            resample_by_volume(&missing, 10.0).unwrap_err(),
+            DataError::MissingVolume { index: 3 }
+        );
+    }
+
+    #[test]
+    fn test_resample_by_volume_rejects_non_positive_targets() {
+        let samples = vec![Sample {
+            volume: Some(1.0),
+            ..sample(2025, 11, 28, 10, 0, 0, 100.0)
+        }];
+
+        for target in [0.0, -5.0, f64::NAN] {
+            assert!(matches!(
+                resample_by_volume(&samples, target),
+                Err(DataError::InvalidBarSize(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_align_keeps_only_shared_timestamps() {
+        // a: 10:00-14:00, b: 12:00-16:00 with 13:00 missing
+        let a: Vec<Sample> = (10..=14)
+            .map(|h| sample(2025, 11, 28, h, 0, 0, h as f64))
+            .collect();
+        let b: Vec<Sample> = [12, 14, 15, 16]
+            .iter()
+            .map(|&h| sample(2025, 11, 28, h, 0, 0, 100.0 + h as f64))
+            .collect();
+
+        let (prices_a, prices_b, timestamps) = align(&a, &b);
+
+        assert_eq!(prices_a, [12.0, 14.0]);
+        assert_eq!(prices_b, [112.0, 114.0]);
+        assert_eq!(timestamps, [a[2].ts, a[4].ts]);
+
+        let (prices_a, prices_b, timestamps) = align(&a, &[]);
+        assert!(prices_a.is_empty() && prices_b.is_empty() && timestamps.is_empty());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_get_samples_from_gzipped_csv_matches_plain_csv() {
+        use std::io::Write;
+
+        let csv = "timestamp,price\n\
+                   2025-11-28T10:00:00Z,100.5\n\
+                   2025-11-28T11:00:00Z,101.25\n\
+                   2025-11-28T12:00:00Z,99.75\n";
+        let dir = std::env::temp_dir();
+        let plain_path = dir.join(format!("trade_signal_{}_prices.csv", std::process::id()));
+        let gz_path = dir.join(format!("trade_signal_{}_prices.csv.gz", std::process::id()));
+
+        std::fs::write(&plain_path, csv).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&gz_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(csv.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let plain = get_samples_from_input_file(&plain_path).unwrap();
+        let gzipped = get_samples_from_input_file(&gz_path).unwrap();
+
+        assert_eq!(plain.len(), 3);
+        assert_eq!(gzipped.len(), plain.len());
+        for (g, p) in gzipped.iter().zip(&plain) {
+            assert_eq!(g.ts, p.ts);
+            assert_eq!(g.price, p.price);
+        }
+
+        std::fs::remove_file(&plain_path).unwrap();
+        std::fs::remove_file(&gz_path).unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_get_samples_from_corrupt_gzip_names_the_file() {
+        let path = std::env::temp_dir().join(format!(
+            "trade_signal_{}_corrupt.csv.gz",
+            std::process::id()
+        ));
+        std::fs::write(&path, "timestamp,price\nnot actually gzip\n").unwrap();
+
+        let err = get_samples_from_input_file(&path).unwrap_err();
+        let message = format!("{err:#}");
+        assert!(message.contains("corrupt gzip stream"), "{message}");
+        assert!(message.contains("corrupt.csv.gz"), "{message}");
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
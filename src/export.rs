@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+use crate::backtest::position::PositionBacktestResult;
+use crate::backtest::spot::SpotBacktestResult;
+use crate::data::{Sample, validate_dataset};
+use crate::indicators::atr::{DEFAULT_ATR_PERIOD, atr_percent_series};
+use crate::indicators::sma_series;
+use crate::signal::{Action, StrategyConfig};
+
+fn opt_to_field<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Write one row per candle with SMA(short), SMA(long), ATR% and regime.
+/// Each value is computed over the candles up to (and including) that row;
+/// warmup rows leave the indicator columns blank.
+pub fn write_indicators_csv(
+    candles: &[Sample],
+    strategy: &StrategyConfig,
+    path: &Path,
+) -> Result<()> {
+    validate_dataset(candles)?;
+    let prices: Vec<f64> = candles.iter().map(|c| c.price).collect();
+
+    let sma_short = sma_series(&prices, strategy.sma_config.short_window);
+    let sma_long = sma_series(&prices, strategy.sma_config.long_window);
+    let atr_period = strategy
+        .filters
+        .atr
+        .map(|atr| atr.period())
+        .unwrap_or(DEFAULT_ATR_PERIOD);
+    let atr_pct = atr_percent_series(candles, atr_period);
+    let regime = strategy
+        .filters
+        .regime
+        .unwrap_or_default()
+        .regime_series(&prices);
+
+    let mut wtr = csv::Writer::from_path(path)
+        .with_context(|| format!("failed to create indicators file: {:?}", path))?;
+    wtr.write_record([
+        "timestamp",
+        "price",
+        "sma_short",
+        "sma_long",
+        "atr_pct",
+        "regime",
+    ])?;
+
+    for (i, candle) in candles.iter().enumerate() {
+        wtr.write_record([
+            candle.ts.to_rfc3339(),
+            candle.price.to_string(),
+            opt_to_field(sma_short[i]),
+            opt_to_field(sma_long[i]),
+            opt_to_field(atr_pct[i]),
+            opt_to_field(regime[i].map(|r| format!("{:?}", r))),
+        ])?;
+    }
+    wtr.flush()
+        .with_context(|| format!("failed to write indicators file: {:?}", path))?;
+
+    Ok(())
+}
+
+/// Write one wide row per candle (`timestamp,close,sma_short,sma_long,action,equity`)
+/// of a position backtest over `candles`, for charting in TradingView or pandas.
+/// `action` is what the backtest executed on the candle (HOLD if nothing, the later
+/// one if several; see `PositionBacktestResult::executed_actions`) and `equity` its
+/// equity curve, matched by timestamp. Warmup rows, and candles a thinned curve
+/// skipped, leave those columns blank.
+pub fn write_chart_csv(
+    candles: &[Sample],
+    result: &PositionBacktestResult,
+    path: &Path,
+) -> Result<()> {
+    validate_dataset(candles)?;
+    let prices: Vec<f64> = candles.iter().map(|c| c.price).collect();
+
+    let sma_config = &result.effective_sma_config;
+    let sma_short = sma_series(&prices, sma_config.short_window);
+    let sma_long = sma_series(&prices, sma_config.long_window);
+    let warmup = candles.len().saturating_sub(result.usable_candles);
+    let actions: HashMap<_, _> = result.executed_actions().into_iter().collect();
+    let equity: HashMap<_, _> = result.equity_curve.iter().copied().collect();
+
+    let mut wtr = csv::Writer::from_path(path)
+        .with_context(|| format!("failed to create chart file: {:?}", path))?;
+    wtr.write_record([
+        "timestamp",
+        "close",
+        "sma_short",
+        "sma_long",
+        "action",
+        "equity",
+    ])?;
+
+    for (i, candle) in candles.iter().enumerate() {
+        let past_warmup = i >= warmup;
+        let action = past_warmup.then(|| actions.get(&candle.ts).unwrap_or(&Action::Hold));
+        wtr.write_record([
+            candle.ts.to_rfc3339(),
+            candle.price.to_string(),
+            opt_to_field(sma_short[i]),
+            opt_to_field(sma_long[i]),
+            opt_to_field(action),
+            opt_to_field(action.and(equity.get(&candle.ts))),
+        ])?;
+    }
+    wtr.flush()
+        .with_context(|| format!("failed to write chart file: {:?}", path))?;
+
+    Ok(())
+}
+
+/// Write one row per position of a position backtest, in a fixed column order with
+/// RFC3339 timestamps. Exit columns are blank for a position that's still open.
+/// `rule` is the signal rule that opened the position (e.g. "Crossovers"); the
+/// reasons say why it was opened and closed.
+pub fn write_trades_csv(result: &PositionBacktestResult, path: &Path) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(path)
+        .with_context(|| format!("failed to create trades file: {:?}", path))?;
+    wtr.write_record([
+        "side",
+        "entry_time",
+        "exit_time",
+        "entry_price",
+        "exit_price",
+        "size",
+        "entry_collateral",
+        "profit",
+        "return_pct",
+        "mfe_pct",
+        "mae_pct",
+        "entry_reason",
+        "exit_reason",
+        "rule",
+    ])?;
+
+    for pos in &result.positions {
+        wtr.write_record([
+            format!("{:?}", pos.side),
+            pos.entry_time.to_rfc3339(),
+            opt_to_field(pos.exit_time.map(|ts| ts.to_rfc3339())),
+            pos.entry_price.to_string(),
+            opt_to_field(pos.exit_price),
+            pos.size.to_string(),
+            pos.entry_collateral_gross.to_string(),
+            opt_to_field(pos.profit),
+            opt_to_field(pos.return_pct),
+            pos.mfe_pct.to_string(),
+            pos.mae_pct.to_string(),
+            pos.entry_reason.clone(),
+            opt_to_field(pos.exit_reason.as_ref()),
+            opt_to_field(pos.rule.as_ref()),
+        ])?;
+    }
+    wtr.flush()
+        .with_context(|| format!("failed to write trades file: {:?}", path))?;
+
+    Ok(())
+}
+
+/// Write a spot backtest's equity curve as `timestamp,equity` rows (RFC3339
+/// timestamps, like the input), replacing the file if it exists.
+pub fn write_equity_curve_csv(result: &SpotBacktestResult, path: &Path) -> Result<()> {
+    write_curve_csv(&result.equity_curve, path)
+}
+
+/// `write_equity_curve_csv` for a position backtest.
+pub fn write_position_equity_curve_csv(result: &PositionBacktestResult, path: &Path) -> Result<()> {
+    write_curve_csv(&result.equity_curve, path)
+}
+
+fn write_curve_csv(curve: &[(DateTime<Utc>, f64)], path: &Path) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(path)
+        .with_context(|| format!("failed to create equity curve file: {:?}", path))?;
+    wtr.write_record(["timestamp", "equity"])?;
+
+    for (ts, equity) in curve {
+        wtr.write_record([ts.to_rfc3339(), equity.to_string()])?;
+    }
+    wtr.flush()
+        .with_context(|| format!("failed to write equity curve file: {:?}", path))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::backtest::Candidate;
+    use crate::backtest::position::{Position, PositionBacktester};
+    use crate::backtest::spot::SpotBacktester;
+    use crate::indicators::sma::SmaConfig;
+    use crate::indicators::{AtrFilter, RegimeFilter};
+    use crate::signal::FilterConfig;
+
+    fn samples(n: usize) -> Vec<Sample> {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        (0..n)
+            .map(|i| Sample {
+                ts: start + Duration::hours(i as i64),
+                price: 100.0 + i as f64,
+                high: None,
+                low: None,
+                volume: None,
+            })
+            .collect()
+    }
+
+    fn strategy() -> StrategyConfig {
+        StrategyConfig {
+            breakouts: None,
+            pullbacks: None,
+            enable_crossovers: true,
+            enable_bias_only: false,
+            enable_macd: None,
+            rule_order: Vec::new(),
+            bollinger: None,
+            sma_config: SmaConfig::new(3, 5),
+            filters: FilterConfig {
+                atr: Some(AtrFilter::new_fixed(2, 0.0)),
+                regime: Some(RegimeFilter {
+                    long_window: 4,
+                    slope_window: 2,
+                    min_trend_strength: 0.0,
+                    min_range: 0.0,
+                    smooth_slope: false,
+                }),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("trade_signal_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_write_indicators_csv_header_and_warmup_rows() {
+        let path = temp_path("indicators.csv");
+        write_indicators_csv(&samples(8), &strategy(), &path).unwrap();
+
+        let mut rdr = csv::Reader::from_path(&path).unwrap();
+        let header = rdr.headers().unwrap().clone();
+        assert_eq!(
+            header.iter().collect::<Vec<_>>(),
+            [
+                "timestamp",
+                "price",
+                "sma_short",
+                "sma_long",
+                "atr_pct",
+                "regime"
+            ]
+        );
+
+        let rows: Vec<csv::StringRecord> = rdr.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), 8);
+
+        // First row: every indicator is still warming up
+        assert_eq!(&rows[0][1], "100");
+        assert!(rows[0].iter().skip(2).all(|field| field.is_empty()));
+
+        // SMA(3) appears at row 2, ATR(2) at row 2, regime at row 4, SMA(5) at row 4
+        assert_eq!(&rows[1][2], "");
+        assert_eq!(&rows[2][2], "101");
+        assert!(!rows[2][4].is_empty());
+        assert_eq!(&rows[3][3], "");
+        assert_eq!(&rows[3][5], "");
+        assert_eq!(&rows[4][3], "102");
+        assert_eq!(&rows[4][5], "TrendingUp");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_equity_curve_csv_truncates_and_round_trips() {
+        let candidate = Candidate {
+            buy_sell_fraction: 0.5,
+            strategy: strategy(),
+            stop: None,
+        };
+        let result = SpotBacktester::new(1_000.0, 0.0, 10.0)
+            .run_samples(&samples(8), &candidate)
+            .unwrap();
+
+        let path = temp_path("equity.csv");
+        std::fs::write(
+            &path,
+            "stale contents that are longer than one line\n".repeat(50),
+        )
+        .unwrap();
+        write_equity_curve_csv(&result, &path).unwrap();
+
+        let mut rdr = csv::Reader::from_path(&path).unwrap();
+        assert_eq!(
+            rdr.headers().unwrap().iter().collect::<Vec<_>>(),
+            ["timestamp", "equity"]
+        );
+        let rows: Vec<csv::StringRecord> = rdr.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), result.equity_curve.len());
+        for (row, (ts, equity)) in rows.iter().zip(&result.equity_curve) {
+            let parsed = DateTime::parse_from_rfc3339(&row[0]).unwrap();
+            assert_eq!(parsed.with_timezone(&Utc), *ts);
+            assert_eq!(row[1].parse::<f64>().unwrap(), *equity);
+        }
+
+        assert!(write_equity_curve_csv(&result, &temp_path("missing/dir/equity.csv")).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_trades_csv_round_trip() {
+        // SMA(2) vs SMA(3) bias: long at 3, short at 6, long at 7 (closed at EOF)
+        let mut candles = samples(9);
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5, 16.0, 17.0];
+        for (candle, price) in candles.iter_mut().zip(prices) {
+            candle.price = price;
+        }
+        let mut strategy = strategy();
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = true;
+        strategy.sma_config = SmaConfig::new(2, 3);
+        strategy.filters.atr = None;
+        strategy.filters.regime = None;
+        let candidate = Candidate {
+            buy_sell_fraction: 0.5,
+            strategy,
+            stop: None,
+        };
+        let mut result = PositionBacktester::new(1_000.0)
+            .run_samples(&candles, &candidate)
+            .unwrap();
+        assert_eq!(result.positions.len(), 3);
+        // Pretend the last one is still open
+        let open = Position {
+            exit_time: None,
+            exit_price: None,
+            exit_reason: None,
+            profit: None,
+            return_pct: None,
+            ..result.positions[2].clone()
+        };
+        result.positions[2] = open;
+
+        let path = temp_path("trades.csv");
+        write_trades_csv(&result, &path).unwrap();
+
+        let mut rdr = csv::Reader::from_path(&path).unwrap();
+        assert_eq!(&rdr.headers().unwrap()[0], "side");
+        let rows: Vec<csv::StringRecord> = rdr.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), 3);
+
+        let first = &result.positions[0];
+        assert_eq!(&rows[0][0], "Long");
+        assert_eq!(&rows[0][1], candles[3].ts.to_rfc3339());
+        assert_eq!(&rows[0][2], candles[6].ts.to_rfc3339());
+        assert_eq!(rows[0][3].parse::<f64>().unwrap(), 11.0);
+        assert_eq!(rows[0][7].parse::<f64>().unwrap(), first.profit.unwrap());
+        assert_eq!(&rows[0][13], "Bias only");
+        assert_eq!(&rows[1][0], "Short");
+
+        // Open position: exit columns blank, entry columns filled
+        assert_eq!(&rows[2][1], candles[7].ts.to_rfc3339());
+        for column in [2, 4, 7, 8, 12] {
+            assert_eq!(&rows[2][column], "", "column {column}");
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_chart_csv_columns_and_one_row_per_candle() {
+        // A dip then a rally, so the SMA(3)/SMA(5) cross opens a long
+        let prices = [
+            110.0, 108.0, 106.0, 104.0, 102.0, 100.0, 103.0, 107.0, 112.0, 118.0, 125.0, 133.0,
+        ];
+        let mut candles = samples(prices.len());
+        for (candle, price) in candles.iter_mut().zip(prices) {
+            candle.price = price;
+        }
+        let strategy = strategy();
+        let candidate = Candidate {
+            buy_sell_fraction: 0.5,
+            strategy: strategy.clone(),
+            stop: None,
+        };
+        let result = PositionBacktester::new(1_000.0)
+            .run_samples(&candles, &candidate)
+            .unwrap();
+
+        let path = temp_path("chart.csv");
+        write_chart_csv(&candles, &result, &path).unwrap();
+
+        let mut rdr = csv::Reader::from_path(&path).unwrap();
+        assert_eq!(
+            rdr.headers().unwrap().iter().collect::<Vec<_>>(),
+            [
+                "timestamp",
+                "close",
+                "sma_short",
+                "sma_long",
+                "action",
+                "equity"
+            ]
+        );
+        let rows: Vec<csv::StringRecord> = rdr.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), candles.len());
+
+        // SMA(5) warmup: no action or equity yet
+        assert!(rows[0].iter().skip(2).all(|field| field.is_empty()));
+        assert_eq!(&rows[4][4], "");
+        let entry = &result.positions[0];
+        let entry_row = candles
+            .iter()
+            .position(|c| c.ts == entry.entry_time)
+            .unwrap();
+        assert_eq!(&rows[entry_row][4], "BUY");
+        assert_eq!(&rows[entry_row + 1][4], "HOLD");
+        let last = rows.last().unwrap();
+        assert!(!last[4].is_empty());
+        assert_eq!(
+            last[5].parse::<f64>().unwrap(),
+            result.equity_curve.last().unwrap().1
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
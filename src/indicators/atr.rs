@@ -1,3 +1,6 @@
+use crate::data::Sample;
+use crate::stats::{PercentileMethod, percentile};
+
 #[derive(Debug, Clone, Copy)]
 pub struct AtrFilter {
     period: usize,
@@ -10,38 +13,42 @@ impl AtrFilter {
         Self { period, floor }
     }
 
+    /// Floor at a percentile of the true-range ATR% over `samples`' history.
     /// Example: percentile = 0.4 => 40th percentile.
-    pub fn from_history(prices: &[f64], period: usize, percentile: f64) -> Option<Self> {
-        if prices.len() < period + 2 {
+    pub fn from_history(samples: &[Sample], period: usize, percentile: f64) -> Option<Self> {
+        Self::from_history_with_method(samples, period, percentile, PercentileMethod::default())
+    }
+
+    /// Same as `from_history`, but with an explicit percentile method.
+    pub fn from_history_with_method(
+        samples: &[Sample],
+        period: usize,
+        p: f64,
+        method: PercentileMethod,
+    ) -> Option<Self> {
+        if samples.len() < period + 2 {
             return None;
         }
 
         let mut atr_percents = Vec::new();
 
-        for end in (period + 1)..=prices.len() {
-            if let Some(atr_p) = atr_percent(&prices[..end], period) {
+        for end in (period + 1)..=samples.len() {
+            if let Some(atr_p) = true_range_atr_percent(&samples[..end], period) {
                 atr_percents.push(atr_p);
             }
         }
 
-        if atr_percents.is_empty() {
-            return None;
-        }
-
         atr_percents.sort_by(|a, b| a.partial_cmp(b).unwrap());
 
-        let p = percentile.clamp(0.0, 1.0);
-        let idx = ((atr_percents.len() - 1) as f64 * p).round() as usize;
-
         Some(Self {
             period,
-            floor: atr_percents[idx],
+            floor: percentile(&atr_percents, p, method)?,
         })
     }
 
-    /// ATR% at the end of `prices` using this filter's period.
-    pub fn atr_percent(&self, prices: &[f64]) -> Option<f64> {
-        atr_percent(prices, self.period)
+    /// True-range ATR% at the end of `samples` using this filter's period.
+    pub fn atr_percent(&self, samples: &[Sample]) -> Option<f64> {
+        true_range_atr_percent(samples, self.period)
     }
 
     pub fn period(&self) -> usize {
@@ -53,6 +60,9 @@ impl AtrFilter {
     }
 }
 
+/// ATR period used when nothing more specific is configured.
+pub const DEFAULT_ATR_PERIOD: usize = 14;
+
 /// Simple ATR approximation using only close prices:
 /// TR_i = |close_i - close_{i-1}|
 /// ATR  = mean(TR_last_period)
@@ -85,9 +95,67 @@ pub fn atr_percent(prices: &[f64], period: usize) -> Option<f64> {
     }
     Some(atr_val / last_price)
 }
+/// True-range ATR:
+/// TR_i = max(high_i - low_i, |high_i - close_{i-1}|, |low_i - close_{i-1}|)
+/// ATR  = mean(TR_last_period)
+/// Samples without high/low count as flat (high = low = close), so on close-only
+/// data this is exactly `atr` over the closes.
+pub fn true_range_atr(samples: &[Sample], period: usize) -> Option<f64> {
+    if samples.len() < period + 1 || period == 0 {
+        return None;
+    }
+
+    let sum_tr: f64 = samples[samples.len() - period - 1..]
+        .windows(2)
+        .map(|w| {
+            let prev_close = w[0].price;
+            let (high, low) = (w[1].high_or_close(), w[1].low_or_close());
+            (high - low)
+                .max((high - prev_close).abs())
+                .max((low - prev_close).abs())
+        })
+        .sum();
+
+    Some(sum_tr / period as f64)
+}
+
+/// True-range ATR as a fraction of the last close.
+pub fn true_range_atr_percent(samples: &[Sample], period: usize) -> Option<f64> {
+    let atr_val = true_range_atr(samples, period)?;
+    let last_close = samples.last()?.price;
+    if last_close <= 0.0 {
+        return None;
+    }
+    Some(atr_val / last_close)
+}
+
+/// True-range ATR% over every growing prefix of `samples` (one entry per sample).
+/// Entries are None until `period + 1` samples are available.
+pub fn atr_percent_series(samples: &[Sample], period: usize) -> Vec<Option<f64>> {
+    (1..=samples.len())
+        .map(|end| true_range_atr_percent(&samples[..end], period))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn hourly_samples(prices: &[f64]) -> Vec<Sample> {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, &price)| Sample {
+                ts: start + Duration::hours(i as i64),
+                price,
+                high: None,
+                low: None,
+                volume: None,
+            })
+            .collect()
+    }
     #[test]
     fn test_atr_returns_none_when_not_enough_data() {
         let prices = vec![100.0, 101.0, 102.0];
@@ -122,9 +190,28 @@ mod tests {
         assert!((result - 4.0).abs() < 1e-12);
     }
 
+    #[test]
+    fn test_true_range_atr_uses_high_low_and_matches_close_only_on_flat_candles() {
+        let mut candles = hourly_samples(&[10.0, 11.0, 13.0, 16.0]);
+        assert_eq!(true_range_atr(&candles, 3), Some(2.0));
+        assert_eq!(true_range_atr(&candles, 4), None);
+
+        // Widen the last candle: TR = max(18 - 12, |18 - 13|, |12 - 13|) = 6
+        candles[3].high = Some(18.0);
+        candles[3].low = Some(12.0);
+        // TRs: 1, 2, 6 -> ATR = 3
+        assert!((true_range_atr(&candles, 3).unwrap() - 3.0).abs() < 1e-12);
+        assert!((true_range_atr_percent(&candles, 3).unwrap() - 3.0 / 16.0).abs() < 1e-12);
+
+        // A gap away from the previous close counts even with a narrow range
+        candles[3].high = Some(20.0);
+        candles[3].low = Some(19.5);
+        assert!((true_range_atr(&candles, 1).unwrap() - 7.0).abs() < 1e-12);
+    }
+
     #[test]
     fn test_atr_filter_from_history_returns_none_when_not_enough_data() {
-        let prices = vec![100.0, 101.0, 102.0, 103.0];
+        let prices = hourly_samples(&[100.0, 101.0, 102.0, 103.0]);
         let period = 3;
 
         // Need prices.len() >= period + 2 (5 here). We only have 4.
@@ -155,7 +242,7 @@ mod tests {
         // atr_percents (unsorted) ≈ [0.11538, 0.15625, 0.13333]
         // sorted ≈ [0.11538, 0.13333, 0.15625]
 
-        let prices = vec![10.0, 11.0, 13.0, 16.0, 15.0];
+        let prices = hourly_samples(&[10.0, 11.0, 13.0, 16.0, 15.0]);
         let period = 2;
 
         // Percentile 0.0 -> clamped to 0 -> idx = 0 -> lowest value
@@ -175,9 +262,28 @@ mod tests {
         assert!((fmid.floor - 0.1333333).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_atr_filter_from_history_with_linear_method_interpolates() {
+        // Same series as above: sorted ATR% ≈ [0.11538, 0.13333, 0.15625]
+        // Percentile 0.25 -> rank 0.5
+        // NearestRank: round(0.5) = 1 -> 0.13333
+        // Linear:      0.11538 + (0.13333 - 0.11538) * 0.5 ≈ 0.12436
+        let prices = hourly_samples(&[10.0, 11.0, 13.0, 16.0, 15.0]);
+        let period = 2;
+
+        let nearest = AtrFilter::from_history(&prices, period, 0.25).unwrap();
+        assert!((nearest.floor - 0.1333333).abs() < 1e-6);
+
+        let linear =
+            AtrFilter::from_history_with_method(&prices, period, 0.25, PercentileMethod::Linear)
+                .unwrap();
+        let expected = (1.5 / 13.0 + 2.0 / 15.0) / 2.0;
+        assert!((linear.floor - expected).abs() < 1e-9);
+    }
+
     #[test]
     fn test_atr_filter_from_history_clamps_percentile_below_zero_to_zero() {
-        let prices = vec![10.0, 11.0, 13.0, 16.0, 15.0];
+        let prices = hourly_samples(&[10.0, 11.0, 13.0, 16.0, 15.0]);
         let period = 2;
 
         // percentile = -1.0 -> clamped to 0.0
@@ -188,7 +294,7 @@ mod tests {
 
     #[test]
     fn test_atr_filter_from_history_clamps_percentile_above_one_to_one() {
-        let prices = vec![10.0, 11.0, 13.0, 16.0, 15.0];
+        let prices = hourly_samples(&[10.0, 11.0, 13.0, 16.0, 15.0]);
         let period = 2;
 
         // percentile = 2.0 -> clamped to 1.0
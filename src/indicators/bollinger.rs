@@ -0,0 +1,57 @@
+use crate::indicators::simple_moving_average;
+use crate::stats::stddev;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BollingerBands {
+    pub middle: f64,
+    pub upper: f64,
+    pub lower: f64,
+}
+
+/// Bollinger Bands over the last `period` prices: SMA(period) as the middle band,
+/// `num_std` population standard deviations above and below it. Flat prices (within
+/// `stats::stddev`'s epsilon) give bands collapsed onto the mean.
+/// Returns None with fewer than `period` prices or a `period` of 0.
+pub fn bollinger(prices: &[f64], period: usize, num_std: f64) -> Option<BollingerBands> {
+    if period == 0 {
+        return None;
+    }
+    let middle = simple_moving_average(prices, period)?;
+    let window = &prices[prices.len() - period..];
+    let width = num_std * stddev(window, 0).unwrap_or(0.0);
+    Some(BollingerBands {
+        middle,
+        upper: middle + width,
+        lower: middle - width,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bollinger_flat_prices_collapse_to_the_mean() {
+        let bands = bollinger(&[7.0, 10.0, 10.0, 10.0], 3, 2.0).unwrap();
+        assert_eq!(
+            bands,
+            BollingerBands {
+                middle: 10.0,
+                upper: 10.0,
+                lower: 10.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_bollinger_population_stddev_and_edge_cases() {
+        // Mean 5, population variance (9 + 1 + 1 + 9) / 4 = 5
+        let bands = bollinger(&[100.0, 2.0, 4.0, 6.0, 8.0], 4, 2.0).unwrap();
+        assert_eq!(bands.middle, 5.0);
+        assert!((bands.upper - (5.0 + 2.0 * 5f64.sqrt())).abs() < 1e-12);
+        assert!((bands.lower - (5.0 - 2.0 * 5f64.sqrt())).abs() < 1e-12);
+
+        assert!(bollinger(&[1.0, 2.0], 3, 2.0).is_none());
+        assert!(bollinger(&[1.0, 2.0], 0, 2.0).is_none());
+    }
+}
@@ -0,0 +1,62 @@
+/// Exponential moving average over every growing prefix of `prices`.
+/// Seeded with the SMA of the first `period` prices, then
+/// EMA_i = alpha * price_i + (1 - alpha) * EMA_{i-1}, alpha = 2 / (period + 1).
+/// Entries are None until `period` prices are available.
+pub fn ema_series(prices: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut out = vec![None; prices.len()];
+    if period == 0 || prices.len() < period {
+        return out;
+    }
+
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut ema = prices[..period].iter().sum::<f64>() / period as f64;
+    out[period - 1] = Some(ema);
+
+    for (i, &price) in prices.iter().enumerate().skip(period) {
+        ema = alpha * price + (1.0 - alpha) * ema;
+        out[i] = Some(ema);
+    }
+    out
+}
+
+/// EMA at the end of `prices`. Returns None if there isn't enough data.
+pub fn ema(prices: &[f64], period: usize) -> Option<f64> {
+    ema_series(prices, period).last().copied().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f64, b: f64, eps: f64) {
+        assert!(
+            (a - b).abs() <= eps,
+            "expected {b}, got {a} (diff = {})",
+            (a - b).abs()
+        );
+    }
+
+    #[test]
+    fn test_ema_returns_none_when_not_enough_data() {
+        assert_eq!(ema(&[1.0, 2.0], 3), None);
+        assert_eq!(ema(&[1.0, 2.0], 0), None);
+    }
+
+    #[test]
+    fn test_ema_is_seeded_with_sma_then_smoothed() {
+        // period = 3 -> alpha = 0.5
+        // seed = (1 + 2 + 3) / 3 = 2
+        // next = 0.5 * 10 + 0.5 * 2 = 6
+        let series = ema_series(&[1.0, 2.0, 3.0, 10.0], 3);
+        assert_eq!(series[0], None);
+        assert_eq!(series[1], None);
+        approx_eq(series[2].unwrap(), 2.0, 1e-12);
+        approx_eq(series[3].unwrap(), 6.0, 1e-12);
+        approx_eq(ema(&[1.0, 2.0, 3.0, 10.0], 3).unwrap(), 6.0, 1e-12);
+    }
+
+    #[test]
+    fn test_ema_of_flat_prices_is_the_price() {
+        approx_eq(ema(&[5.0; 20], 4).unwrap(), 5.0, 1e-12);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::indicators::ema_series;
+
+/// MACD values at one candle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MacdOutput {
+    /// EMA(fast) - EMA(slow).
+    pub macd: f64,
+    /// EMA of the MACD line over the signal period.
+    pub signal: f64,
+    /// `macd - signal`.
+    pub histogram: f64,
+}
+
+/// MACD over every growing prefix of `prices` (one entry per price).
+/// The signal line is an EMA over the MACD line starting where the slow EMA does,
+/// so entries are None for the first `slow + signal - 2` prices, and everywhere if
+/// a period is 0 or `fast >= slow`.
+pub fn macd_series(
+    prices: &[f64],
+    fast: usize,
+    slow: usize,
+    signal: usize,
+) -> Vec<Option<MacdOutput>> {
+    let mut out = vec![None; prices.len()];
+    if fast == 0 || signal == 0 || fast >= slow || prices.len() < slow {
+        return out;
+    }
+
+    let fast_ema = ema_series(prices, fast);
+    let slow_ema = ema_series(prices, slow);
+    // The MACD line exists from the slow EMA's first value on
+    let line: Vec<f64> = fast_ema
+        .iter()
+        .zip(&slow_ema)
+        .skip(slow - 1)
+        .map(|(f, s)| f.unwrap_or_default() - s.unwrap_or_default())
+        .collect();
+    let signal_line = ema_series(&line, signal);
+
+    for (i, (&macd, signal)) in line.iter().zip(signal_line).enumerate() {
+        out[slow - 1 + i] = signal.map(|signal| MacdOutput {
+            macd,
+            signal,
+            histogram: macd - signal,
+        });
+    }
+    out
+}
+
+/// MACD at the end of `prices`. Returns None if there isn't enough data.
+pub fn macd(prices: &[f64], fast: usize, slow: usize, signal: usize) -> Option<MacdOutput> {
+    macd_series(prices, fast, slow, signal)
+        .last()
+        .copied()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::ema;
+
+    #[test]
+    fn test_macd_matches_ema_difference_and_signal_ema() {
+        let prices: Vec<f64> = (0..20)
+            .map(|i| 100.0 + (i as f64 * 0.7).sin() * 5.0)
+            .collect();
+        let out = macd(&prices, 3, 6, 4).unwrap();
+
+        let line: Vec<f64> = (6..=prices.len())
+            .map(|end| ema(&prices[..end], 3).unwrap() - ema(&prices[..end], 6).unwrap())
+            .collect();
+        let expected_signal = ema(&line, 4).unwrap();
+
+        assert!((out.macd - line.last().unwrap()).abs() < 1e-9);
+        assert!((out.signal - expected_signal).abs() < 1e-9);
+        assert!((out.histogram - (out.macd - out.signal)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_macd_series_warmup_and_invalid_periods() {
+        let prices: Vec<f64> = (0..10).map(|i| i as f64).collect();
+        let series = macd_series(&prices, 2, 4, 3);
+        // First value once the slow EMA (4) and 3 MACD values exist
+        assert!(series[..5].iter().all(Option::is_none));
+        assert!(series[5..].iter().all(Option::is_some));
+
+        assert_eq!(macd(&prices, 4, 4, 3), None);
+        assert_eq!(macd(&prices, 2, 4, 0), None);
+        assert_eq!(macd(&prices[..5], 2, 4, 3), None);
+    }
+}
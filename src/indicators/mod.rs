@@ -1,7 +1,24 @@
 pub mod atr;
+pub mod bollinger;
+pub mod ema;
+pub mod macd;
 pub mod regime;
+pub mod rsi;
 pub mod sma;
+pub mod spread;
+pub mod volatility;
+pub mod vwap;
 
-pub use atr::AtrFilter;
+pub use atr::{AtrFilter, true_range_atr, true_range_atr_percent};
+pub use bollinger::{BollingerBands, bollinger};
+pub use ema::{ema, ema_series};
+pub use macd::{MacdOutput, macd, macd_series};
 pub use regime::{Regime, RegimeFilter};
-pub use sma::{Smas, compute_smas, simple_moving_average};
+pub use rsi::{RsiFilter, rsi};
+pub use sma::{
+    DEFAULT_SMA_EQ_EPSILON, MaStack, RollingSma, Smas, cmp_with_epsilon, compute_many_smas,
+    compute_smas, ma_stack, simple_moving_average, sma_series,
+};
+pub use spread::spread_zscore;
+pub use volatility::{annualize_volatility, realized_volatility};
+pub use vwap::anchored_vwap;
@@ -1,4 +1,7 @@
-use crate::indicators::simple_moving_average;
+use crate::indicators::{ema_series, simple_moving_average};
+
+/// EMA period used to smooth the slope endpoints when `smooth_slope` is set.
+const SLOPE_EMA_PERIOD: usize = 3;
 
 /// Market regime in the *bigger picture*.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,6 +28,10 @@ pub struct RegimeFilter {
     /// Minimum total range over slope window to avoid "dead" chop.
     /// Example: 0.03 = 3% high/low range over the slope window.
     pub min_range: f64,
+
+    /// Measure the slope on a short EMA instead of raw endpoint prices,
+    /// so a single noisy candle doesn't flip the classification.
+    pub smooth_slope: bool,
 }
 
 impl Default for RegimeFilter {
@@ -34,17 +41,41 @@ impl Default for RegimeFilter {
             slope_window: 48,         // last 2 days (on 1h)
             min_trend_strength: 0.02, // 2% over slope window
             min_range: 0.03,          // 3% high/low range
+            smooth_slope: false,
         }
     }
 }
 
 impl RegimeFilter {
+    /// Number of candles needed before `detect_regime` looks at the data.
+    pub fn required_history(&self) -> usize {
+        self.long_window.max(self.slope_window) + 1
+    }
+
+    /// Regime over every growing prefix of `prices` (one entry per price).
+    /// Entries are None while there isn't enough history (instead of the
+    /// `Sideways` fallback of `detect_regime`).
+    pub fn regime_series(&self, prices: &[f64]) -> Vec<Option<Regime>> {
+        // An EMA value only depends on the prices up to it, so one pass serves every prefix
+        let slope_ema = self
+            .smooth_slope
+            .then(|| ema_series(prices, SLOPE_EMA_PERIOD));
+        (1..=prices.len())
+            .map(|end| {
+                (end >= self.required_history()).then(|| {
+                    self.classify(&prices[..end], slope_ema.as_ref().map(|ema| &ema[..end]))
+                })
+            })
+            .collect()
+    }
+
     /// Detect macro regime (1h candles expected).
     ///
     /// Logic:
     /// 1. Need enough data for long_window & slope_window.
     /// 2. Compute long SMA.
     /// 3. Compute trend over slope_window: price_change%
+    ///    (on EMA(3) values instead of raw prices if `smooth_slope`)
     /// 4. Compute price range over slope_window.
     /// 5. If trend is small AND range is small => Sideways
     /// 6. Else:
@@ -52,9 +83,16 @@ impl RegimeFilter {
     ///    - if price < long SMA and trend down -> TrendingDown
     ///    - otherwise Sideways
     pub fn detect_regime(&self, prices: &[f64]) -> Regime {
+        let slope_ema = self
+            .smooth_slope
+            .then(|| ema_series(prices, SLOPE_EMA_PERIOD));
+        self.classify(prices, slope_ema.as_deref())
+    }
+
+    /// `detect_regime`, given the slope EMA over `prices` when `smooth_slope` is set.
+    fn classify(&self, prices: &[f64], slope_ema: Option<&[Option<f64>]>) -> Regime {
         let n = prices.len();
-        let required = self.long_window.max(self.slope_window) + 1;
-        if n < required {
+        if n < self.required_history() {
             // Not enough history -> treat as Sideways to avoid overconfidence.
             return Regime::Sideways;
         }
@@ -69,12 +107,18 @@ impl RegimeFilter {
         let start_price = prices[start];
         let end_price = prices[end];
 
-        if start_price <= 0.0 {
+        let (slope_start, slope_end) = match slope_ema.map(|ema| (ema[start], ema[end])) {
+            Some((Some(s), Some(e))) => (s, e),
+            // Unsmoothed, or the EMA is still warming up at the window start
+            _ => (start_price, end_price),
+        };
+
+        if slope_start <= 0.0 {
             return Regime::Sideways;
         }
 
         // % move over slope_window
-        let trend = (end_price / start_price) - 1.0;
+        let trend = (slope_end / slope_start) - 1.0;
 
         // High/low range over slope_window
         let window = &prices[start..=end];
@@ -117,6 +161,7 @@ mod tests {
                 slope_window: 5,
                 min_trend_strength: 0.02, // 2%
                 min_range: 0.03,          // 3%
+                smooth_slope: false,
             }
         }
     }
@@ -274,4 +319,42 @@ mod tests {
 
         assert_eq!(regime, Regime::Sideways);
     }
+
+    #[test]
+    fn test_detect_regime_smoothed_slope_ignores_noisy_last_candle() {
+        let mut rf = RegimeFilter::test_default_regime();
+        rf.long_window = 10;
+        rf.slope_window = 5;
+        rf.min_trend_strength = 0.02; // 2%
+        rf.min_range = 0.01; // 1%
+
+        // Steady uptrend 100..=119, then a single noisy dip to 116.
+        // Raw slope: 116 / 115 - 1 ≈ 0.87% < 2% -> Sideways
+        // EMA(3) slope: ≈ 117 / 114 - 1 ≈ 2.6% > 2% -> TrendingUp
+        let mut prices: Vec<f64> = (100..=119).map(|p| p as f64).collect();
+        prices.push(116.0);
+
+        assert_eq!(rf.detect_regime(&prices), Regime::Sideways);
+
+        rf.smooth_slope = true;
+        assert_eq!(rf.detect_regime(&prices), Regime::TrendingUp);
+    }
+
+    #[test]
+    fn test_regime_series_matches_detect_regime_on_every_prefix() {
+        let mut rf = RegimeFilter::test_default_regime();
+        rf.smooth_slope = true;
+        rf.min_range = 0.01;
+        let prices: Vec<f64> = (0..40)
+            .map(|i| 100.0 + i as f64 + if i % 4 == 0 { -3.0 } else { 0.0 })
+            .collect();
+
+        let series = rf.regime_series(&prices);
+
+        for (end, regime) in (1..=prices.len()).zip(&series) {
+            let expected = (end >= rf.required_history()).then(|| rf.detect_regime(&prices[..end]));
+            assert_eq!(*regime, expected, "prefix of {end}");
+        }
+        assert!(series.contains(&Some(Regime::TrendingUp)));
+    }
 }
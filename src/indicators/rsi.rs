@@ -0,0 +1,69 @@
+/// Relative Strength Index over `period` price changes, with Wilder's smoothing:
+/// the first average gain/loss is a plain mean, every later one is
+/// `(prev * (period - 1) + current) / period`. The result is in 0..=100.
+/// Returns None with fewer than `period + 1` prices or a `period` of 0.
+/// A series without losses gives 100, a flat one 50.
+pub fn rsi(prices: &[f64], period: usize) -> Option<f64> {
+    if period == 0 || prices.len() < period + 1 {
+        return None;
+    }
+
+    let changes: Vec<f64> = prices.windows(2).map(|w| w[1] - w[0]).collect();
+    let n = period as f64;
+    let mut avg_gain = changes[..period].iter().map(|c| c.max(0.0)).sum::<f64>() / n;
+    let mut avg_loss = changes[..period].iter().map(|c| (-c).max(0.0)).sum::<f64>() / n;
+    for &change in &changes[period..] {
+        avg_gain = (avg_gain * (n - 1.0) + change.max(0.0)) / n;
+        avg_loss = (avg_loss * (n - 1.0) + (-change).max(0.0)) / n;
+    }
+
+    if avg_loss == 0.0 {
+        return Some(if avg_gain == 0.0 { 50.0 } else { 100.0 });
+    }
+    Some(100.0 - 100.0 / (1.0 + avg_gain / avg_loss))
+}
+
+/// Momentum gate: no longs while RSI is above `overbought`, no shorts while it's
+/// below `oversold` (e.g. 70 / 30).
+#[derive(Debug, Clone, Copy)]
+pub struct RsiFilter {
+    pub period: usize,
+    pub overbought: f64,
+    pub oversold: f64,
+}
+
+impl RsiFilter {
+    /// RSI at the end of `prices` using this filter's period.
+    pub fn rsi(&self, prices: &[f64]) -> Option<f64> {
+        rsi(prices, self.period)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rsi_wilder_smoothing_on_known_series() {
+        // Changes: +2, -1, +1, then -2 smoothed in
+        let prices = [10.0, 12.0, 11.0, 12.0, 10.0];
+        let (gain, loss) = (3.0 / 3.0, 1.0 / 3.0);
+        let (gain, loss) = ((gain * 2.0) / 3.0, (loss * 2.0 + 2.0) / 3.0);
+        let expected = 100.0 - 100.0 / (1.0 + gain / loss);
+
+        assert!((rsi(&prices, 3).unwrap() - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_rsi_edge_cases() {
+        let rising: Vec<f64> = (0..10).map(|i| i as f64).collect();
+        let falling: Vec<f64> = rising.iter().rev().copied().collect();
+        assert_eq!(rsi(&rising, 5), Some(100.0));
+        assert_eq!(rsi(&falling, 5), Some(0.0));
+        assert_eq!(rsi(&[5.0; 10], 5), Some(50.0));
+
+        assert_eq!(rsi(&rising[..5], 5), None);
+        assert!(rsi(&rising[..6], 5).is_some());
+        assert_eq!(rsi(&rising, 0), None);
+    }
+}
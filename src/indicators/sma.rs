@@ -1,4 +1,12 @@
-#[derive(Copy, Clone)]
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+/// Default relative tolerance under which two SMAs are treated as equal.
+pub const DEFAULT_SMA_EQ_EPSILON: f64 = 1e-9;
+
+#[derive(Copy, Clone, Serialize)]
 pub struct Smas {
     pub sma_short: f64,
     pub sma_long: f64,
@@ -6,18 +14,111 @@ pub struct Smas {
     pub prev_sma_long: f64,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct SmaConfig {
     pub short_window: usize,
     pub long_window: usize,
+    /// Ordered SMA windows (fastest first) checked by the MA-stack filter,
+    /// e.g. [10, 20, 50]. Empty when the filter isn't used.
+    pub stack_windows: Vec<usize>,
+    /// Relative tolerance for SMA comparisons in the rules: values closer than
+    /// `eq_epsilon * max(|a|, |b|)` compare as equal (neutral).
+    pub eq_epsilon: f64,
+    /// Minimum relative gap between the SMAs for the trend to count as up/down;
+    /// closer than this they compare as equal (neutral). 0 = any gap beyond `eq_epsilon`.
+    pub min_separation_pct: f64,
 }
 
 impl SmaConfig {
-    pub fn sma_20_50() -> Self {
+    pub fn new(short_window: usize, long_window: usize) -> Self {
         Self {
-            short_window: 20,
-            long_window: 50,
+            short_window,
+            long_window,
+            stack_windows: Vec::new(),
+            eq_epsilon: DEFAULT_SMA_EQ_EPSILON,
+            min_separation_pct: 0.0,
+        }
+    }
+
+    pub fn sma_20_50() -> Self {
+        Self::new(20, 50)
+    }
+
+    /// Relative tolerance for comparing SMA(short) with SMA(long): the larger of
+    /// `eq_epsilon` and `min_separation_pct`.
+    pub fn trend_epsilon(&self) -> f64 {
+        self.eq_epsilon.max(self.min_separation_pct)
+    }
+
+    /// Same config with the long window shrunk so that `candles` candles still leave
+    /// `min_usable` (at least one) with computable SMAs. None when it already fits, or
+    /// when the shrunk window would no longer be longer than the short one.
+    pub fn fit_to_history(&self, candles: usize, min_usable: usize) -> Option<Self> {
+        let long_window = candles.checked_sub(min_usable.max(1))?;
+        if long_window >= self.long_window {
+            return None;
+        }
+        let fitted = Self {
+            long_window,
+            ..self.clone()
+        };
+        fitted.validate().ok().map(|()| fitted)
+    }
+
+    /// The long SMA must be strictly longer than the short one (and the short one non-empty);
+    /// otherwise both track the same thing and crossovers are meaningless.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.short_window == 0 {
+            return Err("short_window must be at least 1".into());
+        }
+        if self.long_window <= self.short_window {
+            return Err(format!(
+                "long_window ({}) must be greater than short_window ({})",
+                self.long_window, self.short_window
+            ));
         }
+        Ok(())
+    }
+}
+
+/// Compare `a` to `b`, treating values within `rel_epsilon` (relative to the larger
+/// magnitude) as equal.
+pub fn cmp_with_epsilon(a: f64, b: f64, rel_epsilon: f64) -> Ordering {
+    if (a - b).abs() <= rel_epsilon * a.abs().max(b.abs()) {
+        Ordering::Equal
+    } else if a > b {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    }
+}
+
+/// Ordering of a set of SMAs, fastest window first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaStack {
+    /// Each SMA strictly above the next slower one (e.g. SMA10 > SMA20 > SMA50).
+    Bullish,
+    /// Each SMA strictly below the next slower one.
+    Bearish,
+    /// Anything else.
+    Mixed,
+}
+
+/// Check whether the SMAs over `windows` (fastest first) are stacked in order.
+/// Returns None if there isn't enough data for every window, or fewer than two
+/// windows to order.
+pub fn ma_stack(prices: &[f64], windows: &[usize]) -> Option<MaStack> {
+    if windows.len() < 2 {
+        return None;
+    }
+    let smas = compute_many_smas(prices, windows)?;
+
+    if smas.windows(2).all(|pair| pair[0] > pair[1]) {
+        Some(MaStack::Bullish)
+    } else if smas.windows(2).all(|pair| pair[0] < pair[1]) {
+        Some(MaStack::Bearish)
+    } else {
+        Some(MaStack::Mixed)
     }
 }
 
@@ -34,9 +135,61 @@ pub fn simple_moving_average(prices: &[f64], window: usize) -> Option<f64> {
     Some(sum / window as f64)
 }
 
+/// SMAs over the last `w` values for every `w` in `windows` (same order as given).
+/// Done in one backward pass over the longest window.
+/// Returns None if there isn't enough data for every window (or a window is 0).
+pub fn compute_many_smas(prices: &[f64], windows: &[usize]) -> Option<Vec<f64>> {
+    let longest = windows.iter().copied().max()?;
+    if prices.len() < longest || windows.contains(&0) {
+        return None;
+    }
+
+    // Visit windows shortest first while walking back from the last price
+    let mut order: Vec<usize> = (0..windows.len()).collect();
+    order.sort_by_key(|&i| windows[i]);
+
+    let mut out = vec![0.0; windows.len()];
+    let mut next = order.iter().peekable();
+    let mut sum = 0.0;
+    for (count, &price) in prices.iter().rev().take(longest).enumerate() {
+        sum += price;
+        while let Some(&&i) = next.peek() {
+            if windows[i] != count + 1 {
+                break;
+            }
+            out[i] = sum / windows[i] as f64;
+            next.next();
+        }
+    }
+    Some(out)
+}
+
+/// SMA over every growing prefix of `prices` (one entry per price).
+/// Entries are None until `window` prices are available.
+pub fn sma_series(prices: &[f64], window: usize) -> Vec<Option<f64>> {
+    if window == 0 {
+        return vec![None; prices.len()];
+    }
+
+    let mut out = Vec::with_capacity(prices.len());
+    let mut sum = 0.0;
+    for (i, &price) in prices.iter().enumerate() {
+        sum += price;
+        if i >= window {
+            sum -= prices[i - window];
+        }
+        out.push(if i + 1 >= window {
+            Some(sum / window as f64)
+        } else {
+            None
+        });
+    }
+    out
+}
+
 /// Compute SMA<short>, SMA<long> and their "previous candle" versions.
 /// Returns None if not enough data (needs at least <long+1> prices).
-pub fn compute_smas(prices: &[f64], cfg: SmaConfig) -> Option<Smas> {
+pub fn compute_smas(prices: &[f64], cfg: &SmaConfig) -> Option<Smas> {
     if prices.len() < cfg.long_window + 1 {
         return None;
     }
@@ -56,6 +209,83 @@ pub fn compute_smas(prices: &[f64], cfg: SmaConfig) -> Option<Smas> {
     })
 }
 
+/// Mean of the last `window` values pushed, kept as a running sum.
+#[derive(Debug, Clone)]
+struct RollingMean {
+    window: usize,
+    values: VecDeque<f64>,
+    sum: f64,
+    /// Pushes since the sum was last recomputed from `values`.
+    since_resum: usize,
+}
+
+impl RollingMean {
+    fn new(window: usize) -> Self {
+        Self {
+            window,
+            values: VecDeque::with_capacity(window + 1),
+            sum: 0.0,
+            since_resum: 0,
+        }
+    }
+
+    fn push(&mut self, value: f64) -> Option<f64> {
+        if self.window == 0 {
+            return None;
+        }
+        self.values.push_back(value);
+        self.sum += value;
+        if self.values.len() > self.window {
+            self.sum -= self.values.pop_front()?;
+        }
+        // Re-add from scratch once per window so rounding can't drift (amortized O(1))
+        self.since_resum += 1;
+        if self.since_resum >= self.window {
+            self.sum = self.values.iter().sum();
+            self.since_resum = 0;
+        }
+        (self.values.len() == self.window).then(|| self.sum / self.window as f64)
+    }
+}
+
+/// `compute_smas` one price at a time: each `push` is O(1) instead of re-summing
+/// both windows, for loops that walk a series candle by candle. Values agree with
+/// `compute_smas` over the same prices up to floating-point rounding.
+#[derive(Debug, Clone)]
+pub struct RollingSma {
+    short: RollingMean,
+    long: RollingMean,
+    /// (short, long) SMAs after the previous push, once both windows are full.
+    prev: Option<(f64, f64)>,
+}
+
+impl RollingSma {
+    pub fn new(cfg: &SmaConfig) -> Self {
+        Self {
+            short: RollingMean::new(cfg.short_window),
+            long: RollingMean::new(cfg.long_window),
+            prev: None,
+        }
+    }
+
+    /// Add the next price; None until `long_window + 1` prices were pushed, like
+    /// `compute_smas`.
+    pub fn push(&mut self, price: f64) -> Option<Smas> {
+        let current = self.short.push(price).zip(self.long.push(price));
+        let smas = match (current, self.prev) {
+            (Some((sma_short, sma_long)), Some((prev_sma_short, prev_sma_long))) => Some(Smas {
+                sma_short,
+                sma_long,
+                prev_sma_short,
+                prev_sma_long,
+            }),
+            _ => None,
+        };
+        self.prev = current;
+        smas
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,10 +334,49 @@ mod tests {
         assert_eq!(simple_moving_average(&prices, 1), None);
     }
 
+    #[test]
+    fn test_rolling_sma_matches_compute_smas() {
+        let prices: Vec<f64> = (0..500)
+            .map(|i| 70_000.0 + (i as f64 * 0.37).sin() * 1_234.5 + i as f64 * 3.1)
+            .collect();
+        let cfg = SmaConfig::new(20, 50);
+        let mut rolling = RollingSma::new(&cfg);
+
+        for end in 1..=prices.len() {
+            let got = rolling.push(prices[end - 1]);
+            let want = compute_smas(&prices[..end], &cfg);
+            assert_eq!(got.is_some(), want.is_some(), "prefix of {end}");
+            if let (Some(got), Some(want)) = (got, want) {
+                for (a, b) in [
+                    (got.sma_short, want.sma_short),
+                    (got.sma_long, want.sma_long),
+                    (got.prev_sma_short, want.prev_sma_short),
+                    (got.prev_sma_long, want.prev_sma_long),
+                ] {
+                    approx_eq(a, b, b.abs() * 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sma_series_matches_simple_moving_average_on_each_prefix() {
+        let prices: Vec<f64> = (1..=30).map(|x| (x as f64).sin() * 10.0 + 100.0).collect();
+        let series = sma_series(&prices, 7);
+
+        assert_eq!(series.len(), prices.len());
+        for (i, value) in series.iter().enumerate() {
+            match simple_moving_average(&prices[..=i], 7) {
+                Some(expected) => approx_eq(value.unwrap(), expected, 1e-9),
+                None => assert!(value.is_none()),
+            }
+        }
+    }
+
     #[test]
     fn test_compute_smas_returns_none_when_less_than_51_prices() {
         let prices: Vec<f64> = (1..=50).map(|x| x as f64).collect();
-        assert!(compute_smas(&prices, SmaConfig::sma_20_50()).is_none());
+        assert!(compute_smas(&prices, &SmaConfig::sma_20_50()).is_none());
     }
 
     #[test]
@@ -115,7 +384,7 @@ mod tests {
         // prices = 1..=51
         let prices: Vec<f64> = (1..=51).map(|x| x as f64).collect();
 
-        let smas = compute_smas(&prices, SmaConfig::sma_20_50()).expect("should have SMAs");
+        let smas = compute_smas(&prices, &SmaConfig::sma_20_50()).expect("should have SMAs");
 
         // Last 20 values: 32..=51 -> average = (32 + 51) / 2 = 41.5
         approx_eq(smas.sma_short, 41.5, 1e-9);
@@ -136,7 +405,7 @@ mod tests {
         // prices = 1..=60
         let prices: Vec<f64> = (1..=60).map(|x| x as f64).collect();
 
-        let smas = compute_smas(&prices, SmaConfig::sma_20_50()).expect("should have SMAs");
+        let smas = compute_smas(&prices, &SmaConfig::sma_20_50()).expect("should have SMAs");
 
         // Current SMA20: last 20 values -> 41..=60 -> avg = (41 + 60) / 2 = 50.5
         approx_eq(smas.sma_short, 50.5, 1e-9);
@@ -151,4 +420,39 @@ mod tests {
         // Prev SMA50: last 50 of 1..=59 -> 10..=59 -> avg = (10 + 59) / 2 = 34.5
         approx_eq(smas.prev_sma_long, 34.5, 1e-9);
     }
+
+    #[test]
+    fn test_compute_many_smas_matches_individual_smas() {
+        let prices: Vec<f64> = (1..=60).map(|x| (x as f64).cos() * 5.0 + 50.0).collect();
+        let windows = [20, 5, 50];
+
+        let smas = compute_many_smas(&prices, &windows).unwrap();
+        assert_eq!(smas.len(), 3);
+        for (sma, &window) in smas.iter().zip(&windows) {
+            approx_eq(*sma, simple_moving_average(&prices, window).unwrap(), 1e-9);
+        }
+
+        assert_eq!(compute_many_smas(&prices, &[5, 61]), None);
+        assert_eq!(compute_many_smas(&prices, &[]), None);
+    }
+
+    #[test]
+    fn test_ma_stack_detects_stacked_and_jumbled_order() {
+        // Steady rise: SMA(2) > SMA(3) > SMA(5)
+        let rising = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(ma_stack(&rising, &[2, 3, 5]), Some(MaStack::Bullish));
+
+        let falling = [6.0, 5.0, 4.0, 3.0, 2.0, 1.0];
+        assert_eq!(ma_stack(&falling, &[2, 3, 5]), Some(MaStack::Bearish));
+
+        // SMA(2) = 3.5, SMA(3) = 4, SMA(5) = 3.8 -> not monotonic
+        let jumbled = [1.0, 1.0, 6.0, 5.0, 4.0, 3.0];
+        assert_eq!(ma_stack(&jumbled, &[2, 3, 5]), Some(MaStack::Mixed));
+
+        assert_eq!(ma_stack(&rising, &[2, 3, 10]), None);
+
+        // Nothing to order
+        assert_eq!(ma_stack(&rising, &[]), None);
+        assert_eq!(ma_stack(&rising, &[2]), None);
+    }
 }
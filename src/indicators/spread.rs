@@ -0,0 +1,64 @@
+use crate::stats::stddev;
+
+/// Z-score of the latest log-spread `ln(a) - ln(b)` (the log of the price ratio)
+/// against its last `window` values, for two series aligned on the same timestamps
+/// (see `data::align`). Positive when `a` is rich relative to `b`.
+/// Returns None if the series differ in length, there are fewer than `window`
+/// prices, a price isn't positive, or the spread doesn't vary over the window.
+pub fn spread_zscore(a: &[f64], b: &[f64], window: usize) -> Option<f64> {
+    if a.len() != b.len() || window < 2 || a.len() < window {
+        return None;
+    }
+
+    let start = a.len() - window;
+    let spreads: Vec<f64> = a[start..]
+        .iter()
+        .zip(&b[start..])
+        .map(|(&pa, &pb)| (pa > 0.0 && pb > 0.0).then(|| pa.ln() - pb.ln()))
+        .collect::<Option<_>>()?;
+
+    let std = stddev(&spreads, 0)?;
+    let mean = spreads.iter().sum::<f64>() / window as f64;
+    let last = *spreads.last()?;
+    Some((last - mean) / std)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spread_zscore_flags_divergence_of_correlated_series() {
+        // b tracks a at ~half its price with a little noise, until a jumps 10% on the last candle
+        let a: Vec<f64> = (0..30)
+            .map(|i| 100.0 + 5.0 * (i as f64 / 4.0).sin())
+            .collect();
+        let mut b: Vec<f64> = a
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p / 2.0 * (1.0 + 0.001 * (i as f64 * 1.7).cos()))
+            .collect();
+
+        let calm = spread_zscore(&a, &b, 20).unwrap();
+        assert!(calm.abs() < 2.0, "z = {calm}");
+
+        let mut diverged = a.clone();
+        *diverged.last_mut().unwrap() *= 1.1;
+        let z = spread_zscore(&diverged, &b, 20).unwrap();
+        assert!(z > 3.0, "z = {z}");
+
+        // ...and the mirror image when b is the one that jumps
+        *b.last_mut().unwrap() *= 1.1;
+        assert!(spread_zscore(&a, &b, 20).unwrap() < -3.0);
+    }
+
+    #[test]
+    fn test_spread_zscore_none_without_enough_data_or_variance() {
+        let a = [10.0, 11.0, 12.0];
+        assert_eq!(spread_zscore(&a, &[5.0, 5.5, 6.0], 5), None);
+        // Constant ratio -> zero variance
+        assert_eq!(spread_zscore(&a, &[5.0, 5.5, 6.0], 3), None);
+        assert_eq!(spread_zscore(&a, &[5.0, 5.5], 2), None);
+        assert_eq!(spread_zscore(&a, &[5.0, 0.0, 6.0], 3), None);
+    }
+}
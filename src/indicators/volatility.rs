@@ -0,0 +1,59 @@
+use crate::stats::stddev;
+
+/// Realized volatility: sample standard deviation of the last `window` log returns.
+/// It's per candle; scale with `annualize_volatility`. Unlike ATR it only looks at
+/// close-to-close moves.
+/// Returns None with fewer than `window + 1` prices, a `window` under 2 or a
+/// non-positive price in the window. Constant returns give 0.
+pub fn realized_volatility(prices: &[f64], window: usize) -> Option<f64> {
+    if window < 2 || prices.len() < window + 1 {
+        return None;
+    }
+
+    let returns: Vec<f64> = prices[prices.len() - window - 1..]
+        .windows(2)
+        .map(|w| (w[0] > 0.0 && w[1] > 0.0).then(|| (w[1] / w[0]).ln()))
+        .collect::<Option<_>>()?;
+
+    Some(stddev(&returns, 1).unwrap_or(0.0))
+}
+
+/// Scale a per-candle volatility to `periods_per_year` candles (e.g. 24 * 365 for hourly).
+pub fn annualize_volatility(per_candle: f64, periods_per_year: f64) -> f64 {
+    per_candle * periods_per_year.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_realized_volatility_of_known_returns() {
+        // Log returns alternate +1% / -1%: mean 0, sample variance 4 * 0.01^2 / 3
+        let prices: Vec<f64> = (0..9)
+            .map(|i| {
+                if i % 2 == 0 {
+                    100.0
+                } else {
+                    100.0 * 0.01_f64.exp()
+                }
+            })
+            .collect();
+        let vol = realized_volatility(&prices, 4).unwrap();
+        assert!((vol - 0.01 * (4.0_f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert!((annualize_volatility(vol, 365.0) - vol * 365.0_f64.sqrt()).abs() < 1e-12);
+
+        // Steady growth: every return is the same
+        let steady: Vec<f64> = (0..6).map(|i| 100.0 * 1.01_f64.powi(i)).collect();
+        assert_eq!(realized_volatility(&steady, 5), Some(0.0));
+    }
+
+    #[test]
+    fn test_realized_volatility_none_without_enough_data() {
+        let prices = [100.0, 101.0, 99.0, 102.0];
+        assert_eq!(realized_volatility(&prices, 4), None);
+        assert_eq!(realized_volatility(&prices, 1), None);
+        assert_eq!(realized_volatility(&[100.0, 0.0, 99.0, 102.0], 3), None);
+        assert!(realized_volatility(&prices, 3).is_some());
+    }
+}
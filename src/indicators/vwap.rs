@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+
+use crate::data::Sample;
+
+/// Volume-weighted average close from the first candle at or after `anchor` (e.g. a
+/// major swing low) to the end. Candles must be in time order.
+/// Returns None if every candle is before the anchor, a candle from the anchor on has
+/// no volume, or their total volume is zero.
+pub fn anchored_vwap(candles: &[Sample], anchor: DateTime<Utc>) -> Option<f64> {
+    let start = candles.partition_point(|c| c.ts < anchor);
+    let (mut notional, mut volume) = (0.0, 0.0);
+    for candle in &candles[start..] {
+        let v = candle.volume?;
+        notional += candle.price * v;
+        volume += v;
+    }
+    (volume > 0.0).then(|| notional / volume)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn candles(prices_and_volumes: &[(f64, f64)]) -> Vec<Sample> {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        prices_and_volumes
+            .iter()
+            .enumerate()
+            .map(|(i, &(price, volume))| Sample {
+                ts: start + Duration::hours(i as i64),
+                price,
+                high: None,
+                low: None,
+                volume: Some(volume),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_anchored_vwap_only_weights_candles_from_the_anchor() {
+        // The heavy candles before the anchor don't count
+        let series = candles(&[(50.0, 1_000.0), (60.0, 1_000.0), (100.0, 1.0), (110.0, 3.0)]);
+
+        let vwap = anchored_vwap(&series, series[2].ts).unwrap();
+        assert!((vwap - (100.0 + 110.0 * 3.0) / 4.0).abs() < 1e-12);
+
+        // An anchor between candles starts at the next one
+        let vwap = anchored_vwap(&series, series[2].ts + Duration::minutes(30)).unwrap();
+        assert!((vwap - 110.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_anchored_vwap_none_after_the_end_or_without_volume() {
+        let series = candles(&[(100.0, 1.0), (110.0, 0.0)]);
+        assert_eq!(
+            anchored_vwap(&series, series[1].ts + Duration::hours(1)),
+            None
+        );
+        assert_eq!(anchored_vwap(&series, series[1].ts), None);
+
+        let mut missing = series.clone();
+        missing[1].volume = None;
+        assert_eq!(anchored_vwap(&missing, series[0].ts), None);
+    }
+}
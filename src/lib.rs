@@ -1,6 +1,12 @@
 pub mod backtest;
 pub mod data;
+pub mod export;
 pub mod indicators;
+pub mod live;
 pub mod output;
 pub mod patterns;
+pub mod reason;
 pub mod signal;
+pub mod source;
+pub mod stats;
+pub mod units;
@@ -0,0 +1,329 @@
+use std::fmt::Write;
+
+use anyhow::{Result, bail};
+use chrono::Duration;
+
+use crate::data::Sample;
+use crate::indicators::compute_smas;
+use crate::signal::{AnalysisResult, StrategyConfig, analyze};
+
+/// Incremental analysis of a live tick stream.
+///
+/// Ticks are bucketed into candles the same way `resample_to_n_hours` does (epoch
+/// aligned, closing on the last tick, with the ticks' high/low and total volume). The candle still forming only feeds the
+/// *provisional* decision, which can flip with every tick; the *confirmed* decision
+/// is computed once per candle, when the next bucket's first tick closes it, and
+/// never repaints. Alerts should act on the confirmed one.
+pub struct LiveAnalyzer {
+    strategy: StrategyConfig,
+    step_secs: i64,
+    /// Closed candles, oldest first; trimmed when the strategy's lookback is bounded.
+    closed: Vec<Sample>,
+    /// Bucket start (Unix seconds) and the candle being formed, up to the latest tick.
+    forming: Option<(i64, Sample)>,
+    confirmed: Option<AnalysisResult>,
+    ticks: u64,
+    candles_closed: u64,
+    /// Confirmed BUY and SELL decisions so far.
+    signals: [u64; 2],
+}
+
+impl LiveAnalyzer {
+    /// Fails if `step` is shorter than one second.
+    pub fn new(strategy: StrategyConfig, step: Duration) -> Result<Self> {
+        let step_secs = step.num_seconds();
+        if step_secs <= 0 {
+            bail!("candle step must be at least one second, got {step}");
+        }
+        Ok(Self {
+            strategy,
+            step_secs,
+            closed: Vec::new(),
+            forming: None,
+            confirmed: None,
+            ticks: 0,
+            candles_closed: 0,
+            signals: [0; 2],
+        })
+    }
+
+    /// Feed one tick. Returns true when it closed a candle, i.e. the confirmed
+    /// decision was recomputed. Ticks older than the forming candle are rejected.
+    pub fn push(&mut self, tick: Sample) -> Result<bool> {
+        let bucket = tick.ts.timestamp().div_euclid(self.step_secs) * self.step_secs;
+        if let Some((start, candle)) = &mut self.forming {
+            if bucket < *start || tick.ts < candle.ts {
+                bail!(
+                    "tick at {} is older than the last one ({})",
+                    tick.ts,
+                    candle.ts
+                );
+            }
+            if bucket == *start {
+                self.ticks += 1;
+                candle.absorb(&tick);
+                return Ok(false);
+            }
+        }
+        self.ticks += 1;
+        match self.forming.replace((bucket, tick)) {
+            Some((_, last)) => {
+                self.close(last);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Decision on the last fully closed candle; None until enough candles closed.
+    pub fn confirmed(&self) -> Option<&AnalysisResult> {
+        self.confirmed.as_ref()
+    }
+
+    /// Decision as if the forming candle closed at its latest tick. May change
+    /// before the candle closes; don't alert on it.
+    pub fn provisional(&self) -> Option<AnalysisResult> {
+        let (_, forming) = self.forming.as_ref()?;
+        let mut candles = self.closed.clone();
+        candles.push(forming.clone());
+        self.analyze(&candles)
+    }
+
+    /// Counters and gauges in the Prometheus text exposition format, for a scrape
+    /// endpoint. Counters cover accepted ticks, closed candles and confirmed signals;
+    /// gauges describe the confirmed decision (action is 1 for BUY, -1 for SELL, 0 for
+    /// HOLD) and are left out until there is one.
+    pub fn metrics_text(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+            let _ = writeln!(out, "# HELP trade_signal_{name} {help}");
+            let _ = writeln!(out, "# TYPE trade_signal_{name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(out, "trade_signal_{name}{labels} {value}");
+            }
+        };
+
+        metric(
+            "ticks_total",
+            "counter",
+            "Ticks accepted.",
+            &[("", self.ticks as f64)],
+        );
+        metric(
+            "candles_closed_total",
+            "counter",
+            "Candles closed.",
+            &[("", self.candles_closed as f64)],
+        );
+        metric(
+            "signals_total",
+            "counter",
+            "Confirmed BUY/SELL decisions.",
+            &[
+                ("{action=\"BUY\"}", self.signals[0] as f64),
+                ("{action=\"SELL\"}", self.signals[1] as f64),
+            ],
+        );
+        if let Some(confirmed) = &self.confirmed {
+            let action = match confirmed.suggestion.as_str() {
+                "BUY" => 1.0,
+                "SELL" => -1.0,
+                _ => 0.0,
+            };
+            metric(
+                "action",
+                "gauge",
+                "Confirmed decision: 1 BUY, -1 SELL, 0 HOLD.",
+                &[("", action)],
+            );
+            metric(
+                "last_price",
+                "gauge",
+                "Close of the last confirmed candle.",
+                &[("", confirmed.last.price)],
+            );
+            metric(
+                "sma_short",
+                "gauge",
+                "SMA(short) at the last confirmed candle.",
+                &[("", confirmed.smas.sma_short)],
+            );
+            metric(
+                "sma_long",
+                "gauge",
+                "SMA(long) at the last confirmed candle.",
+                &[("", confirmed.smas.sma_long)],
+            );
+        }
+        out
+    }
+
+    fn close(&mut self, candle: Sample) {
+        self.closed.push(candle);
+        if let Some(keep) = self.strategy.required_history()
+            && self.closed.len() >= 2 * keep.max(1)
+        {
+            self.closed.drain(..self.closed.len() - keep);
+        }
+        self.confirmed = self.analyze(&self.closed);
+        self.candles_closed += 1;
+        match self.confirmed.as_ref().map(|c| c.suggestion.as_str()) {
+            Some("BUY") => self.signals[0] += 1,
+            Some("SELL") => self.signals[1] += 1,
+            _ => {}
+        }
+    }
+
+    fn analyze(&self, candles: &[Sample]) -> Option<AnalysisResult> {
+        let prices: Vec<f64> = candles.iter().map(|s| s.price).collect();
+        let smas = compute_smas(&prices, &self.strategy.sma_config)?;
+        analyze(candles, &prices, smas, &self.strategy, false).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::sma::SmaConfig;
+    use crate::signal::FilterConfig;
+    use chrono::{TimeZone, Utc};
+
+    fn tick(hour: u32, min: u32, price: f64) -> Sample {
+        let ts = Utc
+            .with_ymd_and_hms(2025, 1, 1, hour, min, 0)
+            .single()
+            .expect("valid datetime");
+        Sample {
+            ts,
+            price,
+            high: None,
+            low: None,
+            volume: None,
+        }
+    }
+
+    fn strategy() -> StrategyConfig {
+        StrategyConfig {
+            breakouts: None,
+            pullbacks: None,
+            enable_crossovers: false,
+            enable_bias_only: true,
+            enable_macd: None,
+            rule_order: Vec::new(),
+            bollinger: None,
+            sma_config: SmaConfig::new(2, 3),
+            filters: FilterConfig::default(),
+        }
+    }
+
+    fn suggestion(result: Option<&AnalysisResult>) -> Option<&str> {
+        result.map(|r| r.suggestion.as_str())
+    }
+
+    #[test]
+    fn test_confirmed_decision_only_changes_on_bucket_rollover() {
+        let mut live = LiveAnalyzer::new(strategy(), Duration::hours(1)).unwrap();
+        for hour in 0..3 {
+            assert_eq!(live.push(tick(hour, 10, 12.0)).unwrap(), hour > 0);
+            live.push(tick(hour, 50, 10.0)).unwrap();
+        }
+
+        // Hour 3 forms: the provisional decision follows every tick
+        assert!(live.push(tick(3, 5, 11.0)).unwrap());
+        assert_eq!(live.provisional().unwrap().suggestion, "BUY");
+        live.push(tick(3, 40, 9.0)).unwrap();
+        assert_eq!(live.provisional().unwrap().suggestion, "SELL");
+        assert_eq!(suggestion(live.confirmed()), None);
+
+        // Hour 4's first tick closes hour 3 at 9.0
+        assert!(live.push(tick(4, 0, 12.0)).unwrap());
+        let confirmed = live.confirmed().unwrap();
+        assert_eq!(confirmed.suggestion, "SELL");
+        assert_eq!(confirmed.last.ts, tick(3, 40, 9.0).ts);
+        assert_eq!(confirmed.last.price, 9.0);
+
+        // Ticks inside hour 4 turn the provisional decision around, not the confirmed one
+        live.push(tick(4, 30, 13.0)).unwrap();
+        assert_eq!(live.provisional().unwrap().suggestion, "BUY");
+        assert_eq!(suggestion(live.confirmed()), Some("SELL"));
+
+        // Closing hour 4 at 13.0 confirms the BUY
+        assert!(live.push(tick(5, 0, 13.0)).unwrap());
+        assert_eq!(suggestion(live.confirmed()), Some("BUY"));
+    }
+
+    #[test]
+    fn test_closed_candle_merges_its_ticks() {
+        let mut live = LiveAnalyzer::new(strategy(), Duration::hours(1)).unwrap();
+        for hour in 0..3 {
+            live.push(tick(hour, 0, 10.0)).unwrap();
+        }
+        for (min, price, high, low, volume) in [
+            (5, 11.0, 11.5, 10.5, 2.0),
+            (20, 14.0, 15.0, 13.0, 3.0),
+            (40, 12.0, 12.5, 9.0, 1.5),
+        ] {
+            live.push(Sample {
+                high: Some(high),
+                low: Some(low),
+                volume: Some(volume),
+                ..tick(3, min, price)
+            })
+            .unwrap();
+        }
+
+        assert!(live.push(tick(4, 0, 12.0)).unwrap());
+        let candle = &live.confirmed().unwrap().last;
+        assert_eq!(candle.ts, tick(3, 40, 12.0).ts);
+        assert_eq!(candle.price, 12.0);
+        assert_eq!(candle.high, Some(15.0));
+        assert_eq!(candle.low, Some(9.0));
+        assert_eq!(candle.volume, Some(6.5));
+    }
+
+    #[test]
+    fn test_metrics_text_reports_counters_and_confirmed_gauges() {
+        let mut live = LiveAnalyzer::new(strategy(), Duration::hours(1)).unwrap();
+        live.push(tick(0, 0, 10.0)).unwrap();
+        let text = live.metrics_text();
+        assert!(text.contains("# TYPE trade_signal_ticks_total counter\n"));
+        assert!(text.contains("trade_signal_ticks_total 1\n"));
+        assert!(!text.contains("trade_signal_action"));
+
+        // Closes at 10, 10, 10, 11, 12: a BUY once SMA(2) rises above SMA(3)
+        for (hour, price) in [(1, 10.0), (2, 10.0), (3, 11.0), (4, 12.0), (5, 12.0)] {
+            live.push(tick(hour, 0, price)).unwrap();
+        }
+        let text = live.metrics_text();
+        assert!(text.contains("trade_signal_ticks_total 6\n"));
+        assert!(text.contains("trade_signal_candles_closed_total 5\n"));
+        assert!(text.contains("trade_signal_signals_total{action=\"BUY\"} 2\n"));
+        assert!(text.contains("trade_signal_signals_total{action=\"SELL\"} 0\n"));
+        assert!(text.contains("trade_signal_action 1\n"));
+        assert!(text.contains("trade_signal_last_price 12\n"));
+        assert!(text.contains("trade_signal_sma_short 11.5\n"));
+    }
+
+    #[test]
+    fn test_push_rejects_ticks_from_the_past() {
+        let mut live = LiveAnalyzer::new(strategy(), Duration::hours(1)).unwrap();
+        live.push(tick(1, 30, 10.0)).unwrap();
+        assert!(live.push(tick(1, 10, 10.0)).is_err());
+        live.push(tick(2, 0, 10.0)).unwrap();
+        assert!(live.push(tick(1, 45, 10.0)).is_err());
+
+        // The rejected ticks aren't counted, and the forming candle survives them:
+        // the next bucket's first tick still closes it
+        assert!(live.push(tick(3, 0, 10.0)).unwrap());
+        let text = live.metrics_text();
+        assert!(text.contains("trade_signal_ticks_total 3\n"));
+        assert!(text.contains("trade_signal_candles_closed_total 2\n"));
+    }
+
+    #[test]
+    fn test_new_rejects_sub_second_steps() {
+        assert!(LiveAnalyzer::new(strategy(), Duration::milliseconds(500)).is_err());
+        assert!(LiveAnalyzer::new(strategy(), Duration::zero()).is_err());
+        assert!(LiveAnalyzer::new(strategy(), Duration::seconds(1)).is_ok());
+    }
+}
@@ -1,8 +1,11 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use trade_signal::{
     indicators::sma::SmaConfig,
-    signal::{BreakoutConfig, FilterConfig, PullbackConfig, StrategyConfig},
+    signal::{
+        BreakoutConfig, BreakoutTrigger, FilterConfig, PullbackConfig, PullbackToleranceMode,
+        StrategyConfig,
+    },
 };
 
 use std::path::PathBuf;
@@ -15,6 +18,33 @@ struct Args {
     /// Path to the CSV file (timestamp,price)
     #[arg(long)]
     input: PathBuf,
+
+    /// Print the outcome of every rule evaluated on the last candle
+    #[arg(long)]
+    trace: bool,
+
+    /// Output format of the analysis; progress messages go to stderr with json
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Run something other than the analysis of the last candle
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Dump the per-candle indicators (SMAs, ATR%, regime) as CSV instead of analyzing
+    ExportIndicators {
+        /// Path of the CSV to write
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
 }
 
 fn main() -> Result<()> {
@@ -22,58 +52,80 @@ fn main() -> Result<()> {
 
     // Load raw samples from CSV
     let samples = trade_signal::data::get_samples_from_input_file(&args.input)?;
-    if samples.is_empty() {
-        println!("No data found in CSV.");
-        return Ok(());
-    }
-
     // Resample to hourly closes
-    let hourly = trade_signal::data::resample_to_hourly(&samples);
-    println!(
+    let (hourly, gaps) = trade_signal::data::resample_to_n_hours_checked(&samples, 1);
+    let json = args.format == OutputFormat::Json;
+    // Keep stdout to the analysis alone when it's JSON
+    let info = |msg: String| {
+        if json {
+            eprintln!("{msg}");
+        } else {
+            println!("{msg}");
+        }
+    };
+    info(format!(
         "Loaded {} raw points, {} hourly candles after resampling.",
         samples.len(),
         hourly.len()
-    );
-    if hourly.is_empty() {
-        println!("No hourly data after resampling.");
-        return Ok(());
+    ));
+    if let Some(gaps) = trade_signal::data::describe_gaps(&gaps) {
+        info(format!("Warning: {gaps} in the resampled data"));
     }
+    trade_signal::data::validate_dataset(&hourly)?;
 
     // Extract prices and compute SMAs
     let prices: Vec<f64> = hourly.iter().map(|s| s.price).collect();
     let sma_config = SmaConfig::sma_20_50();
-    let Some(smas) = trade_signal::indicators::compute_smas(&prices, sma_config) else {
-        println!(
+    let Some(smas) = trade_signal::indicators::compute_smas(&prices, &sma_config) else {
+        info(format!(
             "Not enough data: need at least 51 hourly candles for SMA20/50 logic, got {}.",
             prices.len()
-        );
+        ));
         return Ok(());
     };
 
     let strategy = StrategyConfig {
         breakouts: Some(BreakoutConfig {
             breakout_lookback: BREAKDOWN_LOOKBACK,
+            min_trend_bars: 0,
+            require_retest: None,
+            breakout_trigger: BreakoutTrigger::Close,
         }),
         enable_bias_only: true,
+        enable_macd: None,
+        rule_order: Vec::new(),
+        bollinger: None,
         enable_crossovers: true,
         pullbacks: Some(PullbackConfig {
             bounce_tolerance_pct: PULLBACK_TOLERANCE_PCT,
             reject_tolerance_pct: PULLBACK_TOLERANCE_PCT,
+            tolerance_mode: PullbackToleranceMode::Percent,
         }),
         sma_config,
         filters: FilterConfig {
-            atr: None,
-            regime: None,
             require_price_confirmation: true,
             require_trend_filter: true,
+            ..Default::default()
         },
     };
 
+    if let Some(Command::ExportIndicators { output }) = &args.command {
+        trade_signal::export::write_indicators_csv(&hourly, &strategy, output)?;
+        info(format!("Indicators written to {:?}", output));
+        return Ok(());
+    }
+
     // Perform final analysis
-    let result = trade_signal::signal::analyze(&hourly, &prices, smas, strategy);
+    let result = trade_signal::signal::analyze(&hourly, &prices, smas, &strategy, args.trace)?;
 
     // Print result.clone()
-    trade_signal::output::print_analysis(&result, sma_config);
+    let mut out = std::io::stdout();
+    match args.format {
+        OutputFormat::Text => {
+            trade_signal::output::print_analysis(&mut out, &result, &strategy.sma_config)?
+        }
+        OutputFormat::Json => trade_signal::output::print_analysis_json(&mut out, &result)?,
+    }
 
     Ok(())
 }
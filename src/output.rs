@@ -1,25 +1,114 @@
-use crate::{indicators::sma::SmaConfig, signal::AnalysisResult};
+use std::io::{self, Write};
 
-pub fn print_analysis(result: &AnalysisResult, sma_config: SmaConfig) {
-    println!("Last (hourly) timestamp: {}", result.last.ts);
-    println!("Last (hourly) price:     {:.4}", result.last.price);
-    println!(
+use anyhow::Result;
+
+use crate::{
+    indicators::sma::SmaConfig,
+    signal::{AnalysisResult, RuleOutcome},
+};
+
+pub fn print_analysis(
+    out: &mut impl Write,
+    result: &AnalysisResult,
+    sma_config: &SmaConfig,
+) -> io::Result<()> {
+    writeln!(out, "Last (hourly) timestamp: {}", result.last.ts)?;
+    writeln!(out, "Last (hourly) price:     {:.4}", result.last.price)?;
+    writeln!(
+        out,
         "SMA({}):                 {:.4}",
         sma_config.short_window, result.smas.sma_short
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "SMA({}):                 {:.4}",
         sma_config.long_window, result.smas.sma_long
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "Prev SMA({}):            {:.4}",
         sma_config.short_window, result.smas.prev_sma_short
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "Prev SMA({}):            {:.4}",
         sma_config.long_window, result.smas.prev_sma_long
-    );
+    )?;
+
+    writeln!(out, "Suggestion:              {}", result.suggestion)?;
+    writeln!(out, "Reason:                  {}", result.reason)?;
+
+    if let Some(trace) = &result.trace {
+        writeln!(out, "Trace:")?;
+        for entry in &trace.rules {
+            let outcome = match &entry.outcome {
+                RuleOutcome::NoMatch => "no match".to_string(),
+                RuleOutcome::Blocked { reason, gate } => format!("blocked ({gate}): {reason}"),
+                RuleOutcome::Fired(d) => format!("fired {}: {}", d.action, d.reason),
+            };
+            writeln!(out, "  {:<12} {}", entry.rule, outcome)?;
+        }
+    }
+    Ok(())
+}
+
+/// The analysis as a JSON object (last sample, SMAs, suggestion, reason and its
+/// code, strength), for piping into other tools. The rule trace isn't included.
+pub fn analysis_to_json(result: &AnalysisResult) -> Result<String> {
+    Ok(serde_json::to_string_pretty(result)?)
+}
+
+pub fn print_analysis_json(out: &mut impl Write, result: &AnalysisResult) -> Result<()> {
+    writeln!(out, "{}", analysis_to_json(result)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    use crate::data::Sample;
+    use crate::indicators::Smas;
+    use crate::reason::ReasonCode;
+    use crate::signal::SignalStrength;
+
+    #[test]
+    fn test_analysis_to_json_has_sample_smas_and_decision() {
+        let result = AnalysisResult {
+            last: Sample {
+                ts: Utc.with_ymd_and_hms(2025, 11, 22, 10, 0, 0).unwrap(),
+                price: 70234.12,
+                high: None,
+                low: None,
+                volume: None,
+            },
+            smas: Smas {
+                sma_short: 70180.55,
+                sma_long: 69990.32,
+                prev_sma_short: 70170.44,
+                prev_sma_long: 69980.11,
+            },
+            suggestion: "BUY".to_string(),
+            reason: "Golden Cross".to_string(),
+            reason_code: ReasonCode::GoldenCross,
+            strength: Some(SignalStrength::Strong),
+            rule: Some("Crossovers".to_string()),
+            trace: None,
+        };
+
+        let json: serde_json::Value =
+            serde_json::from_str(&analysis_to_json(&result).unwrap()).unwrap();
 
-    println!("Suggestion:              {}", result.suggestion);
-    println!("Reason:                  {}", result.reason);
+        assert_eq!(json["last"]["ts"], "2025-11-22T10:00:00Z");
+        assert_eq!(json["last"]["price"], 70234.12);
+        assert_eq!(json["smas"]["sma_short"], 70180.55);
+        assert_eq!(json["smas"]["prev_sma_long"], 69980.11);
+        assert_eq!(json["suggestion"], "BUY");
+        assert_eq!(json["reason"], "Golden Cross");
+        assert_eq!(json["reason_code"], "GoldenCross");
+        assert_eq!(json["strength"], "Strong");
+        assert_eq!(json["rule"], "Crossovers");
+        assert!(json.get("trace").is_none());
+    }
 }
@@ -1,3 +1,126 @@
+/// (low, high) of the `lookback` candles before the current one (the window the
+/// breakout checks compare against). None if there isn't enough data.
+pub fn recent_range(prices: &[f64], lookback: usize) -> Option<(f64, f64)> {
+    if lookback == 0 || prices.len() < lookback + 1 {
+        return None;
+    }
+
+    let last_idx = prices.len() - 1;
+    let window = &prices[last_idx - lookback..last_idx];
+    Some(
+        window
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &p| {
+                (lo.min(p), hi.max(p))
+            }),
+    )
+}
+
+/// Where the current close sits within the min/max of the last `window` closes
+/// (including it): 0 at the low, 1 at the high. None if there isn't enough data or
+/// the window is flat.
+pub fn price_percentile(prices: &[f64], window: usize) -> Option<f64> {
+    if window == 0 || prices.len() < window {
+        return None;
+    }
+
+    let closes = &prices[prices.len() - window..];
+    let (lo, hi) = closes
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &p| {
+            (lo.min(p), hi.max(p))
+        });
+    if hi - lo <= 0.0 {
+        return None;
+    }
+    let last = *closes.last()?;
+    Some((last - lo) / (hi - lo))
+}
+
+/// Progress of a breakout towards confirmation on retest.
+#[derive(Clone, Copy)]
+enum Retest {
+    Idle,
+    /// Closed above the recent high `level` at candle `at`.
+    Broken {
+        level: f64,
+        at: usize,
+    },
+    /// Has since come back within the tolerance of `level`.
+    Retested {
+        level: f64,
+        at: usize,
+    },
+}
+
+/// The recent high broken by a breakout that price then retested and held, when the
+/// hold is on the current candle.
+///
+/// After a breakout above the high of the `lookback` candles before it, price must
+/// come back within `tolerance_pct` of that level, then close above both the level and
+/// the previous close. Closing below the tolerance band, or `lookahead` candles passing
+/// since the breakout, drops it.
+pub fn breakout_retest_held(
+    prices: &[f64],
+    lookback: usize,
+    lookahead: usize,
+    tolerance_pct: f64,
+) -> Option<f64> {
+    let last = prices.len().checked_sub(1)?;
+    if lookback == 0 || lookahead == 0 || last < lookback {
+        return None;
+    }
+
+    let breakout_at = |i: usize| {
+        let (_, high) = recent_range(&prices[..=i], lookback)?;
+        (prices[i] > high + 1e-6 * high.abs()).then_some(high)
+    };
+    let band = |level: f64| tolerance_pct * level.abs();
+
+    let mut state = Retest::Idle;
+    // A breakout from before this window would have expired by now
+    for i in last.saturating_sub(lookahead).max(lookback)..=last {
+        let price = prices[i];
+        state = match state {
+            Retest::Broken { level, at } | Retest::Retested { level, at }
+                if i - at > lookahead || price < level - band(level) =>
+            {
+                Retest::Idle
+            }
+            Retest::Broken { level, at } if price <= level + band(level) => {
+                Retest::Retested { level, at }
+            }
+            Retest::Retested { level, .. } if price > level && price > prices[i - 1] => {
+                if i == last {
+                    return Some(level);
+                }
+                Retest::Idle
+            }
+            state => state,
+        };
+        if matches!(state, Retest::Idle)
+            && let Some(level) = breakout_at(i)
+        {
+            state = Retest::Broken { level, at: i };
+        }
+    }
+    None
+}
+
+/// Mirror image of `breakout_retest_held`: the recent low broken by a breakdown that
+/// price then retested from below and held under, on the current candle.
+pub fn breakdown_retest_held(
+    prices: &[f64],
+    lookback: usize,
+    lookahead: usize,
+    tolerance_pct: f64,
+) -> Option<f64> {
+    // Only the candles the breakout scan can reach
+    let tail = &prices[prices.len().saturating_sub(lookback + lookahead + 1)..];
+    let mirrored: Vec<f64> = tail.iter().map(|p| -p).collect();
+    breakout_retest_held(&mirrored, lookback, lookahead, tolerance_pct).map(|level| -level)
+}
+
 /// Check if we have a breakdown below a recent low.
 ///
 /// - Lookback N (e.g. 5) means:
@@ -575,4 +698,46 @@ mod tests {
             &prices, sma_short, 0.0003
         ));
     }
+
+    #[test]
+    fn test_breakout_retest_held_needs_retest_then_hold() {
+        // Breakout over 100 at index 3, back to 100.1 (retest), then holds at 101
+        let retested = [100.0, 99.0, 100.0, 103.0, 100.1, 101.0];
+        assert_eq!(breakout_retest_held(&retested, 3, 4, 0.002), Some(100.0));
+        // Only on the hold candle itself
+        assert_eq!(breakout_retest_held(&retested[..5], 3, 4, 0.002), None);
+        // Too slow: the breakout expired
+        assert_eq!(breakout_retest_held(&retested, 3, 1, 0.002), None);
+
+        // Never comes back to the level
+        assert_eq!(
+            breakout_retest_held(&[100.0, 99.0, 100.0, 103.0, 104.0, 105.0], 3, 4, 0.002),
+            None
+        );
+        // Falls through the band: a failed breakout
+        assert_eq!(
+            breakout_retest_held(&[100.0, 99.0, 100.0, 103.0, 99.0, 101.0], 3, 4, 0.002),
+            None
+        );
+
+        let mirrored: Vec<f64> = retested.iter().map(|p| 200.0 - p).collect();
+        assert_eq!(breakdown_retest_held(&mirrored, 3, 4, 0.002), Some(100.0));
+    }
+
+    #[test]
+    fn test_recent_range_excludes_last_candle() {
+        let prices = vec![100.0, 105.0, 98.0, 101.0, 120.0];
+        assert_eq!(recent_range(&prices, 3), Some((98.0, 105.0)));
+        assert_eq!(recent_range(&prices, 5), None);
+        assert_eq!(recent_range(&prices, 0), None);
+    }
+
+    #[test]
+    fn test_price_percentile_within_recent_range() {
+        let prices = vec![50.0, 100.0, 90.0, 110.0, 120.0];
+        assert!((price_percentile(&prices, 4).unwrap() - 1.0).abs() < 1e-12);
+        assert!((price_percentile(&[100.0, 110.0, 102.5], 3).unwrap() - 0.25).abs() < 1e-12);
+        assert_eq!(price_percentile(&[100.0, 100.0, 100.0], 3), None);
+        assert_eq!(price_percentile(&prices, 6), None);
+    }
 }
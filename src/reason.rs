@@ -0,0 +1,444 @@
+//! Structured reasons behind a suggestion, rendered to text separately so that
+//! callers can match on the code and front-ends can pick the wording.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Language a `ReasonCode` is rendered in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    English,
+}
+
+/// Side a gate vetoed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Side {
+    Long,
+    Short,
+}
+
+/// Why a rule fired, why it was vetoed, or why the analysis held.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ReasonCode {
+    // ~~~~ Signals ~~~~
+    GoldenCross,
+    DeathCross,
+    /// `retested` when the breakout was only acted on after holding a retest.
+    BreakoutAboveHigh {
+        retested: bool,
+    },
+    BreakdownBelowLow {
+        retested: bool,
+    },
+    PullbackBounce,
+    PullbackRejection,
+    MacdCrossAbove,
+    MacdCrossBelow,
+    BollingerReclaimLower,
+    BollingerReclaimUpper,
+    Uptrend,
+    Downtrend,
+    /// Free text from a rule outside this crate.
+    Custom(String),
+
+    // ~~~~ Gates ~~~~
+    /// Trend filter backed by the regime detector.
+    RegimeTrendVeto(Side),
+    TrendVeto(Side),
+    PriceConfirmationVeto(Side),
+    RegimeVeto(Side),
+    MaStackVeto(Side),
+    RoundNumberVeto {
+        side: Side,
+        level: f64,
+    },
+    /// RSI past the filter's overbought (long) or oversold (short) `threshold`.
+    RsiVeto {
+        side: Side,
+        rsi: f64,
+        threshold: f64,
+    },
+    /// The breakout candle's volume is under `min_ratio` times the `average`.
+    VolumeVeto {
+        side: Side,
+        volume: f64,
+        min_ratio: f64,
+        average: f64,
+    },
+    /// The breakout's SMA relationship has held for `held` of the `required` bars.
+    TrendTooYoung {
+        held: usize,
+        required: usize,
+    },
+    NoAtrForMove,
+    /// The triggering move is under `mult` ATRs.
+    MoveTooSmall {
+        move_size: f64,
+        mult: f64,
+        atr: f64,
+    },
+
+    // ~~~~ Holds ~~~~
+    /// `signal` matched but `gate` vetoed it.
+    Vetoed {
+        signal: Box<ReasonCode>,
+        gate: Box<ReasonCode>,
+    },
+    /// Every rule that matched was vetoed, in evaluation order.
+    AllVetoed(Vec<ReasonCode>),
+    NoStrategyMatched,
+    InsufficientAtrData {
+        period: usize,
+    },
+    InsufficientRsiData {
+        period: usize,
+    },
+    /// ATR and its floor as fractions of the price.
+    VolatilityTooLow {
+        period: usize,
+        atr: f64,
+        floor: f64,
+    },
+    StaleData {
+        gap_minutes: i64,
+        max_gap_minutes: i64,
+    },
+}
+
+impl ReasonCode {
+    pub fn vetoed(signal: ReasonCode, gate: ReasonCode) -> Self {
+        Self::Vetoed {
+            signal: Box::new(signal),
+            gate: Box::new(gate),
+        }
+    }
+}
+
+/// `code` as text in `locale`.
+pub fn render(code: &ReasonCode, locale: Locale) -> String {
+    match locale {
+        Locale::English => render_english(code),
+    }
+}
+
+fn render_english(code: &ReasonCode) -> String {
+    let side = |side: &Side| match side {
+        Side::Long => "long",
+        Side::Short => "short",
+    };
+    match code {
+        ReasonCode::GoldenCross => "Golden Cross".into(),
+        ReasonCode::DeathCross => "Death Cross".into(),
+        ReasonCode::BreakoutAboveHigh { retested } => format!(
+            "Breakout above recent high{}",
+            if *retested { " held on retest" } else { "" }
+        ),
+        ReasonCode::BreakdownBelowLow { retested } => format!(
+            "Breakdown below recent low{}",
+            if *retested { " held on retest" } else { "" }
+        ),
+        ReasonCode::PullbackBounce => "Pullback to SMA short and bounce".into(),
+        ReasonCode::PullbackRejection => "Pullback up to SMA short and rejection".into(),
+        ReasonCode::MacdCrossAbove => "MACD crossed above signal line".into(),
+        ReasonCode::MacdCrossBelow => "MACD crossed below signal line".into(),
+        ReasonCode::BollingerReclaimLower => "Price closed back above lower Bollinger band".into(),
+        ReasonCode::BollingerReclaimUpper => "Price closed back below upper Bollinger band".into(),
+        ReasonCode::Uptrend => "Uptrend (SMA short > SMA long)".into(),
+        ReasonCode::Downtrend => "Downtrend (SMA short < SMA long)".into(),
+        ReasonCode::Custom(text) => text.clone(),
+
+        ReasonCode::RegimeTrendVeto(s) => format!(
+            "Trend filter vetoed {} (regime not trending {})",
+            side(s),
+            match s {
+                Side::Long => "up",
+                Side::Short => "down",
+            }
+        ),
+        ReasonCode::TrendVeto(s) => format!(
+            "Trend filter vetoed {} (not {})",
+            side(s),
+            match s {
+                Side::Long => "uptrend",
+                Side::Short => "downtrend",
+            }
+        ),
+        ReasonCode::PriceConfirmationVeto(s) => format!(
+            "Price confirmation vetoed {} (not {} both MAs)",
+            side(s),
+            match s {
+                Side::Long => "above",
+                Side::Short => "below",
+            }
+        ),
+        ReasonCode::RegimeVeto(s) => format!("Regime filter vetoed {}", side(s)),
+        ReasonCode::MaStackVeto(s) => format!(
+            "MA stack vetoed {} (SMAs not stacked {})",
+            side(s),
+            match s {
+                Side::Long => "up",
+                Side::Short => "down",
+            }
+        ),
+        ReasonCode::RoundNumberVeto { side: s, level } => {
+            format!(
+                "Round-number filter vetoed {} (price near {level})",
+                side(s)
+            )
+        }
+        ReasonCode::RsiVeto {
+            side: s,
+            rsi,
+            threshold,
+        } => match s {
+            Side::Long => format!("RSI filter vetoed long (RSI {rsi:.1} > overbought {threshold})"),
+            Side::Short => format!("RSI filter vetoed short (RSI {rsi:.1} < oversold {threshold})"),
+        },
+        ReasonCode::VolumeVeto {
+            side: s,
+            volume,
+            min_ratio,
+            average,
+        } => format!(
+            "Volume confirmation vetoed {} (volume {volume:.2} < {min_ratio}x average {average:.2})",
+            side(s)
+        ),
+        ReasonCode::TrendTooYoung { held, required } => {
+            format!("trend only {held} of {required} bars old")
+        }
+        ReasonCode::NoAtrForMove => "no ATR to measure the move against".into(),
+        ReasonCode::MoveTooSmall {
+            move_size,
+            mult,
+            atr,
+        } => format!("move {move_size:.4} < {mult}xATR ({:.4})", mult * atr),
+
+        ReasonCode::Vetoed { signal, gate } => {
+            format!("{}, but {}", render_english(signal), render_english(gate))
+        }
+        ReasonCode::AllVetoed(codes) => codes
+            .iter()
+            .map(render_english)
+            .collect::<Vec<_>>()
+            .join(" & "),
+        ReasonCode::NoStrategyMatched => "No strategy matched".into(),
+        ReasonCode::InsufficientAtrData { period } => {
+            format!("Insufficient data for ATR({period}) volatility filter")
+        }
+        ReasonCode::InsufficientRsiData { period } => {
+            format!("Insufficient data for RSI({period}) momentum filter")
+        }
+        ReasonCode::VolatilityTooLow { period, atr, floor } => format!(
+            "Volatility too low: ATR({period}) = {:.2}% < floor {:.2}%",
+            atr * 100.0,
+            floor * 100.0
+        ),
+        ReasonCode::StaleData {
+            gap_minutes,
+            max_gap_minutes,
+        } => format!(
+            "stale/gapped data: {gap_minutes}min between candles in the indicator window (max {max_gap_minutes}min)"
+        ),
+    }
+}
+
+/// Renders in the default locale.
+impl fmt::Display for ReasonCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&render(self, Locale::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_english_matches_legacy_strings() {
+        let cases = [
+            (ReasonCode::GoldenCross, "Golden Cross"),
+            (ReasonCode::DeathCross, "Death Cross"),
+            (
+                ReasonCode::BreakoutAboveHigh { retested: false },
+                "Breakout above recent high",
+            ),
+            (
+                ReasonCode::BreakoutAboveHigh { retested: true },
+                "Breakout above recent high held on retest",
+            ),
+            (
+                ReasonCode::BreakdownBelowLow { retested: false },
+                "Breakdown below recent low",
+            ),
+            (
+                ReasonCode::BreakdownBelowLow { retested: true },
+                "Breakdown below recent low held on retest",
+            ),
+            (
+                ReasonCode::PullbackBounce,
+                "Pullback to SMA short and bounce",
+            ),
+            (
+                ReasonCode::PullbackRejection,
+                "Pullback up to SMA short and rejection",
+            ),
+            (ReasonCode::MacdCrossAbove, "MACD crossed above signal line"),
+            (ReasonCode::MacdCrossBelow, "MACD crossed below signal line"),
+            (
+                ReasonCode::BollingerReclaimLower,
+                "Price closed back above lower Bollinger band",
+            ),
+            (
+                ReasonCode::BollingerReclaimUpper,
+                "Price closed back below upper Bollinger band",
+            ),
+            (ReasonCode::Uptrend, "Uptrend (SMA short > SMA long)"),
+            (ReasonCode::Downtrend, "Downtrend (SMA short < SMA long)"),
+            (ReasonCode::Custom("Custom rule".into()), "Custom rule"),
+            (
+                ReasonCode::RegimeTrendVeto(Side::Long),
+                "Trend filter vetoed long (regime not trending up)",
+            ),
+            (
+                ReasonCode::RegimeTrendVeto(Side::Short),
+                "Trend filter vetoed short (regime not trending down)",
+            ),
+            (
+                ReasonCode::TrendVeto(Side::Long),
+                "Trend filter vetoed long (not uptrend)",
+            ),
+            (
+                ReasonCode::TrendVeto(Side::Short),
+                "Trend filter vetoed short (not downtrend)",
+            ),
+            (
+                ReasonCode::PriceConfirmationVeto(Side::Long),
+                "Price confirmation vetoed long (not above both MAs)",
+            ),
+            (
+                ReasonCode::PriceConfirmationVeto(Side::Short),
+                "Price confirmation vetoed short (not below both MAs)",
+            ),
+            (
+                ReasonCode::RegimeVeto(Side::Long),
+                "Regime filter vetoed long",
+            ),
+            (
+                ReasonCode::RegimeVeto(Side::Short),
+                "Regime filter vetoed short",
+            ),
+            (
+                ReasonCode::MaStackVeto(Side::Long),
+                "MA stack vetoed long (SMAs not stacked up)",
+            ),
+            (
+                ReasonCode::MaStackVeto(Side::Short),
+                "MA stack vetoed short (SMAs not stacked down)",
+            ),
+            (
+                ReasonCode::RoundNumberVeto {
+                    side: Side::Long,
+                    level: 100.0,
+                },
+                "Round-number filter vetoed long (price near 100)",
+            ),
+            (
+                ReasonCode::RoundNumberVeto {
+                    side: Side::Short,
+                    level: 2.5,
+                },
+                "Round-number filter vetoed short (price near 2.5)",
+            ),
+            (
+                ReasonCode::RsiVeto {
+                    side: Side::Long,
+                    rsi: 74.26,
+                    threshold: 70.0,
+                },
+                "RSI filter vetoed long (RSI 74.3 > overbought 70)",
+            ),
+            (
+                ReasonCode::RsiVeto {
+                    side: Side::Short,
+                    rsi: 25.0,
+                    threshold: 30.0,
+                },
+                "RSI filter vetoed short (RSI 25.0 < oversold 30)",
+            ),
+            (
+                ReasonCode::VolumeVeto {
+                    side: Side::Short,
+                    volume: 12.0,
+                    min_ratio: 1.5,
+                    average: 10.0,
+                },
+                "Volume confirmation vetoed short (volume 12.00 < 1.5x average 10.00)",
+            ),
+            (
+                ReasonCode::TrendTooYoung {
+                    held: 2,
+                    required: 5,
+                },
+                "trend only 2 of 5 bars old",
+            ),
+            (
+                ReasonCode::NoAtrForMove,
+                "no ATR to measure the move against",
+            ),
+            (
+                ReasonCode::MoveTooSmall {
+                    move_size: 0.5,
+                    mult: 1.5,
+                    atr: 1.0,
+                },
+                "move 0.5000 < 1.5xATR (1.5000)",
+            ),
+            (
+                ReasonCode::vetoed(ReasonCode::GoldenCross, ReasonCode::RegimeVeto(Side::Long)),
+                "Golden Cross, but Regime filter vetoed long",
+            ),
+            (
+                ReasonCode::AllVetoed(vec![
+                    ReasonCode::vetoed(
+                        ReasonCode::PullbackBounce,
+                        ReasonCode::TrendVeto(Side::Long),
+                    ),
+                    ReasonCode::vetoed(ReasonCode::Uptrend, ReasonCode::TrendVeto(Side::Long)),
+                ]),
+                "Pullback to SMA short and bounce, but Trend filter vetoed long (not uptrend) & \
+                 Uptrend (SMA short > SMA long), but Trend filter vetoed long (not uptrend)",
+            ),
+            (ReasonCode::NoStrategyMatched, "No strategy matched"),
+            (
+                ReasonCode::InsufficientAtrData { period: 14 },
+                "Insufficient data for ATR(14) volatility filter",
+            ),
+            (
+                ReasonCode::InsufficientRsiData { period: 14 },
+                "Insufficient data for RSI(14) momentum filter",
+            ),
+            (
+                ReasonCode::VolatilityTooLow {
+                    period: 14,
+                    atr: 0.00123,
+                    floor: 0.005,
+                },
+                "Volatility too low: ATR(14) = 0.12% < floor 0.50%",
+            ),
+            (
+                ReasonCode::StaleData {
+                    gap_minutes: 180,
+                    max_gap_minutes: 120,
+                },
+                "stale/gapped data: 180min between candles in the indicator window (max 120min)",
+            ),
+        ];
+
+        for (code, expected) in cases {
+            assert_eq!(render(&code, Locale::English), expected, "{code:?}");
+            assert_eq!(code.to_string(), expected);
+        }
+    }
+}
@@ -1,22 +1,119 @@
-use crate::data::Sample;
+use std::cmp::Ordering;
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::data::{DataError, Sample, resample_to_n_hours, validate_dataset};
+use crate::indicators::atr::{DEFAULT_ATR_PERIOD, true_range_atr};
 use crate::indicators::sma::SmaConfig;
-use crate::indicators::{AtrFilter, Regime, RegimeFilter, Smas};
+use crate::indicators::{
+    AtrFilter, MaStack, Regime, RegimeFilter, RsiFilter, Smas, bollinger, cmp_with_epsilon,
+    compute_smas, ma_stack, macd_series, simple_moving_average,
+};
 use crate::patterns::{
-    is_breakdown_below_recent_low, is_breakout_above_recent_high,
-    is_pullback_to_sma_short_and_bounce, is_pullback_to_sma_short_and_reject_down,
+    breakdown_retest_held, breakout_retest_held, is_breakdown_below_recent_low,
+    is_breakout_above_recent_high, is_pullback_to_sma_short_and_bounce,
+    is_pullback_to_sma_short_and_reject_down, recent_range,
 };
+use crate::reason::{ReasonCode, Side};
+use crate::source::PriceSource;
+use crate::stats::spearman_correlation;
+use crate::units::Pct;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct StrategyConfig {
     pub breakouts: Option<BreakoutConfig>,
     pub pullbacks: Option<PullbackConfig>,
     pub enable_crossovers: bool,
     pub enable_bias_only: bool,
+    pub enable_macd: Option<MacdConfig>,
+    pub bollinger: Option<BollingerConfig>,
+    /// Priority of the enabled rules, first firing one wins. Empty for the standard
+    /// order; enabled rules left out run after the listed ones, in standard order.
+    pub rule_order: Vec<RuleKind>,
     pub sma_config: SmaConfig,
     pub filters: FilterConfig,
 }
 
 impl StrategyConfig {
+    /// Reject configurations that can't produce meaningful signals.
+    pub fn validate(&self) -> Result<(), String> {
+        self.sma_config.validate()?;
+        if let Some(macd) = self.enable_macd {
+            macd.validate()?;
+        }
+        if let Some(bollinger) = self.bollinger {
+            bollinger.validate()?;
+        }
+        if let Some(volume) = self.filters.volume {
+            volume.validate()?;
+        }
+        if self.filters.require_ma_stack && self.sma_config.stack_windows.len() < 2 {
+            return Err("require_ma_stack needs at least two stack_windows".into());
+        }
+        Ok(())
+    }
+
+    /// `rule_order` without duplicates, completed with the standard order.
+    pub fn effective_rule_order(&self) -> Vec<RuleKind> {
+        let mut order: Vec<RuleKind> = Vec::new();
+        for &kind in self.rule_order.iter().chain(&RuleKind::STANDARD_ORDER) {
+            if !order.contains(&kind) {
+                order.push(kind);
+            }
+        }
+        order
+    }
+
+    /// Prices, ending with the current one, that `analyze` looks at; older history
+    /// doesn't change its decision. None when something depends on the whole series
+    /// (the EMAs behind a smoothed regime slope or MACD, Wilder's smoothing in the RSI
+    /// filter).
+    pub fn required_history(&self) -> Option<usize> {
+        if self.enable_macd.is_some() || self.filters.rsi.is_some() {
+            return None;
+        }
+        let sma = &self.sma_config;
+        let atr_period = self
+            .filters
+            .atr
+            .map(|a| a.period())
+            .unwrap_or(DEFAULT_ATR_PERIOD);
+        let regime = match self.filters.effective_regime() {
+            Some(regime) if regime.smooth_slope => return None,
+            Some(regime) => regime.required_history(),
+            None => 0,
+        };
+        let breakout = self
+            .breakouts
+            .map(|b| {
+                let retest = b.require_retest.map_or(0, |r| r.lookahead);
+                (b.breakout_lookback + retest + 1).max(sma.long_window + b.min_trend_bars)
+            })
+            .unwrap_or(0);
+
+        Some(
+            [
+                // current and previous SMAs
+                sma.long_window + 1,
+                atr_period + 1,
+                regime,
+                breakout,
+                sma.stack_windows.iter().copied().max().unwrap_or(0),
+                // pullback patterns look at the last three closes
+                3,
+                // current and previous bands
+                self.bollinger.map_or(0, |b| b.period + 1),
+                // the checked volume and the ones it's averaged against
+                self.filters.volume.map_or(0, |v| v.lookback + 1),
+            ]
+            .into_iter()
+            .max()
+            .unwrap_or(0),
+        )
+    }
+
     pub fn describe_config(&self) -> String {
         let mut parts = Vec::new();
         parts.push(format!(
@@ -24,39 +121,126 @@ impl StrategyConfig {
             self.sma_config.short_window, self.sma_config.long_window,
         ));
         if let Some(b) = &self.breakouts {
-            parts.push(format!("breakout(lookback={})", b.breakout_lookback));
+            let mut breakout = format!("breakout(lookback={}", b.breakout_lookback);
+            if b.min_trend_bars > 0 {
+                breakout.push_str(&format!(", min_trend_bars={}", b.min_trend_bars));
+            }
+            if let Some(retest) = b.require_retest {
+                breakout.push_str(&format!(
+                    ", retest={}/{}",
+                    retest.lookahead,
+                    Pct(retest.tolerance_pct)
+                ));
+            }
+            if b.breakout_trigger == BreakoutTrigger::High {
+                breakout.push_str(", trigger=high");
+            }
+            parts.push(breakout + ")");
         }
         if let Some(p) = self.pullbacks {
+            let (bounce, rejection) = match p.tolerance_mode {
+                PullbackToleranceMode::Percent => (
+                    Pct(p.bounce_tolerance_pct).to_string(),
+                    Pct(p.reject_tolerance_pct).to_string(),
+                ),
+                PullbackToleranceMode::Atr => (
+                    format!("{}xATR", p.bounce_tolerance_pct),
+                    format!("{}xATR", p.reject_tolerance_pct),
+                ),
+            };
+            parts.push(format!("pullback(bounce={bounce}, rejection={rejection})"));
+        }
+        if self.sma_config.min_separation_pct > 0.0 {
             parts.push(format!(
-                "pullback(bounce={:.3},rejection{:.3})",
-                p.bounce_tolerance_pct, p.reject_tolerance_pct
+                "min_sma_separation={}",
+                Pct(self.sma_config.min_separation_pct)
             ));
         }
         if self.enable_crossovers {
             parts.push("crossovers".to_string());
         }
+        if let Some(macd) = self.enable_macd {
+            parts.push(format!("macd({}/{}/{})", macd.fast, macd.slow, macd.signal));
+        }
+        if let Some(b) = self.bollinger {
+            parts.push(format!(
+                "bollinger(period={}, num_std={})",
+                b.period, b.num_std
+            ));
+        }
         if self.enable_bias_only {
             parts.push("bias_only".to_string());
         }
+        if !self.rule_order.is_empty() {
+            let order: Vec<&str> = self
+                .effective_rule_order()
+                .into_iter()
+                .filter(|&kind| self.rule_enabled(kind))
+                .map(RuleKind::as_str)
+                .collect();
+            parts.push(format!("order({})", order.join(">")));
+        }
         if self.filters.require_price_confirmation {
             parts.push("require_price_confirmation".to_string());
         }
-        if self.filters.require_trend_filter {
+        if self.filters.trend_from_regime() {
+            parts.push("require_trend_filter(regime)".to_string());
+        } else if self.filters.require_trend_filter {
             parts.push("require_trend_filter".to_string());
         }
+        if self.filters.require_ma_stack {
+            let windows: Vec<String> = self
+                .sma_config
+                .stack_windows
+                .iter()
+                .map(|w| w.to_string())
+                .collect();
+            parts.push(format!("ma_stack({})", windows.join(">")));
+        }
         if let Some(atr) = self.filters.atr {
-            let atr_description = format!("atr(floor={}, period={})", atr.floor(), atr.period());
+            let atr_description =
+                format!("atr(floor={}, period={})", Pct(atr.floor()), atr.period());
             parts.push(atr_description);
         }
+        if let Some(rsi) = self.filters.rsi {
+            parts.push(format!(
+                "rsi(period={}, overbought={}, oversold={})",
+                rsi.period, rsi.overbought, rsi.oversold
+            ));
+        }
+        if let Some(volume) = self.filters.volume {
+            parts.push(format!(
+                "volume(lookback={}, min_ratio={})",
+                volume.lookback, volume.min_ratio
+            ));
+        }
         if let Some(regime) = self.filters.regime {
-            let atr_description = format!(
-                "regime(long_window={}, slope_window={}, min_trend_strength={}, min_range={})",
+            let regime_description = format!(
+                "regime(long_window={}, slope_window={}, min_trend_strength={}, min_range={}{})",
                 regime.long_window,
                 regime.slope_window,
-                regime.min_trend_strength,
-                regime.min_range
+                Pct(regime.min_trend_strength),
+                Pct(regime.min_range),
+                if regime.smooth_slope {
+                    ", smoothed"
+                } else {
+                    ""
+                }
             );
-            parts.push(atr_description);
+            parts.push(regime_description);
+        }
+        if let Some(rn) = self.filters.round_number {
+            parts.push(format!(
+                "round_numbers(grid={}, band={})",
+                rn.grid,
+                Pct(rn.band_pct)
+            ));
+        }
+        if let Some(mult) = self.filters.min_signal_atr_mult {
+            parts.push(format!("min_signal={mult}xATR"));
+        }
+        if let Some(gap) = self.filters.max_candle_gap {
+            parts.push(format!("max_candle_gap={}min", gap.num_minutes()));
         }
 
         if parts.is_empty() {
@@ -70,6 +254,36 @@ impl StrategyConfig {
 #[derive(Clone, Copy, Debug)]
 pub struct BreakoutConfig {
     pub breakout_lookback: usize,
+    /// Only honor a breakout once SMA(short) has been on the breakout's side of
+    /// SMA(long) for at least this many bars (0 = no requirement).
+    pub min_trend_bars: usize,
+    /// Wait for price to retest the broken level and hold before acting on a breakout.
+    pub require_retest: Option<RetestConfig>,
+    /// What has to clear the recent range for a breakout to fire.
+    pub breakout_trigger: BreakoutTrigger,
+}
+
+/// What a breakout is measured on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakoutTrigger {
+    /// The close clears the highest (lowest) close of the lookback window.
+    #[default]
+    Close,
+    /// The candle's high (low) clears the highest high (lowest low) of the lookback
+    /// window. Same as `Close` on close-only input. A retest is still judged on
+    /// closes.
+    High,
+}
+
+/// Breakout confirmation on retest: after breaking the recent high (low), price has
+/// to come back within `tolerance_pct` of that level and then close back beyond it,
+/// within `lookahead` candles of the breakout.
+#[derive(Clone, Copy, Debug)]
+pub struct RetestConfig {
+    pub lookahead: usize,
+    /// e.g. 0.002 = within 0.2% of the broken level counts as a retest
+    pub tolerance_pct: f64,
 }
 
 /// e.g. 0.003 = 0.3% tolerance around SMA
@@ -77,21 +291,198 @@ pub struct BreakoutConfig {
 pub struct PullbackConfig {
     pub bounce_tolerance_pct: f64,
     pub reject_tolerance_pct: f64,
+    pub tolerance_mode: PullbackToleranceMode,
+}
+
+/// MACD-cross rule: BUY when the MACD line crosses above its signal line, SELL
+/// when it crosses below.
+#[derive(Clone, Copy, Debug)]
+pub struct MacdConfig {
+    pub fast: usize,
+    pub slow: usize,
+    pub signal: usize,
+}
+
+impl MacdConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fast == 0 || self.signal == 0 {
+            return Err("MACD periods must be at least 1".into());
+        }
+        if self.slow <= self.fast {
+            return Err(format!(
+                "MACD slow period ({}) must be greater than the fast one ({})",
+                self.slow, self.fast
+            ));
+        }
+        Ok(())
+    }
 }
 
+/// Bollinger mean-reversion rule: BUY when the price closes back above the lower
+/// band after closing below it, SELL when it closes back below the upper band.
 #[derive(Clone, Copy, Debug)]
+pub struct BollingerConfig {
+    pub period: usize,
+    /// Band width in population standard deviations, e.g. 2.0.
+    pub num_std: f64,
+}
+
+impl BollingerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.period == 0 {
+            return Err("Bollinger period must be at least 1".into());
+        }
+        if self.num_std.is_nan() || self.num_std <= 0.0 {
+            return Err(format!(
+                "Bollinger num_std must be positive, got {}",
+                self.num_std
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Unit of the pullback tolerances.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PullbackToleranceMode {
+    /// Fraction of SMA(short), e.g. 0.003 = 0.3%.
+    #[default]
+    Percent,
+    /// Multiple of the current ATR, e.g. 0.5 = half an ATR around SMA(short).
+    /// Uses the ATR filter's period if set, `DEFAULT_ATR_PERIOD` otherwise.
+    Atr,
+}
+
+/// What `require_trend_filter` takes the trend from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendSource {
+    /// SMA(short) vs SMA(long), with SMA(long) not sloping against the trade.
+    #[default]
+    SmaSlope,
+    /// The regime classification (the regime filter's settings, or the defaults
+    /// without one). Replaces the separate regime gate, so there's a single veto.
+    Regime,
+}
+
+/// Gates applied on top of the rules. The default turns every one of them off.
+#[derive(Clone, Copy, Debug, Default)]
 pub struct FilterConfig {
     pub require_trend_filter: bool,
+    pub trend_source: TrendSource,
     pub require_price_confirmation: bool,
+    /// Require the SMAs in `SmaConfig::stack_windows` to be stacked in trade direction.
+    pub require_ma_stack: bool,
     pub atr: Option<AtrFilter>,
+    /// Vetoes longs when overbought and shorts when oversold; holds until RSI has data.
+    pub rsi: Option<RsiFilter>,
+    /// Breakouts need above-average volume on the breakout candle.
+    pub volume: Option<VolumeFilter>,
     pub regime: Option<RegimeFilter>,
+    pub round_number: Option<RoundNumberFilter>,
+    /// Breakouts must clear the recent high/low, and crossovers open an SMA spread, of
+    /// at least this many ATRs.
+    pub min_signal_atr_mult: Option<f64>,
+    /// Hold when two consecutive candles behind the SMA/ATR windows are further apart
+    /// than this, since the indicators then span a hole in the data.
+    pub max_candle_gap: Option<Duration>,
+}
+
+impl FilterConfig {
+    /// True when the trend filter is backed by the regime classification.
+    pub fn trend_from_regime(&self) -> bool {
+        self.require_trend_filter && self.trend_source == TrendSource::Regime
+    }
+
+    /// Regime filter that gets evaluated: the configured one, or the default one
+    /// when only the regime-backed trend filter asks for it.
+    pub fn effective_regime(&self) -> Option<RegimeFilter> {
+        self.regime
+            .or_else(|| self.trend_from_regime().then(RegimeFilter::default))
+    }
+}
+
+/// No-trade zone around psychological round numbers: vetoes entries while the price
+/// is within `band_pct` of a multiple of `grid` (e.g. within 0.1% of every 1000).
+#[derive(Clone, Copy, Debug)]
+pub struct RoundNumberFilter {
+    pub grid: f64,
+    /// e.g. 0.001 = 0.1% of the round level
+    pub band_pct: f64,
+}
+
+/// Volume confirmation for breakouts: the latest candle's volume must be at least
+/// `min_ratio` times the average volume of the `lookback` candles before it.
+/// Does nothing when the samples have no volume (close-only input).
+#[derive(Clone, Copy, Debug)]
+pub struct VolumeFilter {
+    pub lookback: usize,
+    pub min_ratio: f64,
+}
+
+impl VolumeFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lookback == 0 {
+            return Err("volume lookback must be at least 1".into());
+        }
+        if !self.min_ratio.is_finite() || self.min_ratio < 0.0 {
+            return Err(format!(
+                "volume min_ratio must be a non-negative number, got {}",
+                self.min_ratio
+            ));
+        }
+        Ok(())
+    }
+
+    /// The last sample's volume and the average before it, when that volume is too
+    /// low to confirm a breakout. None as well when there aren't `lookback` + 1
+    /// volumes to compare.
+    pub fn low_volume(&self, samples: &[Sample]) -> Option<(f64, f64)> {
+        let (last, before) = samples.split_last()?;
+        let latest = last.volume?;
+        if self.lookback == 0 {
+            return None;
+        }
+        let window = before.get(before.len().checked_sub(self.lookback)?..)?;
+        let average = window.iter().map(|s| s.volume).sum::<Option<f64>>()? / self.lookback as f64;
+        (latest < self.min_ratio * average).then_some((latest, average))
+    }
+}
+
+impl RoundNumberFilter {
+    /// The round level `price` is too close to, if any.
+    pub fn nearby_level(&self, price: f64) -> Option<f64> {
+        if self.grid <= 0.0 {
+            return None;
+        }
+        let level = (price / self.grid).round() * self.grid;
+        (level > 0.0 && (price - level).abs() <= self.band_pct * level).then_some(level)
+    }
 }
 
+#[derive(Serialize)]
 pub struct AnalysisResult {
     pub last: Sample,
     pub smas: Smas,
     pub suggestion: String,
+    /// `reason_code` rendered in the default locale.
     pub reason: String,
+    pub reason_code: ReasonCode,
+    /// Strength of the rule behind a BUY/SELL; None for HOLD.
+    pub strength: Option<SignalStrength>,
+    /// Name of the rule behind a BUY/SELL (as in the decision trace); None for HOLD.
+    pub rule: Option<String>,
+    /// Per-rule outcomes, only collected when `analyze` is called with `trace = true`.
+    #[serde(skip)]
+    pub trace: Option<DecisionTrace>,
+}
+
+impl AnalysisResult {
+    /// HOLD because the ATR filter is still warming up, rather than for lack of a signal.
+    pub fn held_for_atr_data(&self) -> bool {
+        matches!(self.reason_code, ReasonCode::InsufficientAtrData { .. })
+    }
 }
 
 /// Advanced trading rule based on:
@@ -100,80 +491,516 @@ pub struct AnalysisResult {
 /// - Pullback to SMA(short) + bounce (uptrend)
 /// - Pullback to SMA(short) + rejection (downtrend)
 /// - Golden Cross / Death Cross detection (using previous + current SMAs)
+/// - MACD line crossing its signal line (if `enable_macd` is set)
 /// - Trend filter using SMA(long) slope
 /// - Price confirmation (price relative to SMA(short) & SMA(long))
 ///
-/// Returns (short_suggestion, optional_detailed_reason)
+/// Fails with `DataError::Empty` when `hourly` or `prices` is empty.
+/// With `trace` set, every enabled rule is evaluated and its outcome recorded in
+/// `AnalysisResult::trace`; the suggestion is still the first rule that fired.
 pub fn analyze(
     hourly: &[Sample],
     prices: &[f64],
     smas: Smas,
-    strategy: StrategyConfig,
-) -> AnalysisResult {
-    let last = hourly.last().expect("hourly is non-empty").to_owned();
-    let (suggestion, reason) = suggest_action(prices, smas, strategy);
-    AnalysisResult {
+    strategy: &StrategyConfig,
+    trace: bool,
+) -> Result<AnalysisResult, DataError> {
+    analyze_with_rules(
+        hourly,
+        prices,
+        smas,
+        strategy,
+        &default_rules(strategy),
+        trace,
+    )
+}
+
+/// `analyze` with an explicit rule list, evaluated in order instead of the
+/// strategy's `default_rules`. The strategy's filters still apply.
+pub fn analyze_with_rules(
+    hourly: &[Sample],
+    prices: &[f64],
+    smas: Smas,
+    strategy: &StrategyConfig,
+    rules: &[Box<dyn Rule>],
+    trace: bool,
+) -> Result<AnalysisResult, DataError> {
+    let (Some(last), false) = (hourly.last().cloned(), prices.is_empty()) else {
+        return Err(DataError::Empty);
+    };
+    // Cheap sanity check; `try_analyze` checks the whole series
+    debug_assert!(
+        prices.len() <= hourly.len() && prices.last() == Some(&last.price),
+        "prices must end with the last sample's price"
+    );
+    debug_assert!(
+        hourly.len() < 2 || hourly[hourly.len() - 2].ts < last.ts,
+        "samples must be in increasing time order"
+    );
+    if let Some(max_gap) = strategy.filters.max_candle_gap
+        && let Some(gap) = largest_gap(hourly, indicator_window(strategy))
+        && gap > max_gap
+    {
+        let reason_code = ReasonCode::StaleData {
+            gap_minutes: gap.num_minutes(),
+            max_gap_minutes: max_gap.num_minutes(),
+        };
+        return Ok(AnalysisResult {
+            last,
+            smas,
+            suggestion: "HOLD".into(),
+            reason: reason_code.to_string(),
+            reason_code,
+            strength: None,
+            rule: None,
+            trace: trace.then(DecisionTrace::default),
+        });
+    }
+
+    let Suggestion {
+        suggestion,
+        reason_code,
+        strength,
+        rule,
+        trace,
+    } = suggest_action_with_rules(hourly, prices, smas, strategy, rules, trace);
+    Ok(AnalysisResult {
         last,
         smas,
         suggestion,
-        reason,
+        reason: reason_code.to_string(),
+        reason_code,
+        strength,
+        rule,
+        trace,
+    })
+}
+
+/// Candles the SMA and ATR values of the last candle are computed from, including the
+/// previous candle's SMAs.
+fn indicator_window(strategy: &StrategyConfig) -> usize {
+    let sma = strategy.sma_config.long_window + 1;
+    let atr = strategy.filters.atr.map_or(0, |atr| atr.period() + 1);
+    sma.max(atr)
+}
+
+/// Longest time between consecutive candles among the last `window` samples.
+fn largest_gap(samples: &[Sample], window: usize) -> Option<Duration> {
+    samples[samples.len().saturating_sub(window)..]
+        .windows(2)
+        .map(|w| w[1].ts - w[0].ts)
+        .max()
+}
+
+/// `analyze`, after checking with `check_series` that the input is usable.
+pub fn try_analyze(
+    hourly: &[Sample],
+    prices: &[f64],
+    smas: Smas,
+    strategy: &StrategyConfig,
+    trace: bool,
+) -> Result<AnalysisResult> {
+    check_series(hourly, prices)?;
+    Ok(analyze(hourly, prices, smas, strategy, trace)?)
+}
+
+/// Check that `samples` are in strictly increasing time order and that `prices` are
+/// their prices (or the most recent of them), as `analyze` and the patterns assume.
+pub fn check_series(samples: &[Sample], prices: &[f64]) -> Result<()> {
+    validate_dataset(samples)?;
+    if prices.len() > samples.len() {
+        bail!("{} prices for only {} samples", prices.len(), samples.len());
+    }
+    let offset = samples.len() - prices.len();
+    if let Some(i) = prices
+        .iter()
+        .zip(&samples[offset..])
+        .position(|(price, sample)| *price != sample.price)
+    {
+        bail!(
+            "price {} at index {} doesn't match sample price {}",
+            prices[i],
+            i,
+            samples[offset + i].price
+        );
+    }
+    Ok(())
+}
+
+/// Drain `source` and analyze its final sample.
+/// Samples are pulled one at a time and, when the strategy's lookback is bounded
+/// (`StrategyConfig::required_history`), only the samples it looks back over are kept,
+/// so the source can be longer than fits in memory.
+/// Returns None when the source doesn't hold enough data for the strategy's SMAs.
+pub fn analyze_source<S: PriceSource + ?Sized>(
+    source: &mut S,
+    strategy: &StrategyConfig,
+) -> Result<Option<AnalysisResult>> {
+    let keep = strategy.required_history();
+    let mut samples: Vec<Sample> = Vec::new();
+    let mut index = 0;
+    while let Some(sample) = source.next_sample() {
+        let sample = sample?;
+        if let Some(last) = samples.last()
+            && sample.ts <= last.ts
+        {
+            return Err(DataError::OutOfOrder { index }.into());
+        }
+        samples.push(sample);
+        index += 1;
+        if let Some(keep) = keep
+            && samples.len() >= 2 * keep.max(1)
+        {
+            samples.drain(..samples.len() - keep);
+        }
+    }
+    if samples.is_empty() {
+        return Err(DataError::Empty.into());
+    }
+    let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+    let Some(smas) = compute_smas(&prices, &strategy.sma_config) else {
+        return Ok(None);
+    };
+    try_analyze(&samples, &prices, smas, strategy, false).map(Some)
+}
+
+/// The strategy's action on every candle past the SMA warmup, replaying the data
+/// candle by candle as a backtest would.
+pub fn collect_signals(
+    samples: &[Sample],
+    strategy: &StrategyConfig,
+) -> Vec<(DateTime<Utc>, Action)> {
+    actions_by_index(samples, strategy)
+        .into_iter()
+        .map(|(i, action)| (samples[i].ts, action))
+        .collect()
+}
+
+/// `collect_signals`, keyed by sample index.
+fn actions_by_index(samples: &[Sample], strategy: &StrategyConfig) -> Vec<(usize, Action)> {
+    let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+    (0..samples.len())
+        .filter_map(|i| {
+            let prices = &prices[..=i];
+            let smas = compute_smas(prices, &strategy.sma_config)?;
+            let analysis = analyze(&samples[..=i], prices, smas, strategy, false).ok()?;
+            Some((i, Action::from_suggestion(&analysis.suggestion)))
+        })
+        .collect()
+}
+
+/// Information coefficient: Spearman rank correlation between each emitted signal
+/// (BUY = +1, SELL = -1) and the return over the following `horizon` candles.
+/// HOLDs and signals without `horizon` candles after them are left out.
+/// Returns None when there's nothing to correlate (e.g. only one kind of signal).
+pub fn information_coefficient(
+    samples: &[Sample],
+    strategy: &StrategyConfig,
+    horizon: usize,
+) -> Option<f64> {
+    if horizon == 0 {
+        return None;
+    }
+
+    let (directions, forward_returns): (Vec<f64>, Vec<f64>) = actions_by_index(samples, strategy)
+        .into_iter()
+        .filter_map(|(i, action)| {
+            let direction = match action {
+                Action::Buy => 1.0,
+                Action::Sell => -1.0,
+                Action::Hold => return None,
+            };
+            let entry = samples[i].price;
+            let exit = samples.get(i + horizon)?.price;
+            (entry > 0.0).then(|| (direction, exit / entry - 1.0))
+        })
+        .unzip();
+
+    spearman_correlation(&directions, &forward_returns)
+}
+
+/// The strategy's current action on several timeframes of the same samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeframeAgreement {
+    /// (candle size in hours, action on its last candle), in the order asked for.
+    /// HOLD where the timeframe doesn't have enough candles for the SMAs.
+    pub actions: Vec<(i64, Action)>,
+    /// Timeframes on the majority side (BUY or SELL); 0 if none has a direction.
+    pub agreement: usize,
+    /// The majority side; None without one (no directions, or a BUY/SELL tie).
+    pub direction: Option<Action>,
+}
+
+/// Resample `samples` to each of `hours` (e.g. [1, 4, 24]), analyze the last candle
+/// of each and count how many agree on a direction. A rule can then ask for, say,
+/// 2 of 3 timeframes agreeing.
+pub fn timeframe_agreement(
+    samples: &[Sample],
+    strategy: &StrategyConfig,
+    hours: &[i64],
+) -> Result<TimeframeAgreement> {
+    validate_dataset(samples)?;
+    let mut actions = Vec::with_capacity(hours.len());
+    for &h in hours {
+        let candles = resample_to_n_hours(samples, h);
+        let prices: Vec<f64> = candles.iter().map(|s| s.price).collect();
+        let action = match compute_smas(&prices, &strategy.sma_config) {
+            Some(smas) => {
+                let result = try_analyze(&candles, &prices, smas, strategy, false)?;
+                Action::from_suggestion(&result.suggestion)
+            }
+            None => Action::Hold,
+        };
+        actions.push((h, action));
     }
+
+    let count = |side: Action| actions.iter().filter(|(_, a)| *a == side).count();
+    let (buys, sells) = (count(Action::Buy), count(Action::Sell));
+    let direction = match buys.cmp(&sells) {
+        Ordering::Greater => Some(Action::Buy),
+        Ordering::Less => Some(Action::Sell),
+        Ordering::Equal => None,
+    };
+
+    Ok(TimeframeAgreement {
+        actions,
+        agreement: buys.max(sells),
+        direction,
+    })
+}
+
+/// Candles where `config_a` and `config_b` suggest different actions, as
+/// (timestamp, action under a, action under b). Only candles past both warmups count.
+pub fn diff_signals(
+    samples: &[Sample],
+    config_a: &StrategyConfig,
+    config_b: &StrategyConfig,
+) -> Vec<(DateTime<Utc>, Action, Action)> {
+    let a = collect_signals(samples, config_a);
+    let b = collect_signals(samples, config_b);
+
+    // Both lists end at the last sample; they only differ in how much warmup they skip
+    let mut diffs: Vec<_> = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .filter(|((_, action_a), (_, action_b))| action_a != action_b)
+        .map(|(&(ts, action_a), &(_, action_b))| (ts, action_a, action_b))
+        .collect();
+    diffs.reverse();
+    diffs
 }
 
-struct AnalysisCtx {
+/// What the rules see of the current candle: SMA trend state, ATR and the gate vetoes.
+pub struct AnalysisCtx<'a> {
+    /// The candles being analyzed, ending with the current one.
+    pub samples: &'a [Sample],
     pub smas: Smas,
-    pub gate_long: Option<String>,
-    pub gate_short: Option<String>,
+    /// SMA(short) vs SMA(long) now and on the previous candle, within
+    /// `SmaConfig::trend_epsilon`.
+    pub short_vs_long: Ordering,
+    pub prev_short_vs_long: Ordering,
+    /// Bars (including this one) that `short_vs_long` has held, counted up to the
+    /// breakout `min_trend_bars` (0 when that isn't configured).
+    pub trend_bars: usize,
+    /// ATR in price units (None if not enough data).
+    pub atr: Option<f64>,
+    pub gate_long: Option<ReasonCode>,
+    pub gate_short: Option<ReasonCode>,
+    pub min_signal_atr_mult: Option<f64>,
+    /// `VolumeFilter` vetoes of a breakout (long) or breakdown (short) on this candle.
+    pub volume_gate_long: Option<ReasonCode>,
+    pub volume_gate_short: Option<ReasonCode>,
 }
 
-impl AnalysisCtx {
-    pub fn new(prices: &[f64], smas: Smas, strategy: &StrategyConfig) -> Self {
+impl<'a> AnalysisCtx<'a> {
+    /// `samples` feed the ATR (true range, using their high/low when present) and the
+    /// volume filter; `prices` are their closes, or the most recent of them.
+    pub fn new(
+        samples: &'a [Sample],
+        prices: &[f64],
+        smas: Smas,
+        strategy: &StrategyConfig,
+    ) -> Self {
         let last_price = *prices.last().expect("prices non-empty");
+        let eps = strategy.sma_config.trend_epsilon();
+        let short_vs_long = cmp_with_epsilon(smas.sma_short, smas.sma_long, eps);
+        let prev_short_vs_long = cmp_with_epsilon(smas.prev_sma_short, smas.prev_sma_long, eps);
+
+        let regime = strategy
+            .filters
+            .effective_regime()
+            .map(|rf| rf.detect_regime(prices));
+        let trend_from_regime = strategy.filters.trend_from_regime();
+
+        let (uptrend, downtrend) = if trend_from_regime {
+            (
+                regime == Some(Regime::TrendingUp),
+                regime == Some(Regime::TrendingDown),
+            )
+        } else {
+            (
+                short_vs_long.is_gt() && smas.sma_long >= smas.prev_sma_long,
+                short_vs_long.is_lt() && smas.sma_long <= smas.prev_sma_long,
+            )
+        };
 
-        let uptrend = smas.sma_short > smas.sma_long && smas.sma_long >= smas.prev_sma_long;
-        let downtrend = smas.sma_short < smas.sma_long && smas.sma_long <= smas.prev_sma_long;
+        let atr_period = strategy
+            .filters
+            .atr
+            .map(|a| a.period())
+            .unwrap_or(DEFAULT_ATR_PERIOD);
+        let atr = true_range_atr(samples, atr_period);
 
         let price_above_both = last_price > smas.sma_short && last_price > smas.sma_long;
         let price_below_both = last_price < smas.sma_short && last_price < smas.sma_long;
 
-        let (regime_up, regime_down) = strategy
+        // A regime-backed trend filter already vetoed on the regime
+        let (regime_up, regime_down) = match regime {
+            Some(r) if !trend_from_regime => (r == Regime::TrendingUp, r == Regime::TrendingDown),
+            _ => (true, true),
+        };
+
+        let stack = if strategy.filters.require_ma_stack {
+            ma_stack(prices, &strategy.sma_config.stack_windows)
+        } else {
+            None
+        };
+        let stack_up = !strategy.filters.require_ma_stack || stack == Some(MaStack::Bullish);
+        let stack_down = !strategy.filters.require_ma_stack || stack == Some(MaStack::Bearish);
+
+        let round_level = strategy
             .filters
-            .regime
-            .map(|rf| {
-                let r = rf.detect_regime(prices);
-                (
-                    matches!(r, Regime::TrendingUp),
-                    matches!(r, Regime::TrendingDown),
-                )
-            })
-            .unwrap_or((true, true));
+            .round_number
+            .and_then(|rn| rn.nearby_level(last_price));
+
+        let rsi = strategy
+            .filters
+            .rsi
+            .and_then(|filter| Some((filter, filter.rsi(prices)?)));
 
-        let gate_long = if strategy.filters.require_trend_filter && !uptrend {
-            Some("Trend filter vetoed long (not uptrend)".into())
+        let gate_long = if trend_from_regime && !uptrend {
+            Some(ReasonCode::RegimeTrendVeto(Side::Long))
+        } else if strategy.filters.require_trend_filter && !uptrend {
+            Some(ReasonCode::TrendVeto(Side::Long))
         } else if strategy.filters.require_price_confirmation && !price_above_both {
-            Some("Price confirmation vetoed long (not above both MAs)".into())
+            Some(ReasonCode::PriceConfirmationVeto(Side::Long))
         } else if !regime_up {
-            Some("Regime filter vetoed long".into())
+            Some(ReasonCode::RegimeVeto(Side::Long))
+        } else if !stack_up {
+            Some(ReasonCode::MaStackVeto(Side::Long))
+        } else if let Some((filter, value)) = rsi
+            && value > filter.overbought
+        {
+            Some(ReasonCode::RsiVeto {
+                side: Side::Long,
+                rsi: value,
+                threshold: filter.overbought,
+            })
         } else {
-            None
+            round_level.map(|level| ReasonCode::RoundNumberVeto {
+                side: Side::Long,
+                level,
+            })
         };
 
-        let gate_short = if strategy.filters.require_trend_filter && !downtrend {
-            Some("Trend filter vetoed short (not downtrend)".into())
+        let gate_short = if trend_from_regime && !downtrend {
+            Some(ReasonCode::RegimeTrendVeto(Side::Short))
+        } else if strategy.filters.require_trend_filter && !downtrend {
+            Some(ReasonCode::TrendVeto(Side::Short))
         } else if strategy.filters.require_price_confirmation && !price_below_both {
-            Some("Price confirmation vetoed short (not below both MAs)".into())
+            Some(ReasonCode::PriceConfirmationVeto(Side::Short))
         } else if !regime_down {
-            Some("Regime filter vetoed short".into())
+            Some(ReasonCode::RegimeVeto(Side::Short))
+        } else if !stack_down {
+            Some(ReasonCode::MaStackVeto(Side::Short))
+        } else if let Some((filter, value)) = rsi
+            && value < filter.oversold
+        {
+            Some(ReasonCode::RsiVeto {
+                side: Side::Short,
+                rsi: value,
+                threshold: filter.oversold,
+            })
         } else {
-            None
+            round_level.map(|level| ReasonCode::RoundNumberVeto {
+                side: Side::Short,
+                level,
+            })
+        };
+
+        let low_volume = strategy
+            .filters
+            .volume
+            .and_then(|filter| Some((filter.min_ratio, filter.low_volume(samples)?)));
+        let volume_gate = |side| {
+            low_volume.map(|(min_ratio, (volume, average))| ReasonCode::VolumeVeto {
+                side,
+                volume,
+                min_ratio,
+                average,
+            })
         };
 
+        let trend_bars = strategy
+            .breakouts
+            .map(|b| b.min_trend_bars)
+            .filter(|&n| n > 0)
+            .map(|n| sma_relation_bars(prices, &strategy.sma_config, short_vs_long, n))
+            .unwrap_or(0);
+
         Self {
+            samples,
             smas,
+            short_vs_long,
+            prev_short_vs_long,
+            trend_bars,
+            atr,
             gate_long,
             gate_short,
+            min_signal_atr_mult: strategy.filters.min_signal_atr_mult,
+            volume_gate_long: volume_gate(Side::Long),
+            volume_gate_short: volume_gate(Side::Short),
         }
     }
+
+    /// Veto for a signal whose triggering move is under `min_signal_atr_mult` ATRs.
+    fn small_move(&self, move_size: f64) -> Option<ReasonCode> {
+        let mult = self.min_signal_atr_mult?;
+        let Some(atr) = self.atr else {
+            return Some(ReasonCode::NoAtrForMove);
+        };
+        (move_size < mult * atr).then_some(ReasonCode::MoveTooSmall {
+            move_size,
+            mult,
+            atr,
+        })
+    }
+}
+
+/// How many bars, ending with the last price, SMA(short) vs SMA(long) compared as
+/// `relation`. Stops counting at `max_bars`.
+fn sma_relation_bars(
+    prices: &[f64],
+    sma_config: &SmaConfig,
+    relation: Ordering,
+    max_bars: usize,
+) -> usize {
+    let mut bars = 0;
+    while bars < max_bars && bars < prices.len() {
+        let prefix = &prices[..prices.len() - bars];
+        let (Some(short), Some(long)) = (
+            simple_moving_average(prefix, sma_config.short_window),
+            simple_moving_average(prefix, sma_config.long_window),
+        ) else {
+            break;
+        };
+        if cmp_with_epsilon(short, long, sma_config.trend_epsilon()) != relation {
+            break;
+        }
+        bars += 1;
+    }
+    bars
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -183,6 +1010,17 @@ pub enum Action {
     Hold,
 }
 
+impl Action {
+    /// Parse a suggestion string ("BUY" / "SELL"); anything else is a hold.
+    pub fn from_suggestion(suggestion: &str) -> Self {
+        match suggestion {
+            "BUY" => Self::Buy,
+            "SELL" => Self::Sell,
+            _ => Self::Hold,
+        }
+    }
+}
+
 impl std::fmt::Display for Action {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let out = format!("{:?}", self).to_uppercase();
@@ -190,127 +1028,518 @@ impl std::fmt::Display for Action {
     }
 }
 
-#[derive(Debug, Clone)]
-struct Decision {
-    action: Action,
-    reason: String,
-    rule: String,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub action: Action,
+    pub reason: ReasonCode,
+    pub rule: String,
+    pub strength: SignalStrength,
+}
+
+/// How much weight a fired rule carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SignalStrength {
+    /// A discrete event: breakout, pullback bounce/rejection or SMA crossover.
+    Strong,
+    /// A standing condition (bias only), repeated on every candle it holds.
+    Weak,
 }
 
-#[derive(Debug, Clone)]
-enum RuleOutcome {
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleOutcome {
     NoMatch,
-    Blocked { reason: String },
+    /// The rule matched but a gate vetoed it. `reason` is the `ReasonCode::Vetoed`
+    /// of the match and `gate`.
+    Blocked {
+        reason: ReasonCode,
+        gate: ReasonCode,
+    },
     Fired(Decision),
 }
 
-fn rule_crossovers(ctx: &AnalysisCtx) -> RuleOutcome {
-    let golden =
-        ctx.smas.prev_sma_short <= ctx.smas.prev_sma_long && ctx.smas.sma_short > ctx.smas.sma_long;
-    let death =
-        ctx.smas.prev_sma_short >= ctx.smas.prev_sma_long && ctx.smas.sma_short < ctx.smas.sma_long;
+/// Outcome of a single rule within a `DecisionTrace`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleTrace {
+    pub rule: String,
+    pub outcome: RuleOutcome,
+}
 
-    if golden {
-        if let Some(r) = &ctx.gate_long {
-            return RuleOutcome::Blocked {
-                reason: format!("Golden Cross, but {r}"),
-            };
-        }
-        return RuleOutcome::Fired(Decision {
-            action: Action::Buy,
-            rule: "Crossovers".into(),
-            reason: "Golden Cross".into(),
-        });
-    }
+/// Every rule evaluated for one candle, in evaluation order.
+/// Empty when the volatility filter held before any rule ran.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecisionTrace {
+    pub rules: Vec<RuleTrace>,
+}
 
-    if death {
-        if let Some(r) = &ctx.gate_short {
-            return RuleOutcome::Blocked {
-                reason: format!("Death Cross, but {r}"),
-            };
-        }
-        return RuleOutcome::Fired(Decision {
-            action: Action::Sell,
-            rule: "Crossovers".into(),
-            reason: "Death Cross".into(),
-        });
+/// A signal rule. `suggest_action` evaluates rules in order and acts on the first
+/// one that fires.
+pub trait Rule {
+    /// Name recorded in the decision trace.
+    fn name(&self) -> &str;
+    fn evaluate(&self, ctx: &AnalysisCtx, prices: &[f64]) -> RuleOutcome;
+}
+
+pub struct BreakoutRule(pub BreakoutConfig);
+
+impl Rule for BreakoutRule {
+    fn name(&self) -> &str {
+        "Breakouts"
     }
 
-    RuleOutcome::NoMatch
+    fn evaluate(&self, ctx: &AnalysisCtx, prices: &[f64]) -> RuleOutcome {
+        rule_breakouts(ctx, prices, self.0)
+    }
 }
 
-fn rule_breakouts(ctx: &AnalysisCtx, prices: &[f64], config: BreakoutConfig) -> RuleOutcome {
-    let rule = "Breakouts";
-    if is_breakout_above_recent_high(prices, config.breakout_lookback) {
-        let reason = "Breakout above recent high";
-        if let Some(r) = &ctx.gate_long {
-            return RuleOutcome::Blocked {
-                reason: format!("{}, but {r}", reason),
-            };
-        }
-        return RuleOutcome::Fired(Decision {
-            action: Action::Buy,
-            rule: rule.into(),
-            reason: reason.into(),
-        });
-    }
+pub struct PullbackRule(pub PullbackConfig);
 
-    if is_breakdown_below_recent_low(prices, config.breakout_lookback) {
-        let reason = "Breakdown below recent low";
-        if let Some(r) = &ctx.gate_short {
-            return RuleOutcome::Blocked {
-                reason: format!("{}, but {r}", reason),
-            };
-        }
-        return RuleOutcome::Fired(Decision {
-            action: Action::Sell,
-            rule: rule.into(),
-            reason: reason.into(),
-        });
+impl Rule for PullbackRule {
+    fn name(&self) -> &str {
+        "Pullbacks"
     }
 
-    RuleOutcome::NoMatch
+    fn evaluate(&self, ctx: &AnalysisCtx, prices: &[f64]) -> RuleOutcome {
+        rule_pullbacks(ctx, prices, self.0)
+    }
 }
 
-fn rule_pullbacks(
-    ctx: &AnalysisCtx,
-    prices: &[f64],
-    pullback_config: PullbackConfig,
-) -> RuleOutcome {
-    let rule = "Pullbacks";
-    if is_pullback_to_sma_short_and_bounce(
-        prices,
-        ctx.smas.sma_short,
-        pullback_config.bounce_tolerance_pct,
-    ) {
-        let reason = "Pullback to SMA short and bounce";
-        if let Some(r) = &ctx.gate_long {
-            return RuleOutcome::Blocked {
-                reason: format!("{}, but {r}", reason),
-            };
-        }
-        return RuleOutcome::Fired(Decision {
-            action: Action::Buy,
-            rule: rule.into(),
-            reason: reason.into(),
-        });
+pub struct CrossoverRule;
+
+impl Rule for CrossoverRule {
+    fn name(&self) -> &str {
+        "Crossovers"
     }
 
-    if is_pullback_to_sma_short_and_reject_down(
-        prices,
-        ctx.smas.sma_short,
-        pullback_config.reject_tolerance_pct,
-    ) {
-        let reason = "Pullback up to SMA short and rejection";
+    fn evaluate(&self, ctx: &AnalysisCtx, _prices: &[f64]) -> RuleOutcome {
+        rule_crossovers(ctx)
+    }
+}
+
+pub struct MacdRule(pub MacdConfig);
+
+impl Rule for MacdRule {
+    fn name(&self) -> &str {
+        "MACD"
+    }
+
+    fn evaluate(&self, ctx: &AnalysisCtx, prices: &[f64]) -> RuleOutcome {
+        rule_macd(ctx, prices, self.0)
+    }
+}
+
+pub struct BollingerRule(pub BollingerConfig);
+
+impl Rule for BollingerRule {
+    fn name(&self) -> &str {
+        "Bollinger"
+    }
+
+    fn evaluate(&self, ctx: &AnalysisCtx, prices: &[f64]) -> RuleOutcome {
+        rule_bollinger(ctx, prices, self.0)
+    }
+}
+
+pub struct BiasOnlyRule;
+
+impl Rule for BiasOnlyRule {
+    fn name(&self) -> &str {
+        "Bias only"
+    }
+
+    fn evaluate(&self, ctx: &AnalysisCtx, _prices: &[f64]) -> RuleOutcome {
+        rule_bias_only(ctx)
+    }
+}
+
+/// The built-in rules, for ordering them in `StrategyConfig::rule_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    Breakout,
+    Pullback,
+    Crossover,
+    Macd,
+    Bollinger,
+    BiasOnly,
+}
+
+impl RuleKind {
+    /// Priority used when `rule_order` is empty.
+    pub const STANDARD_ORDER: [RuleKind; 6] = [
+        RuleKind::Breakout,
+        RuleKind::Pullback,
+        RuleKind::Crossover,
+        RuleKind::Macd,
+        RuleKind::Bollinger,
+        RuleKind::BiasOnly,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RuleKind::Breakout => "breakout",
+            RuleKind::Pullback => "pullback",
+            RuleKind::Crossover => "crossover",
+            RuleKind::Macd => "macd",
+            RuleKind::Bollinger => "bollinger",
+            RuleKind::BiasOnly => "bias_only",
+        }
+    }
+}
+
+impl StrategyConfig {
+    fn rule_enabled(&self, kind: RuleKind) -> bool {
+        match kind {
+            RuleKind::Breakout => self.breakouts.is_some(),
+            RuleKind::Pullback => self.pullbacks.is_some(),
+            RuleKind::Crossover => self.enable_crossovers,
+            RuleKind::Macd => self.enable_macd.is_some(),
+            RuleKind::Bollinger => self.bollinger.is_some(),
+            RuleKind::BiasOnly => self.enable_bias_only,
+        }
+    }
+}
+
+/// The strategy's enabled rules in its `effective_rule_order`; by default
+/// breakouts, pullbacks, crossovers, MACD, Bollinger, bias only.
+pub fn default_rules(strategy: &StrategyConfig) -> Vec<Box<dyn Rule>> {
+    let mut rules: Vec<Box<dyn Rule>> = Vec::new();
+    for kind in strategy.effective_rule_order() {
+        match kind {
+            RuleKind::Breakout => {
+                if let Some(breakouts) = strategy.breakouts {
+                    rules.push(Box::new(BreakoutRule(breakouts)));
+                }
+            }
+            RuleKind::Pullback => {
+                if let Some(pullbacks) = strategy.pullbacks {
+                    rules.push(Box::new(PullbackRule(pullbacks)));
+                }
+            }
+            RuleKind::Crossover => {
+                if strategy.enable_crossovers {
+                    rules.push(Box::new(CrossoverRule));
+                }
+            }
+            RuleKind::Macd => {
+                if let Some(macd) = strategy.enable_macd {
+                    rules.push(Box::new(MacdRule(macd)));
+                }
+            }
+            RuleKind::Bollinger => {
+                if let Some(bollinger) = strategy.bollinger {
+                    rules.push(Box::new(BollingerRule(bollinger)));
+                }
+            }
+            RuleKind::BiasOnly => {
+                if strategy.enable_bias_only {
+                    rules.push(Box::new(BiasOnlyRule));
+                }
+            }
+        }
+    }
+    rules
+}
+
+fn rule_crossovers(ctx: &AnalysisCtx) -> RuleOutcome {
+    let golden = ctx.prev_short_vs_long.is_le() && ctx.short_vs_long.is_gt();
+    let death = ctx.prev_short_vs_long.is_ge() && ctx.short_vs_long.is_lt();
+
+    let spread = (ctx.smas.sma_short - ctx.smas.sma_long).abs();
+
+    if golden {
+        if let Some(r) = ctx.gate_long.clone().or_else(|| ctx.small_move(spread)) {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(ReasonCode::GoldenCross, r.clone()),
+                gate: r,
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Buy,
+            rule: "Crossovers".into(),
+            strength: SignalStrength::Strong,
+            reason: ReasonCode::GoldenCross,
+        });
+    }
+
+    if death {
+        if let Some(r) = ctx.gate_short.clone().or_else(|| ctx.small_move(spread)) {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(ReasonCode::DeathCross, r.clone()),
+                gate: r,
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Sell,
+            rule: "Crossovers".into(),
+            strength: SignalStrength::Strong,
+            reason: ReasonCode::DeathCross,
+        });
+    }
+
+    RuleOutcome::NoMatch
+}
+
+fn rule_breakouts(ctx: &AnalysisCtx, prices: &[f64], config: BreakoutConfig) -> RuleOutcome {
+    let rule = "Breakouts";
+    // Veto for a breakout whose SMA relationship hasn't held for `min_trend_bars` yet
+    let young_trend = |relation: Ordering| {
+        let held = if ctx.short_vs_long == relation {
+            ctx.trend_bars
+        } else {
+            0
+        };
+        (held < config.min_trend_bars).then_some(ReasonCode::TrendTooYoung {
+            held,
+            required: config.min_trend_bars,
+        })
+    };
+    let last = *prices.last().expect("prices non-empty");
+    // Series the plain breakout is measured on: closes, or candle highs and lows
+    let (tail_highs, tail_lows): (Vec<f64>, Vec<f64>);
+    let (highs, lows): (&[f64], &[f64]) = match config.breakout_trigger {
+        BreakoutTrigger::Close => (prices, prices),
+        BreakoutTrigger::High => {
+            let tail = &ctx.samples[ctx
+                .samples
+                .len()
+                .saturating_sub(config.breakout_lookback + 1)..];
+            tail_highs = tail.iter().map(Sample::high_or_close).collect();
+            tail_lows = tail.iter().map(Sample::low_or_close).collect();
+            (&tail_highs, &tail_lows)
+        }
+    };
+    // How far the last candle cleared the recent high (`above`) or low
+    let clearance = |above: bool| {
+        let series = if above { highs } else { lows };
+        let last = *series.last().expect("prices non-empty");
+        match recent_range(series, config.breakout_lookback) {
+            Some((_, high)) if above => last - high,
+            Some((low, _)) => low - last,
+            None => 0.0,
+        }
+    };
+    // Clearance of a breakout (above) and breakdown (below) acted on this candle
+    let (above, below, retested) = match config.require_retest {
+        Some(retest) => (
+            breakout_retest_held(
+                prices,
+                config.breakout_lookback,
+                retest.lookahead,
+                retest.tolerance_pct,
+            )
+            .map(|level| last - level),
+            breakdown_retest_held(
+                prices,
+                config.breakout_lookback,
+                retest.lookahead,
+                retest.tolerance_pct,
+            )
+            .map(|level| level - last),
+            true,
+        ),
+        None => (
+            is_breakout_above_recent_high(highs, config.breakout_lookback).then(|| clearance(true)),
+            is_breakdown_below_recent_low(lows, config.breakout_lookback).then(|| clearance(false)),
+            false,
+        ),
+    };
+
+    if let Some(clearance) = above {
+        let reason = ReasonCode::BreakoutAboveHigh { retested };
+        if let Some(r) = ctx
+            .gate_long
+            .clone()
+            .or_else(|| young_trend(Ordering::Greater))
+            .or_else(|| ctx.small_move(clearance))
+            .or_else(|| ctx.volume_gate_long.clone())
+        {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r,
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Buy,
+            rule: rule.into(),
+            strength: SignalStrength::Strong,
+            reason,
+        });
+    }
+
+    if let Some(clearance) = below {
+        let reason = ReasonCode::BreakdownBelowLow { retested };
+        if let Some(r) = ctx
+            .gate_short
+            .clone()
+            .or_else(|| young_trend(Ordering::Less))
+            .or_else(|| ctx.small_move(clearance))
+            .or_else(|| ctx.volume_gate_short.clone())
+        {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r,
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Sell,
+            rule: rule.into(),
+            strength: SignalStrength::Strong,
+            reason,
+        });
+    }
+
+    RuleOutcome::NoMatch
+}
+
+fn rule_pullbacks(
+    ctx: &AnalysisCtx,
+    prices: &[f64],
+    pullback_config: PullbackConfig,
+) -> RuleOutcome {
+    let rule = "Pullbacks";
+    let (bounce_tol, reject_tol) = match pullback_config.tolerance_mode {
+        PullbackToleranceMode::Percent => (
+            pullback_config.bounce_tolerance_pct,
+            pullback_config.reject_tolerance_pct,
+        ),
+        PullbackToleranceMode::Atr => {
+            let Some(atr) = ctx.atr else {
+                return RuleOutcome::NoMatch;
+            };
+            if ctx.smas.sma_short <= 0.0 {
+                return RuleOutcome::NoMatch;
+            }
+            // Express the ATR band as a fraction of SMA(short)
+            let atr_frac = atr / ctx.smas.sma_short;
+            (
+                pullback_config.bounce_tolerance_pct * atr_frac,
+                pullback_config.reject_tolerance_pct * atr_frac,
+            )
+        }
+    };
+
+    if is_pullback_to_sma_short_and_bounce(prices, ctx.smas.sma_short, bounce_tol) {
+        let reason = ReasonCode::PullbackBounce;
+        if let Some(r) = &ctx.gate_long {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r.clone(),
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Buy,
+            rule: rule.into(),
+            strength: SignalStrength::Strong,
+            reason,
+        });
+    }
+
+    if is_pullback_to_sma_short_and_reject_down(prices, ctx.smas.sma_short, reject_tol) {
+        let reason = ReasonCode::PullbackRejection;
+        if let Some(r) = &ctx.gate_short {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r.clone(),
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Sell,
+            rule: rule.into(),
+            strength: SignalStrength::Strong,
+            reason,
+        });
+    }
+
+    RuleOutcome::NoMatch
+}
+
+fn rule_macd(ctx: &AnalysisCtx, prices: &[f64], config: MacdConfig) -> RuleOutcome {
+    let rule = "MACD";
+    let series = macd_series(prices, config.fast, config.slow, config.signal);
+    let (Some(Some(prev)), Some(Some(curr))) = (
+        series.len().checked_sub(2).map(|i| series[i]),
+        series.last().copied(),
+    ) else {
+        return RuleOutcome::NoMatch;
+    };
+
+    if prev.macd <= prev.signal && curr.macd > curr.signal {
+        let reason = ReasonCode::MacdCrossAbove;
+        if let Some(r) = &ctx.gate_long {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r.clone(),
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Buy,
+            rule: rule.into(),
+            strength: SignalStrength::Strong,
+            reason,
+        });
+    }
+
+    if prev.macd >= prev.signal && curr.macd < curr.signal {
+        let reason = ReasonCode::MacdCrossBelow;
+        if let Some(r) = &ctx.gate_short {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r.clone(),
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Sell,
+            rule: rule.into(),
+            strength: SignalStrength::Strong,
+            reason,
+        });
+    }
+
+    RuleOutcome::NoMatch
+}
+
+fn rule_bollinger(ctx: &AnalysisCtx, prices: &[f64], config: BollingerConfig) -> RuleOutcome {
+    let rule = "Bollinger";
+    let Some((&last, history)) = prices.split_last() else {
+        return RuleOutcome::NoMatch;
+    };
+    let (Some(&prev), Some(prev_bands), Some(bands)) = (
+        history.last(),
+        bollinger(history, config.period, config.num_std),
+        bollinger(prices, config.period, config.num_std),
+    ) else {
+        return RuleOutcome::NoMatch;
+    };
+
+    if prev < prev_bands.lower && last > bands.lower {
+        let reason = ReasonCode::BollingerReclaimLower;
+        if let Some(r) = &ctx.gate_long {
+            return RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r.clone(),
+            };
+        }
+        return RuleOutcome::Fired(Decision {
+            action: Action::Buy,
+            rule: rule.into(),
+            strength: SignalStrength::Strong,
+            reason,
+        });
+    }
+
+    if prev > prev_bands.upper && last < bands.upper {
+        let reason = ReasonCode::BollingerReclaimUpper;
         if let Some(r) = &ctx.gate_short {
             return RuleOutcome::Blocked {
-                reason: format!("{}, but {r}", reason),
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r.clone(),
             };
         }
         return RuleOutcome::Fired(Decision {
             action: Action::Sell,
             rule: rule.into(),
-            reason: reason.into(),
+            strength: SignalStrength::Strong,
+            reason,
         });
     }
 
@@ -319,136 +1548,257 @@ fn rule_pullbacks(
 
 fn rule_bias_only(ctx: &AnalysisCtx) -> RuleOutcome {
     let rule = "Bias only";
-    if ctx.smas.sma_short > ctx.smas.sma_long {
-        let reason = "Uptrend (SMA short > SMA long)";
+    if ctx.short_vs_long.is_gt() {
+        let reason = ReasonCode::Uptrend;
         if let Some(r) = &ctx.gate_long {
             return RuleOutcome::Blocked {
-                reason: format!("{}, but {r}", reason),
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r.clone(),
             };
         }
         return RuleOutcome::Fired(Decision {
             action: Action::Buy,
             rule: rule.into(),
-            reason: reason.into(),
+            strength: SignalStrength::Weak,
+            reason,
         });
     }
 
-    if ctx.smas.sma_short < ctx.smas.sma_long {
-        let reason = "Downtrend (SMA short < SMA long)";
+    if ctx.short_vs_long.is_lt() {
+        let reason = ReasonCode::Downtrend;
         if let Some(r) = &ctx.gate_short {
             return RuleOutcome::Blocked {
-                reason: format!("{}, but {r}", reason),
+                reason: ReasonCode::vetoed(reason, r.clone()),
+                gate: r.clone(),
             };
         }
         return RuleOutcome::Fired(Decision {
             action: Action::Sell,
             rule: rule.into(),
-            reason: reason.into(),
+            strength: SignalStrength::Weak,
+            reason,
         });
     }
 
     RuleOutcome::NoMatch
 }
 
-fn suggest_action(prices: &[f64], smas: Smas, strategy: StrategyConfig) -> (String, String) {
+/// Collects rule outcomes in evaluation order.
+/// Without a trace, evaluation stops at the first rule that fires.
+struct RuleRunner {
+    trace: Option<DecisionTrace>,
+    winner: Option<Decision>,
+    fired_but_blocked: Vec<ReasonCode>,
+}
+
+impl RuleRunner {
+    fn new(trace: bool) -> Self {
+        Self {
+            trace: trace.then(DecisionTrace::default),
+            winner: None,
+            fired_but_blocked: Vec::new(),
+        }
+    }
+
+    fn wants_more(&self) -> bool {
+        self.winner.is_none() || self.trace.is_some()
+    }
+
+    fn record(&mut self, rule: &str, outcome: RuleOutcome) {
+        if let Some(trace) = &mut self.trace {
+            trace.rules.push(RuleTrace {
+                rule: rule.into(),
+                outcome: outcome.clone(),
+            });
+        }
+        if self.winner.is_some() {
+            return;
+        }
+        match outcome {
+            RuleOutcome::Fired(d) => self.winner = Some(d),
+            RuleOutcome::Blocked { reason, .. } => self.fired_but_blocked.push(reason),
+            RuleOutcome::NoMatch => {}
+        }
+    }
+}
+
+/// `analyze_with_rules` over the strategy's `default_rules`, on close-only hourly
+/// samples at `prices`.
+#[cfg(test)]
+fn suggest_action(
+    prices: &[f64],
+    smas: Smas,
+    strategy: &StrategyConfig,
+    trace: bool,
+) -> AnalysisResult {
+    let samples = samples_from_prices(prices);
+    analyze_with_rules(
+        &samples,
+        prices,
+        smas,
+        strategy,
+        &default_rules(strategy),
+        trace,
+    )
+    .expect("prices non-empty")
+}
+
+/// Close-only hourly samples at `prices`, for tests that only care about prices.
+#[cfg(test)]
+fn samples_from_prices(prices: &[f64]) -> Vec<Sample> {
+    use chrono::TimeZone;
+
+    let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+    prices
+        .iter()
+        .enumerate()
+        .map(|(i, &price)| Sample {
+            ts: start + Duration::hours(i as i64),
+            price,
+            high: None,
+            low: None,
+            volume: None,
+        })
+        .collect()
+}
+
+/// What `suggest_action_with_rules` settled on for the last candle.
+struct Suggestion {
+    suggestion: String,
+    reason_code: ReasonCode,
+    strength: Option<SignalStrength>,
+    rule: Option<String>,
+    trace: Option<DecisionTrace>,
+}
+
+impl Suggestion {
+    fn hold(reason_code: ReasonCode, trace: Option<DecisionTrace>) -> Self {
+        Self {
+            suggestion: "HOLD".into(),
+            reason_code,
+            strength: None,
+            rule: None,
+            trace,
+        }
+    }
+}
+
+fn suggest_action_with_rules(
+    samples: &[Sample],
+    prices: &[f64],
+    smas: Smas,
+    strategy: &StrategyConfig,
+    rules: &[Box<dyn Rule>],
+    trace: bool,
+) -> Suggestion {
     // TODO: Consider mocking breakout, atr and regime indicators. Their functionality is already tested by other UTs
+    let trace = trace.then(DecisionTrace::default);
 
     // ~~~~ Volatility filter (ATR) ~~~~
     if let Some(atr_filter) = strategy.filters.atr {
-        let atr_p = match atr_filter.atr_percent(prices) {
+        let atr_p = match atr_filter.atr_percent(samples) {
             Some(v) => v,
             None => {
-                return (
-                    "HOLD".into(),
-                    format!(
-                        "Insufficient data for ATR({}) volatility filter",
-                        atr_filter.period()
-                    ),
+                return Suggestion::hold(
+                    ReasonCode::InsufficientAtrData {
+                        period: atr_filter.period(),
+                    },
+                    trace,
                 );
             }
         };
 
         if atr_p < atr_filter.floor() {
-            let atr_pct = atr_p * 100.0;
-            let floor_pct = atr_filter.floor() * 100.0;
-            return (
-                "HOLD".into(),
-                format!(
-                    "Volatility too low: ATR({}) = {:.2}% < floor {:.2}%",
-                    atr_filter.period(),
-                    atr_pct,
-                    floor_pct
-                ),
+            return Suggestion::hold(
+                ReasonCode::VolatilityTooLow {
+                    period: atr_filter.period(),
+                    atr: atr_p,
+                    floor: atr_filter.floor(),
+                },
+                trace,
             );
         }
     }
 
-    let analysis_ctx = AnalysisCtx::new(prices, smas, &strategy);
-
-    let mut fired_but_blocked = Vec::new();
-
-    // TODO: Move the strategies to different files and create necessary Strategy Trait
-    if let Some(breakouts) = strategy.breakouts {
-        match rule_breakouts(&analysis_ctx, prices, breakouts) {
-            RuleOutcome::Fired(d) => return (d.action.to_string(), d.reason),
-            RuleOutcome::Blocked { reason } => fired_but_blocked.push(reason),
-            _ => {}
-        }
+    // ~~~~ Momentum filter (RSI) ~~~~
+    if let Some(rsi_filter) = strategy.filters.rsi
+        && rsi_filter.rsi(prices).is_none()
+    {
+        return Suggestion::hold(
+            ReasonCode::InsufficientRsiData {
+                period: rsi_filter.period,
+            },
+            trace,
+        );
     }
 
-    if let Some(lookback) = strategy.pullbacks {
-        match rule_pullbacks(&analysis_ctx, prices, lookback) {
-            RuleOutcome::Fired(d) => return (d.action.to_string(), d.reason),
-            RuleOutcome::Blocked { reason } => fired_but_blocked.push(reason),
-            _ => {}
-        }
-    }
+    let analysis_ctx = AnalysisCtx::new(samples, prices, smas, strategy);
 
-    if strategy.enable_crossovers {
-        match rule_crossovers(&analysis_ctx) {
-            RuleOutcome::Fired(d) => return (d.action.to_string(), d.reason),
-            RuleOutcome::Blocked { reason } => fired_but_blocked.push(reason),
-            _ => {}
-        }
-    }
+    let mut runner = RuleRunner::new(trace.is_some());
 
-    if strategy.enable_bias_only {
-        match rule_bias_only(&analysis_ctx) {
-            RuleOutcome::Fired(d) => return (d.action.to_string(), d.reason),
-            RuleOutcome::Blocked { reason } => fired_but_blocked.push(reason),
-            _ => {}
+    for rule in rules {
+        if !runner.wants_more() {
+            break;
         }
+        runner.record(rule.name(), rule.evaluate(&analysis_ctx, prices));
     }
 
-    if !fired_but_blocked.is_empty() {
-        return ("HOLD".into(), fired_but_blocked.join(" & "));
+    let RuleRunner {
+        trace,
+        winner,
+        fired_but_blocked,
+    } = runner;
+
+    if let Some(d) = winner {
+        return Suggestion {
+            suggestion: d.action.to_string(),
+            reason_code: d.reason,
+            strength: Some(d.strength),
+            rule: Some(d.rule),
+            trace,
+        };
     }
 
-    ("HOLD".into(), "No strategy matched".into())
+    let reason = match fired_but_blocked.len() {
+        0 => ReasonCode::NoStrategyMatched,
+        1 => fired_but_blocked
+            .into_iter()
+            .next()
+            .expect("one blocked reason"),
+        _ => ReasonCode::AllVetoed(fired_but_blocked),
+    };
+    Suggestion::hold(reason, trace)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{Duration, TimeZone};
 
     impl StrategyConfig {
         fn test_config() -> Self {
             Self {
                 breakouts: Some(BreakoutConfig {
                     breakout_lookback: 5,
+                    min_trend_bars: 0,
+                    require_retest: None,
+                    breakout_trigger: BreakoutTrigger::Close,
                 }),
                 enable_bias_only: true,
+                enable_macd: None,
+                rule_order: Vec::new(),
+                bollinger: None,
                 enable_crossovers: true,
                 pullbacks: Some(PullbackConfig {
                     bounce_tolerance_pct: 0.003,
                     reject_tolerance_pct: 0.003,
+                    tolerance_mode: PullbackToleranceMode::Percent,
                 }),
                 sma_config: SmaConfig::sma_20_50(),
                 filters: FilterConfig {
                     require_trend_filter: true,
                     require_price_confirmation: true,
-                    atr: None,
-                    regime: None,
+                    ..Default::default()
                 },
             }
         }
@@ -535,8 +1885,9 @@ mod tests {
         let prices = vec![100.0, 99.0, 98.0, 97.0, 96.0, 90.0];
         let smas = Smas::downtrend_for_breakdown();
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
 
         assert_eq!(suggestion, "SELL");
         assert_eq!(reason, "Breakdown below recent low");
@@ -553,8 +1904,9 @@ mod tests {
         let prices = vec![95.0, 100.0, 98.0];
         let smas = Smas::downtrend_for_pullback();
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
 
         assert_eq!(suggestion, "SELL");
         assert_eq!(reason, "Pullback up to SMA short and rejection");
@@ -568,8 +1920,9 @@ mod tests {
         let prices = vec![100.0, 101.0, 102.0, 103.0, 104.0, 110.0];
         let smas = Smas::uptrend_for_breakout();
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
 
         assert_eq!(suggestion, "BUY");
         assert_eq!(reason, "Breakout above recent high");
@@ -586,8 +1939,9 @@ mod tests {
         let prices = vec![105.0, 100.0, 103.0];
         let smas = Smas::uptrend_for_bounce();
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
 
         assert_eq!(suggestion, "BUY");
         assert_eq!(reason, "Pullback to SMA short and bounce");
@@ -600,13 +1954,82 @@ mod tests {
         let prices = vec![100.0, 102.0, 106.0];
         let smas = Smas::golden_cross();
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
 
         assert_eq!(suggestion, "BUY");
         assert_eq!(reason, "Golden Cross");
     }
 
+    #[test]
+    fn test_rule_order_puts_bias_only_ahead_of_crossovers() {
+        let prices = vec![100.0, 102.0, 106.0];
+        let mut strategy = StrategyConfig::test_config();
+        strategy.rule_order = vec![RuleKind::BiasOnly, RuleKind::Crossover];
+
+        let AnalysisResult {
+            suggestion,
+            reason,
+            trace,
+            ..
+        } = super::suggest_action(&prices, Smas::golden_cross(), &strategy, true);
+
+        assert_eq!(suggestion, "BUY");
+        assert_ne!(reason, "Golden Cross");
+        // Unlisted rules keep their standard order after the listed ones
+        let names: Vec<String> = trace.unwrap().rules.into_iter().map(|r| r.rule).collect();
+        assert_eq!(names, ["Bias only", "Crossovers", "Breakouts", "Pullbacks"]);
+        assert!(
+            strategy
+                .describe_config()
+                .contains("order(bias_only>crossover>breakout>pullback)")
+        );
+    }
+
+    #[test]
+    fn test_rule_bollinger_fires_when_price_reclaims_a_band() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = None;
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = false;
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy.bollinger = Some(BollingerConfig {
+            period: 5,
+            num_std: 1.5,
+        });
+        let run = |tail: [f64; 2]| {
+            let mut prices = vec![10.0; 58];
+            prices.extend(tail);
+            let smas = compute_smas(&prices, &strategy.sma_config).unwrap();
+            let AnalysisResult {
+                suggestion, reason, ..
+            } = super::suggest_action(&prices, smas, &strategy, false);
+            (suggestion, reason)
+        };
+
+        // Bands over [10, 10, 10, 10, 8]: 9.6 +- 1.5 x 0.8 -> 8 closed below 8.4
+        assert_eq!(
+            run([8.0, 10.0]),
+            (
+                "BUY".to_string(),
+                "Price closed back above lower Bollinger band".to_string()
+            )
+        );
+        assert_eq!(
+            run([12.0, 10.0]),
+            (
+                "SELL".to_string(),
+                "Price closed back below upper Bollinger band".to_string()
+            )
+        );
+        // Still below the lower band, and flat prices
+        assert_eq!(run([8.0, 7.0]).0, "HOLD");
+        assert_eq!(run([10.0, 10.0]).0, "HOLD");
+    }
+
     #[test]
     fn test_suggest_action_sell_on_death_cross_with_confirmation() {
         // Downtrend + death cross + price_below_both.
@@ -614,8 +2037,9 @@ mod tests {
         let prices = vec![100.0, 99.0, 94.0];
         let smas = Smas::death_cross();
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
 
         assert_eq!(suggestion, "SELL");
         assert_eq!(reason, "Death Cross");
@@ -628,8 +2052,9 @@ mod tests {
         let prices = vec![101.0, 103.0, 106.0];
         let smas = Smas::long_bias_only();
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
 
         assert_eq!(suggestion, "BUY");
         assert_eq!(reason, "Uptrend (SMA short > SMA long)");
@@ -642,50 +2067,116 @@ mod tests {
         let prices = vec![100.0, 95.0, 90.0];
         let smas = Smas::short_bias_only();
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
 
         assert_eq!(suggestion, "SELL");
         assert_eq!(reason, "Downtrend (SMA short < SMA long)");
     }
 
     #[test]
-    fn test_suggest_action_generic_hold_when_no_trend_or_signal() {
-        // Flat SMAs, price neither above nor below both.
+    fn test_suggest_action_treats_smas_within_epsilon_as_equal() {
+        // SMA(short) is above SMA(long) only by float noise
         let prices = vec![100.0, 100.0, 100.0];
         let smas = Smas {
-            sma_short: 100.0,
+            sma_short: 100.0 + 1e-12,
             sma_long: 100.0,
             prev_sma_short: 100.0,
             prev_sma_long: 100.0,
         };
 
-        let (suggestion, reason) =
-            super::suggest_action(&prices, smas, StrategyConfig::test_config());
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
 
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
         assert_eq!(suggestion, "HOLD");
         assert_eq!(reason, "No strategy matched");
+
+        // With exact comparisons the same noise reads as a golden cross
+        let mut strict = strategy.clone();
+        strict.sma_config.eq_epsilon = 0.0;
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strict, false);
+        assert_eq!(suggestion, "BUY");
+        assert_eq!(reason, "Golden Cross");
     }
 
     #[test]
-    fn test_suggest_action_hold_when_volatility_below_floor() {
-        // Flat / almost-flat prices -> ATR% ≈ 0, definitely below a 1% floor.
-        // This should trigger the ATR gate *before* any trend / pattern logic.
-        let prices = vec![100.0; 40]; // enough points for ATR(14) to be computed
-
+    fn test_min_sma_separation_treats_near_equal_smas_as_neutral() {
+        // SMA(short) is 0.05% above SMA(long), on this and the previous candle
+        let prices = vec![100.2, 100.2, 100.2];
         let smas = Smas {
-            sma_short: 100.0,
+            sma_short: 100.05,
             sma_long: 100.0,
-            prev_sma_short: 100.0,
+            prev_sma_short: 100.05,
             prev_sma_long: 100.0,
         };
 
-        // High-ish floor: 1% ATR required.
-        // Since prices are constant, ATR% ≈ 0 -> won't pass the gate
-        let atr_filter = AtrFilter::new_fixed(14, 0.01);
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = None;
+        strategy.pullbacks = None;
+        strategy.filters.require_price_confirmation = false;
+
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
+        assert_eq!(suggestion, "BUY");
+        assert_eq!(reason, "Uptrend (SMA short > SMA long)");
+
+        // A 0.1% minimum separation makes the same SMAs a flat (neutral) market
+        strategy.sma_config.min_separation_pct = 0.001;
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
+        assert_eq!(suggestion, "HOLD");
+        assert_eq!(reason, "No strategy matched");
+    }
+
+    #[test]
+    fn test_suggest_action_generic_hold_when_no_trend_or_signal() {
+        // Flat SMAs, price neither above nor below both.
+        let prices = vec![100.0, 100.0, 100.0];
+        let smas = Smas {
+            sma_short: 100.0,
+            sma_long: 100.0,
+            prev_sma_short: 100.0,
+            prev_sma_long: 100.0,
+        };
+
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), false);
+
+        assert_eq!(suggestion, "HOLD");
+        assert_eq!(reason, "No strategy matched");
+    }
+
+    #[test]
+    fn test_suggest_action_hold_when_volatility_below_floor() {
+        // Flat / almost-flat prices -> ATR% ≈ 0, definitely below a 1% floor.
+        // This should trigger the ATR gate *before* any trend / pattern logic.
+        let prices = vec![100.0; 40]; // enough points for ATR(14) to be computed
+
+        let smas = Smas {
+            sma_short: 100.0,
+            sma_long: 100.0,
+            prev_sma_short: 100.0,
+            prev_sma_long: 100.0,
+        };
+
+        // High-ish floor: 1% ATR required.
+        // Since prices are constant, ATR% ≈ 0 -> won't pass the gate
+        let atr_filter = AtrFilter::new_fixed(14, 0.01);
         let mut strategy = StrategyConfig::test_config();
         strategy.filters.atr = Some(atr_filter);
-        let (suggestion, reason) = super::suggest_action(&prices, smas, strategy);
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
 
         assert_eq!(suggestion, "HOLD");
         assert!(
@@ -701,6 +2192,7 @@ mod tests {
                 slope_window: 3,
                 min_trend_strength: 0.01, // 1%
                 min_range: 0.0,
+                smooth_slope: false,
             }
         }
 
@@ -710,6 +2202,7 @@ mod tests {
                 slope_window: 3,
                 min_trend_strength: 0.01,
                 min_range: 0.0,
+                smooth_slope: false,
             }
         }
 
@@ -720,6 +2213,7 @@ mod tests {
                 slope_window: 3,
                 min_trend_strength: 0.20, // 20% required move -> most of our tiny moves are "sideways"
                 min_range: 0.20,          // and 20% range too
+                smooth_slope: false,
             }
         }
     }
@@ -736,7 +2230,9 @@ mod tests {
         let regime_filter = RegimeFilter::trending_up_filter();
         let mut strategy = StrategyConfig::test_config();
         strategy.filters.regime = Some(regime_filter);
-        let (suggestion, reason) = super::suggest_action(&prices, smas, strategy);
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
 
         assert_eq!(suggestion, "BUY");
         assert!(
@@ -757,7 +2253,9 @@ mod tests {
         let regime_filter = RegimeFilter::trending_down_filter();
         let mut strategy = StrategyConfig::test_config();
         strategy.filters.regime = Some(regime_filter);
-        let (suggestion, reason) = super::suggest_action(&prices, smas, strategy);
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
 
         assert_eq!(suggestion, "SELL");
         assert!(
@@ -777,7 +2275,9 @@ mod tests {
         let regime_filter = RegimeFilter::sideways_filter();
         let mut strategy = StrategyConfig::test_config();
         strategy.filters.regime = Some(regime_filter);
-        let (suggestion, reason) = super::suggest_action(&prices, smas, strategy);
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
 
         assert_eq!(suggestion, "HOLD");
         assert_eq!(
@@ -786,6 +2286,109 @@ mod tests {
         );
     }
 
+    fn macd_only_config() -> StrategyConfig {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = None;
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = false;
+        strategy.enable_macd = Some(MacdConfig {
+            fast: 3,
+            slow: 6,
+            signal: 3,
+        });
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy
+    }
+
+    #[test]
+    fn test_macd_rule_fires_on_signal_line_crosses() {
+        let strategy = macd_only_config();
+        assert!(strategy.describe_config().contains("macd(3/6/3)"));
+
+        // Accelerating fall, then a bounce: MACD crosses above its signal line
+        let falling = vec![
+            100.0, 99.0, 97.0, 94.0, 90.0, 85.0, 79.0, 72.0, 64.0, 55.0, 58.0,
+        ];
+        let AnalysisResult {
+            suggestion,
+            reason,
+            strength,
+            ..
+        } = super::suggest_action(&falling, Smas::uptrend_for_breakout(), &strategy, false);
+        assert_eq!(suggestion, "BUY");
+        assert_eq!(reason, "MACD crossed above signal line");
+        assert_eq!(strength, Some(SignalStrength::Strong));
+
+        // One candle earlier there's no cross yet
+        let AnalysisResult { suggestion, .. } = super::suggest_action(
+            &falling[..falling.len() - 1],
+            Smas::uptrend_for_breakout(),
+            &strategy,
+            false,
+        );
+        assert_eq!(suggestion, "HOLD");
+
+        let rising: Vec<f64> = falling.iter().map(|p| 200.0 - p).collect();
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&rising, Smas::downtrend_for_breakdown(), &strategy, false);
+        assert_eq!(suggestion, "SELL");
+        assert_eq!(reason, "MACD crossed below signal line");
+    }
+
+    #[test]
+    fn test_macd_rule_respects_long_gate() {
+        let mut strategy = macd_only_config();
+        strategy.filters.require_trend_filter = true;
+        let falling = vec![
+            100.0, 99.0, 97.0, 94.0, 90.0, 85.0, 79.0, 72.0, 64.0, 55.0, 58.0,
+        ];
+
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&falling, Smas::downtrend_for_breakdown(), &strategy, false);
+        assert_eq!(suggestion, "HOLD");
+        assert_eq!(
+            reason,
+            "MACD crossed above signal line, but Trend filter vetoed long (not uptrend)"
+        );
+    }
+
+    #[test]
+    fn test_regime_backed_trend_filter_vetoes_long_the_sma_slope_allows() {
+        let prices = vec![100.0, 101.0, 102.0, 103.0, 104.0, 110.0];
+        let smas = Smas::uptrend_for_breakout();
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.regime = Some(RegimeFilter::sideways_filter());
+        strategy.filters.require_trend_filter = true;
+
+        // Without the regime gate, the SMA slope alone lets the breakout through
+        let mut sma_slope = strategy.clone();
+        sma_slope.filters.regime = None;
+        let AnalysisResult { suggestion, .. } =
+            super::suggest_action(&prices, smas, &sma_slope, false);
+        assert_eq!(suggestion, "BUY");
+
+        strategy.filters.trend_source = TrendSource::Regime;
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
+        assert_eq!(suggestion, "HOLD");
+        assert!(
+            reason.contains(
+                "Breakout above recent high, but Trend filter vetoed long (regime not trending up)"
+            ),
+            "unexpected reason: {reason}"
+        );
+        // Single veto: the regime gate doesn't fire on top of the trend filter
+        assert!(
+            !reason.contains("Regime filter vetoed"),
+            "unexpected reason: {reason}"
+        );
+    }
+
     #[test]
     fn test_suggest_action_buy_blocked_in_sideways_regime() {
         // Uptrend breakout, but regime says Sideways -> block BUY
@@ -796,7 +2399,9 @@ mod tests {
 
         let mut strategy = StrategyConfig::test_config();
         strategy.filters.regime = Some(regime_filter);
-        let (suggestion, reason) = super::suggest_action(&prices, smas, strategy);
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
 
         assert_ne!(suggestion, "BUY");
         assert!(
@@ -806,4 +2411,899 @@ mod tests {
             reason
         );
     }
+
+    fn rising_samples(n: usize) -> Vec<Sample> {
+        use chrono::{Duration, TimeZone, Utc};
+
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        (0..n)
+            .map(|i| Sample {
+                ts: start + Duration::hours(i as i64),
+                price: 100.0 + i as f64,
+                high: None,
+                low: None,
+                volume: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_high_trigger_fires_on_a_wick_through_the_level() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = false;
+        let run = |strategy: &StrategyConfig, samples: &[Sample]| {
+            let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+            let smas = compute_smas(&prices, &strategy.sma_config).unwrap();
+            analyze(samples, &prices, smas, strategy, false).unwrap()
+        };
+
+        // The last candle closes at the previous close but its high pierces it
+        let mut samples = rising_samples(60);
+        let last = samples.last_mut().unwrap();
+        last.price -= 1.0;
+        last.high = Some(last.price + 2.0);
+        last.low = Some(last.price - 0.5);
+
+        assert_eq!(run(&strategy, &samples).suggestion, "HOLD");
+
+        strategy.breakouts.as_mut().unwrap().breakout_trigger = BreakoutTrigger::High;
+        let high = run(&strategy, &samples);
+        assert_eq!(high.suggestion, "BUY");
+        assert_eq!(high.reason, "Breakout above recent high");
+    }
+
+    #[test]
+    fn test_volume_filter_confirms_breakouts_and_ignores_missing_volume() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = false;
+        strategy.filters.volume = Some(VolumeFilter {
+            lookback: 5,
+            min_ratio: 1.5,
+        });
+        let run = |samples: &[Sample]| {
+            let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+            let smas = compute_smas(&prices, &strategy.sma_config).unwrap();
+            analyze(samples, &prices, smas, &strategy, false).unwrap()
+        };
+
+        // Close-only input: no-op
+        let mut samples = rising_samples(60);
+        assert_eq!(run(&samples).suggestion, "BUY");
+
+        // Breakout candle at 1.2x the average volume
+        for s in samples.iter_mut() {
+            s.volume = Some(10.0);
+        }
+        samples.last_mut().unwrap().volume = Some(12.0);
+        let weak = run(&samples);
+        assert_eq!(weak.suggestion, "HOLD");
+        assert_eq!(
+            weak.reason,
+            "Breakout above recent high, but Volume confirmation vetoed long \
+             (volume 12.00 < 1.5x average 10.00)"
+        );
+
+        samples.last_mut().unwrap().volume = Some(15.0);
+        assert_eq!(run(&samples).suggestion, "BUY");
+
+        // A gap in the averaged window disables the check
+        samples.last_mut().unwrap().volume = Some(12.0);
+        samples[57].volume = None;
+        assert_eq!(run(&samples).suggestion, "BUY");
+    }
+
+    #[test]
+    fn test_analyze_source_consumes_in_memory_source() {
+        // Steadily rising prices: uptrend, price above both MAs, new high on the last candle.
+        let samples = rising_samples(60);
+        let last = samples.last().unwrap().clone();
+        let mut source = crate::source::VecSource::new(samples);
+
+        let result = analyze_source(&mut source, &StrategyConfig::test_config())
+            .unwrap()
+            .expect("enough data for SMA20/50");
+
+        assert_eq!(result.last.ts, last.ts);
+        assert_eq!(result.last.price, 159.0);
+        assert_eq!(result.suggestion, "BUY");
+        assert_eq!(result.reason, "Breakout above recent high");
+        // source is fully drained
+        assert!(source.next_sample().is_none());
+    }
+
+    #[test]
+    fn test_gap_inside_sma_window_holds() {
+        use chrono::Duration;
+
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.max_candle_gap = Some(Duration::hours(2));
+        let with_gap_at = |index: usize| {
+            let mut samples = rising_samples(60);
+            for sample in &mut samples[index..] {
+                sample.ts += Duration::hours(5);
+            }
+            let mut source = crate::source::VecSource::new(samples);
+            analyze_source(&mut source, &strategy).unwrap().unwrap()
+        };
+
+        // 6h between candles 29 and 30, inside the last 51 candles SMA(50) looks at
+        let gapped = with_gap_at(30);
+        assert_eq!(gapped.suggestion, "HOLD");
+        assert!(gapped.reason.starts_with("stale/gapped data"));
+
+        // The same gap before the window doesn't matter
+        assert_eq!(with_gap_at(5).suggestion, "BUY");
+    }
+
+    #[test]
+    fn test_rules_run_in_the_given_order() {
+        struct AlwaysSell;
+        impl Rule for AlwaysSell {
+            fn name(&self) -> &str {
+                "Always sell"
+            }
+            fn evaluate(&self, _ctx: &AnalysisCtx, _prices: &[f64]) -> RuleOutcome {
+                RuleOutcome::Fired(Decision {
+                    action: Action::Sell,
+                    reason: ReasonCode::Custom("Custom rule".into()),
+                    rule: "Always sell".into(),
+                    strength: SignalStrength::Strong,
+                })
+            }
+        }
+
+        let samples = rising_samples(60);
+        let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+        let strategy = StrategyConfig::test_config();
+        let smas = compute_smas(&prices, &strategy.sma_config).unwrap();
+        let run = |rules: Vec<Box<dyn Rule>>| {
+            analyze_with_rules(&samples, &prices, smas, &strategy, &rules, true).unwrap()
+        };
+
+        // Default order: the breakout wins
+        let default = run(default_rules(&strategy));
+        let names: Vec<&str> = default
+            .trace
+            .as_ref()
+            .unwrap()
+            .rules
+            .iter()
+            .map(|r| r.rule.as_str())
+            .collect();
+        assert_eq!(names, ["Breakouts", "Pullbacks", "Crossovers", "Bias only"]);
+        assert_eq!(default.suggestion, "BUY");
+        assert_eq!(
+            default.reason,
+            analyze(&samples, &prices, smas, &strategy, false)
+                .unwrap()
+                .reason
+        );
+
+        // A custom rule registered first takes priority
+        let mut rules: Vec<Box<dyn Rule>> = vec![Box::new(AlwaysSell)];
+        rules.extend(default_rules(&strategy));
+        let custom = run(rules);
+        assert_eq!(custom.suggestion, "SELL");
+        assert_eq!(custom.reason, "Custom rule");
+
+        // Bias only ahead of breakouts
+        let reordered = run(vec![
+            Box::new(BiasOnlyRule),
+            Box::new(BreakoutRule(strategy.breakouts.unwrap())),
+        ]);
+        assert_eq!(reordered.suggestion, "BUY");
+        assert_eq!(
+            reordered.trace.unwrap().rules[0].outcome,
+            RuleOutcome::Fired(Decision {
+                action: Action::Buy,
+                reason: reordered.reason_code.clone(),
+                rule: "Bias only".into(),
+                strength: SignalStrength::Weak,
+            })
+        );
+    }
+
+    #[test]
+    fn test_analyze_source_keeps_only_the_bounded_history() {
+        let samples = rising_samples(500);
+        let strategy = StrategyConfig::test_config();
+        assert!(strategy.required_history().is_some());
+        let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+        let smas = compute_smas(&prices, &strategy.sma_config).unwrap();
+        let full = try_analyze(&samples, &prices, smas, &strategy, false).unwrap();
+
+        let mut source = crate::source::VecSource::new(samples);
+        let streamed = analyze_source(&mut source, &strategy).unwrap().unwrap();
+
+        assert_eq!(streamed.suggestion, full.suggestion);
+        assert_eq!(streamed.reason, full.reason);
+        assert_eq!(streamed.last.ts, full.last.ts);
+    }
+
+    #[test]
+    fn test_analyze_source_keeps_the_volume_filter_lookback() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = false;
+        strategy.filters.volume = Some(VolumeFilter {
+            lookback: 100,
+            min_ratio: 1.5,
+        });
+        let mut samples = rising_samples(300);
+        for s in samples.iter_mut() {
+            s.volume = Some(10.0);
+        }
+        samples.last_mut().unwrap().volume = Some(12.0);
+        let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+        let smas = compute_smas(&prices, &strategy.sma_config).unwrap();
+        let full = try_analyze(&samples, &prices, smas, &strategy, false).unwrap();
+        assert_eq!(full.suggestion, "HOLD");
+
+        let mut source = crate::source::VecSource::new(samples);
+        let streamed = analyze_source(&mut source, &strategy).unwrap().unwrap();
+
+        assert_eq!(streamed.suggestion, full.suggestion);
+        assert_eq!(streamed.reason, full.reason);
+    }
+
+    #[test]
+    fn test_analyze_source_rejects_out_of_order_samples() {
+        let mut samples = rising_samples(60);
+        samples.swap(10, 11);
+        let mut source = crate::source::VecSource::new(samples);
+
+        let err = analyze_source(&mut source, &StrategyConfig::test_config())
+            .err()
+            .expect("samples out of order");
+
+        assert!(matches!(
+            err.downcast_ref::<DataError>(),
+            Some(DataError::OutOfOrder { index: 11 })
+        ));
+    }
+
+    #[test]
+    fn test_analyze_source_returns_none_when_not_enough_data() {
+        let mut source = crate::source::VecSource::new(rising_samples(50));
+        let result = analyze_source(&mut source, &StrategyConfig::test_config()).unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_pipelines_handle_empty_and_single_candle_input() {
+        let strategy = StrategyConfig::test_config();
+        let single = rising_samples(1);
+
+        let mut empty_source = crate::source::VecSource::new(Vec::new());
+        let err = analyze_source(&mut empty_source, &strategy)
+            .err()
+            .expect("an empty source is an error");
+        assert_eq!(err.to_string(), "no samples");
+        let mut single_source = crate::source::VecSource::new(single.clone());
+        assert!(
+            analyze_source(&mut single_source, &strategy)
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(try_analyze(&[], &[], Smas::uptrend_for_breakout(), &strategy, false).is_err());
+        assert!(matches!(
+            analyze(&[], &[], Smas::uptrend_for_breakout(), &strategy, false),
+            Err(DataError::Empty)
+        ));
+
+        let err = timeframe_agreement(&[], &strategy, &[1, 4]).unwrap_err();
+        assert_eq!(err.to_string(), "no samples");
+        let agreement = timeframe_agreement(&single, &strategy, &[1, 4]).unwrap();
+        assert_eq!(agreement.agreement, 0);
+
+        for samples in [&[][..], &single[..]] {
+            assert!(collect_signals(samples, &strategy).is_empty());
+            assert!(diff_signals(samples, &strategy, &strategy).is_empty());
+            assert_eq!(information_coefficient(samples, &strategy, 1), None);
+        }
+    }
+
+    #[test]
+    fn test_suggest_action_records_every_enabled_rule() {
+        // Breakout above recent high in an uptrend, but no golden cross this candle.
+        let prices = vec![100.0, 101.0, 102.0, 103.0, 104.0, 110.0];
+        let smas = Smas::uptrend_for_breakout();
+
+        let AnalysisResult {
+            suggestion,
+            reason,
+            trace,
+            ..
+        } = super::suggest_action(&prices, smas, &StrategyConfig::test_config(), true);
+        assert_eq!(suggestion, "BUY");
+        assert_eq!(reason, "Breakout above recent high");
+
+        let trace = trace.expect("trace requested");
+        let rules: Vec<&str> = trace.rules.iter().map(|r| r.rule.as_str()).collect();
+        assert_eq!(rules, ["Breakouts", "Pullbacks", "Crossovers", "Bias only"]);
+
+        assert!(matches!(
+            &trace.rules[0].outcome,
+            RuleOutcome::Fired(d) if d.action == Action::Buy && d.rule == "Breakouts"
+        ));
+        assert_eq!(trace.rules[1].outcome, RuleOutcome::NoMatch);
+        assert_eq!(trace.rules[2].outcome, RuleOutcome::NoMatch);
+        assert!(matches!(
+            &trace.rules[3].outcome,
+            RuleOutcome::Fired(d) if d.action == Action::Buy && d.rule == "Bias only"
+        ));
+    }
+
+    #[test]
+    fn test_suggest_action_records_blocking_gate() {
+        let prices = vec![100.0, 101.0, 102.0, 103.0, 104.0, 110.0];
+        let smas = Smas::uptrend_for_breakout();
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.regime = Some(RegimeFilter::sideways_filter());
+
+        let AnalysisResult {
+            suggestion, trace, ..
+        } = super::suggest_action(&prices, smas, &strategy, true);
+        assert_eq!(suggestion, "HOLD");
+
+        let trace = trace.expect("trace requested");
+        assert_eq!(trace.rules.len(), 4);
+        assert_eq!(
+            trace.rules[0].outcome,
+            RuleOutcome::Blocked {
+                reason: ReasonCode::vetoed(
+                    ReasonCode::BreakoutAboveHigh { retested: false },
+                    ReasonCode::RegimeVeto(Side::Long),
+                ),
+                gate: ReasonCode::RegimeVeto(Side::Long),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rsi_filter_vetoes_overbought_long_and_holds_without_data() {
+        let prices = vec![100.0, 101.0, 102.0, 103.0, 104.0, 110.0];
+        let smas = Smas::uptrend_for_breakout();
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.rsi = Some(RsiFilter {
+            period: 3,
+            overbought: 70.0,
+            oversold: 30.0,
+        });
+
+        // Only gains: RSI 100
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
+        assert_eq!(suggestion, "HOLD");
+        assert!(
+            reason.starts_with(
+                "Breakout above recent high, but RSI filter vetoed long (RSI 100.0 > overbought 70)"
+            ),
+            "unexpected reason: {reason}"
+        );
+
+        strategy.filters.rsi = Some(RsiFilter {
+            period: 10,
+            overbought: 70.0,
+            oversold: 30.0,
+        });
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&prices, smas, &strategy, false);
+        assert_eq!(suggestion, "HOLD");
+        assert_eq!(reason, "Insufficient data for RSI(10) momentum filter");
+    }
+
+    #[test]
+    fn test_suggest_action_without_trace_returns_none() {
+        let prices = vec![100.0, 101.0, 102.0, 103.0, 104.0, 110.0];
+        let AnalysisResult { trace, .. } = super::suggest_action(
+            &prices,
+            Smas::uptrend_for_breakout(),
+            &StrategyConfig::test_config(),
+            false,
+        );
+        assert!(trace.is_none());
+    }
+
+    #[test]
+    fn test_rule_pullbacks_atr_band_widens_in_volatile_market() {
+        // p2 = 105 (above SMA), p1 = 100.5 (touch), p0 = 103 (bounce)
+        // Percent 0.003: band up to 100.3 -> 100.5 misses it
+        // ATR(2) = (4.5 + 2.5) / 2 = 3.5; 0.5 x ATR = 1.75 -> band up to 101.75 -> touch
+        let prices = vec![105.0, 100.5, 103.0];
+        let smas = Smas::uptrend_for_bounce();
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.atr = Some(AtrFilter::new_fixed(2, 0.0));
+        let samples = samples_from_prices(&prices);
+        let ctx = AnalysisCtx::new(&samples, &prices, smas, &strategy);
+
+        let percent = PullbackConfig {
+            bounce_tolerance_pct: 0.003,
+            reject_tolerance_pct: 0.003,
+            tolerance_mode: PullbackToleranceMode::Percent,
+        };
+        assert_eq!(rule_pullbacks(&ctx, &prices, percent), RuleOutcome::NoMatch);
+
+        let atr_scaled = PullbackConfig {
+            bounce_tolerance_pct: 0.5,
+            reject_tolerance_pct: 0.5,
+            tolerance_mode: PullbackToleranceMode::Atr,
+        };
+        assert!(matches!(
+            rule_pullbacks(&ctx, &prices, atr_scaled),
+            RuleOutcome::Fired(d) if d.action == Action::Buy
+        ));
+    }
+
+    #[test]
+    fn test_ma_stack_filter_passes_stacked_and_vetoes_jumbled_smas() {
+        let smas = Smas::long_bias_only();
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = None;
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy.filters.require_ma_stack = true;
+        strategy.sma_config.stack_windows = vec![2, 3, 5];
+
+        // Steady rise: SMA(2) > SMA(3) > SMA(5)
+        let stacked = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&stacked, smas, &strategy, false);
+        assert_eq!(suggestion, "BUY");
+        assert_eq!(reason, "Uptrend (SMA short > SMA long)");
+
+        // SMA(2) = 3.5, SMA(3) = 4, SMA(5) = 3.8 -> not stacked
+        let jumbled = vec![1.0, 1.0, 6.0, 5.0, 4.0, 3.0];
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&jumbled, smas, &strategy, false);
+        assert_eq!(suggestion, "HOLD");
+        assert_eq!(
+            reason,
+            "Uptrend (SMA short > SMA long), but MA stack vetoed long (SMAs not stacked up)"
+        );
+    }
+
+    #[test]
+    fn test_breakout_requires_established_trend_with_min_trend_bars() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = Some(BreakoutConfig {
+            breakout_lookback: 3,
+            min_trend_bars: 3,
+            require_retest: None,
+            breakout_trigger: BreakoutTrigger::Close,
+        });
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = false;
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy.sma_config = SmaConfig::new(2, 3);
+
+        // Downtrend until index 5; SMA(2) crosses above SMA(3) at index 6 (7.5),
+        // then each candle breaks above the previous three
+        let series = [10.0, 9.0, 8.0, 7.0, 6.0, 5.0, 7.5, 9.5, 11.0, 13.0];
+        let run = |len: usize| {
+            let prices = &series[..len];
+            let smas = compute_smas(prices, &strategy.sma_config).unwrap();
+            super::suggest_action(prices, smas, &strategy, false)
+        };
+
+        // One bar after the flip: SMA(2) > SMA(3) has held for only 2 bars
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = run(8);
+        assert_eq!(suggestion, "HOLD");
+        assert_eq!(
+            reason,
+            "Breakout above recent high, but trend only 2 of 3 bars old"
+        );
+
+        // Three bars after the flip the breakout is honored
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = run(10);
+        assert_eq!(suggestion, "BUY");
+        assert_eq!(reason, "Breakout above recent high");
+    }
+
+    #[test]
+    fn test_breakout_with_required_retest_waits_for_retest_hold() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = Some(BreakoutConfig {
+            breakout_lookback: 3,
+            min_trend_bars: 0,
+            require_retest: Some(RetestConfig {
+                lookahead: 4,
+                tolerance_pct: 0.01,
+            }),
+            breakout_trigger: BreakoutTrigger::Close,
+        });
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = false;
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy.sma_config = SmaConfig::new(2, 3);
+        let suggestions = |series: &[f64]| -> Vec<String> {
+            (4..=series.len())
+                .map(|len| {
+                    let prices = &series[..len];
+                    let smas = compute_smas(prices, &strategy.sma_config).unwrap();
+                    super::suggest_action(prices, smas, &strategy, false).suggestion
+                })
+                .collect()
+        };
+
+        // Clean breakout over 10 at index 4 that never looks back: nothing fires
+        let clean = [10.0, 10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 14.0];
+        assert!(suggestions(&clean).iter().all(|s| s == "HOLD"));
+
+        // Breakout, retest of 10 at index 5, hold at index 6
+        let retest = [10.0, 10.0, 10.0, 10.0, 11.0, 10.05, 10.5];
+        assert_eq!(suggestions(&retest), ["HOLD", "HOLD", "HOLD", "BUY"]);
+        let smas = compute_smas(&retest, &strategy.sma_config).unwrap();
+        let AnalysisResult { reason, .. } = super::suggest_action(&retest, smas, &strategy, false);
+        assert_eq!(reason, "Breakout above recent high held on retest");
+    }
+
+    #[test]
+    fn test_diff_signals_lists_candles_where_bias_only_changes_the_action() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let prices = [10.0, 10.0, 10.0, 11.0, 12.0, 13.0, 10.5, 16.0, 17.0];
+        let samples: Vec<Sample> = prices
+            .iter()
+            .enumerate()
+            .map(|(i, &price)| Sample {
+                ts: start + Duration::hours(i as i64),
+                price,
+                high: None,
+                low: None,
+                volume: None,
+            })
+            .collect();
+
+        let mut crossovers_only = StrategyConfig::test_config();
+        crossovers_only.breakouts = None;
+        crossovers_only.pullbacks = None;
+        crossovers_only.enable_bias_only = false;
+        crossovers_only.filters.require_trend_filter = false;
+        crossovers_only.filters.require_price_confirmation = false;
+        crossovers_only.sma_config = SmaConfig::new(2, 3);
+        let mut with_bias = crossovers_only.clone();
+        with_bias.enable_bias_only = true;
+
+        // SMA(2) vs SMA(3): crosses at 3 (up), 6 (down), 7 (up); above SMA(3) at 4, 5 and 8
+        let diffs = diff_signals(&samples, &crossovers_only, &with_bias);
+        assert_eq!(
+            diffs,
+            [
+                (samples[4].ts, Action::Hold, Action::Buy),
+                (samples[5].ts, Action::Hold, Action::Buy),
+                (samples[8].ts, Action::Hold, Action::Buy),
+            ]
+        );
+
+        assert!(diff_signals(&samples, &with_bias, &with_bias.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_round_number_filter_vetoes_entries_near_the_grid() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = None;
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy.filters.round_number = Some(RoundNumberFilter {
+            grid: 1000.0,
+            band_pct: 0.001,
+        });
+        let smas = Smas::long_bias_only();
+
+        // 999.9 is 0.01% away from 1000
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&[990.0, 995.0, 999.9], smas, &strategy, false);
+        assert_eq!(suggestion, "HOLD");
+        assert_eq!(
+            reason,
+            "Uptrend (SMA short > SMA long), but Round-number filter vetoed long (price near 1000)"
+        );
+
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&[940.0, 945.0, 950.0], smas, &strategy, false);
+        assert_eq!(suggestion, "BUY");
+        assert_eq!(reason, "Uptrend (SMA short > SMA long)");
+    }
+
+    #[test]
+    fn test_validate_rejects_long_window_not_above_short() {
+        let mut strategy = StrategyConfig::test_config();
+        assert!(strategy.validate().is_ok());
+
+        strategy.sma_config = SmaConfig::new(20, 20);
+        assert_eq!(
+            strategy.validate(),
+            Err("long_window (20) must be greater than short_window (20)".to_string())
+        );
+
+        strategy.sma_config = SmaConfig::new(50, 20);
+        assert!(strategy.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_degenerate_volume_filter() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.volume = Some(VolumeFilter {
+            lookback: 0,
+            min_ratio: 1.0,
+        });
+        assert!(strategy.validate().is_err());
+
+        strategy.filters.volume = Some(VolumeFilter {
+            lookback: 20,
+            min_ratio: f64::NAN,
+        });
+        assert!(strategy.validate().is_err());
+
+        strategy.filters.volume = Some(VolumeFilter {
+            lookback: 20,
+            min_ratio: 1.5,
+        });
+        assert!(strategy.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_ma_stack_without_two_windows() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.require_ma_stack = true;
+        assert!(strategy.validate().is_err());
+
+        strategy.sma_config.stack_windows = vec![20];
+        assert!(strategy.validate().is_err());
+
+        strategy.sma_config.stack_windows = vec![10, 20, 50];
+        assert!(strategy.validate().is_ok());
+    }
+
+    #[test]
+    fn test_describe_config_prints_human_friendly_units() {
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.atr = Some(AtrFilter::new_fixed(14, 0.004));
+        strategy.filters.regime = Some(RegimeFilter::default());
+
+        assert_eq!(
+            strategy.describe_config(),
+            "SMA20/50 + breakout(lookback=5) + pullback(bounce=0.30%, rejection=0.30%) \
+             + crossovers + bias_only + require_price_confirmation + require_trend_filter \
+             + atr(floor=0.40%, period=14) \
+             + regime(long_window=200, slope_window=48, min_trend_strength=2.00%, min_range=3.00%)"
+        );
+
+        let mut atr_pullbacks = StrategyConfig::test_config();
+        atr_pullbacks.pullbacks = Some(PullbackConfig {
+            bounce_tolerance_pct: 0.5,
+            reject_tolerance_pct: 0.25,
+            tolerance_mode: PullbackToleranceMode::Atr,
+        });
+        assert!(
+            atr_pullbacks
+                .describe_config()
+                .contains("pullback(bounce=0.5xATR, rejection=0.25xATR)")
+        );
+    }
+
+    #[test]
+    fn test_timeframe_agreement_counts_timeframes_on_the_majority_side() {
+        // Ten days of steady gains, then a four-hour drop into the eleventh day
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let prices = (0..240)
+            .map(|i| 100.0 + i as f64)
+            .chain([336.0, 333.0, 330.0, 327.0]);
+        let samples: Vec<Sample> = prices
+            .enumerate()
+            .map(|(i, price)| Sample {
+                ts: start + Duration::hours(i as i64),
+                price,
+                high: None,
+                low: None,
+                volume: None,
+            })
+            .collect();
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = None;
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy.sma_config = SmaConfig::new(2, 3);
+
+        let rising = timeframe_agreement(&samples[..240], &strategy, &[1, 4, 24]).unwrap();
+        assert_eq!(
+            rising.actions,
+            vec![(1, Action::Buy), (4, Action::Buy), (24, Action::Buy)]
+        );
+        assert_eq!(rising.agreement, 3);
+        assert_eq!(rising.direction, Some(Action::Buy));
+
+        // The drop turns 1h and 4h around; the daily closes still rise
+        let mixed = timeframe_agreement(&samples, &strategy, &[1, 4, 24]).unwrap();
+        assert_eq!(
+            mixed.actions,
+            vec![(1, Action::Sell), (4, Action::Sell), (24, Action::Buy)]
+        );
+        assert_eq!(mixed.agreement, 2);
+        assert_eq!(mixed.direction, Some(Action::Sell));
+    }
+
+    #[test]
+    fn test_information_coefficient_near_one_when_signals_predict_next_move() {
+        // 20 doublings, a 2-candle plateau, 20 halvings, ... Powers of two keep the
+        // returns exact (+100% / -50%), so SMA(2) vs SMA(3) only misses at the plateaus
+        let mut prices = vec![1.0; 3];
+        for leg in 0..6 {
+            for _ in 0..20 {
+                let last = *prices.last().unwrap();
+                prices.push(if leg % 2 == 0 { last * 2.0 } else { last * 0.5 });
+            }
+            let last = *prices.last().unwrap();
+            prices.extend([last, last]);
+        }
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let samples: Vec<Sample> = prices
+            .iter()
+            .enumerate()
+            .map(|(i, &price)| Sample {
+                ts: start + Duration::hours(i as i64),
+                price,
+                high: None,
+                low: None,
+                volume: None,
+            })
+            .collect();
+
+        let mut strategy = StrategyConfig::test_config();
+        strategy.breakouts = None;
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy.sma_config = SmaConfig::new(2, 3);
+
+        let ic = information_coefficient(&samples, &strategy, 1).unwrap();
+        assert!(ic > 0.9, "ic = {ic}");
+
+        // Against the move two candles later it's no longer as clean
+        let ic_2 = information_coefficient(&samples, &strategy, 2).unwrap();
+        assert!(ic_2 < ic);
+        assert_eq!(information_coefficient(&samples, &strategy, 0), None);
+    }
+
+    #[test]
+    fn test_try_analyze_rejects_reversed_samples() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let mut samples: Vec<Sample> = (0..60)
+            .map(|i| Sample {
+                ts: start + Duration::hours(i),
+                price: 100.0 + i as f64,
+                high: None,
+                low: None,
+                volume: None,
+            })
+            .collect();
+        samples.reverse();
+        let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+        let strategy = StrategyConfig::test_config();
+        let smas = compute_smas(&prices, &strategy.sma_config).unwrap();
+
+        let err = try_analyze(&samples, &prices, smas, &strategy, false)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            err.starts_with("samples are not in increasing time order"),
+            "{err}"
+        );
+
+        // Back in order it goes through; prices that aren't the samples' don't
+        samples.reverse();
+        let prices: Vec<f64> = samples.iter().map(|s| s.price).collect();
+        assert!(try_analyze(&samples, &prices, smas, &strategy, false).is_ok());
+        assert!(check_series(&samples, &prices[1..]).is_ok());
+        let mut wrong = prices.clone();
+        wrong[3] = 0.0;
+        assert!(check_series(&samples, &wrong).is_err());
+        assert!(check_series(&samples[1..], &prices).is_err());
+    }
+
+    #[test]
+    fn test_atr_filter_uses_high_low_when_samples_have_them() {
+        let prices = vec![100.0; 6];
+        let smas = Smas {
+            sma_short: 100.0,
+            sma_long: 100.0,
+            prev_sma_short: 100.0,
+            prev_sma_long: 100.0,
+        };
+        let mut strategy = StrategyConfig::test_config();
+        strategy.filters.atr = Some(AtrFilter::new_fixed(3, 0.01));
+
+        // Flat closes: no close-to-close range at all
+        let close_only = samples_from_prices(&prices);
+        let held = analyze(&close_only, &prices, smas, &strategy, false).unwrap();
+        assert!(matches!(
+            held.reason_code,
+            ReasonCode::VolatilityTooLow { .. }
+        ));
+
+        // Same closes with a 3-point intrabar range: true-range ATR% = 3%
+        let ranged: Vec<Sample> = close_only
+            .iter()
+            .map(|s| Sample {
+                high: Some(101.5),
+                low: Some(98.5),
+                ..s.clone()
+            })
+            .collect();
+        let analysis = analyze(&ranged, &prices, smas, &strategy, false).unwrap();
+        assert!(!matches!(
+            analysis.reason_code,
+            ReasonCode::VolatilityTooLow { .. } | ReasonCode::InsufficientAtrData { .. }
+        ));
+    }
+
+    #[test]
+    fn test_min_signal_atr_mult_gates_marginal_breakout() {
+        // Alternating 100/101 (ATR ~1), then a new high above the 101s
+        let with_last = |last: f64| {
+            let mut prices: Vec<f64> = (0..20)
+                .map(|i| if i % 2 == 0 { 101.0 } else { 100.0 })
+                .collect();
+            prices.push(last);
+            prices
+        };
+        let smas = Smas {
+            sma_short: 100.5,
+            sma_long: 100.5,
+            prev_sma_short: 100.5,
+            prev_sma_long: 100.5,
+        };
+        let mut strategy = StrategyConfig::test_config();
+        strategy.pullbacks = None;
+        strategy.enable_crossovers = false;
+        strategy.enable_bias_only = false;
+        strategy.filters.require_trend_filter = false;
+        strategy.filters.require_price_confirmation = false;
+        strategy.filters.min_signal_atr_mult = Some(0.5);
+
+        // Clears the high by 0.2, under half an ATR
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&with_last(101.2), smas, &strategy, false);
+        assert_eq!(suggestion, "HOLD");
+        assert!(
+            reason.starts_with("Breakout above recent high, but move 0.2000 < 0.5xATR"),
+            "{reason}"
+        );
+
+        // Clears it by a full point
+        let AnalysisResult {
+            suggestion, reason, ..
+        } = super::suggest_action(&with_last(102.0), smas, &strategy, false);
+        assert_eq!(suggestion, "BUY");
+        assert_eq!(reason, "Breakout above recent high");
+    }
 }
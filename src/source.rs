@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use csv::{DeserializeRecordsIntoIter, ReaderBuilder};
+
+use crate::data::{PriceRow, Sample};
+
+/// A (possibly endless) stream of price samples in chronological order.
+/// Implemented by historical sources (CSV, in-memory) and, eventually, live feeds.
+pub trait PriceSource {
+    /// Next sample, `None` once the source is exhausted.
+    fn next_sample(&mut self) -> Option<Result<Sample>>;
+}
+
+/// Reads `timestamp,price` rows from a CSV file, one sample at a time.
+/// Files ending in `.gz` are decompressed on the fly (requires the `gzip` feature).
+pub struct CsvSource {
+    rows: DeserializeRecordsIntoIter<Box<dyn Read>, PriceRow>,
+}
+
+impl CsvSource {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            rows: Self::reader(path)?.into_deserialize(),
+        })
+    }
+
+    /// CSV reader over `path` (decompressed if it ends in `.gz`), headers expected.
+    pub(crate) fn reader(path: &Path) -> Result<csv::Reader<Box<dyn Read>>> {
+        let file =
+            File::open(path).with_context(|| format!("failed to open input file: {:?}", path))?;
+        let reader = Self::maybe_decompress(path, file)?;
+        let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(reader);
+        // Read the header now: a failure here would otherwise just end the row iterator,
+        // so an unreadable file looks like an empty one
+        rdr.headers()
+            .with_context(|| format!("failed to read CSV header: {:?}", path))?;
+        Ok(rdr)
+    }
+}
+
+impl CsvSource {
+    fn is_gzip(path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+    }
+
+    #[cfg(feature = "gzip")]
+    fn maybe_decompress(path: &Path, file: File) -> Result<Box<dyn Read>> {
+        if Self::is_gzip(path) {
+            Ok(Box::new(GzipReader {
+                path: path.to_path_buf(),
+                decoder: flate2::read::GzDecoder::new(file),
+            }))
+        } else {
+            Ok(Box::new(file))
+        }
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    fn maybe_decompress(path: &Path, file: File) -> Result<Box<dyn Read>> {
+        if Self::is_gzip(path) {
+            anyhow::bail!(
+                "{:?} is gzip-compressed; rebuild with `--features gzip` to read it",
+                path
+            );
+        }
+        Ok(Box::new(file))
+    }
+}
+
+/// Gzip decoder that names the file in its errors, which would otherwise surface as a
+/// bare "invalid gzip header" or "corrupt deflate stream" inside a CSV row error.
+#[cfg(feature = "gzip")]
+struct GzipReader {
+    path: std::path::PathBuf,
+    decoder: flate2::read::GzDecoder<File>,
+}
+
+#[cfg(feature = "gzip")]
+impl Read for GzipReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.decoder.read(buf).map_err(|err| {
+            std::io::Error::new(
+                err.kind(),
+                format!("corrupt gzip stream in {:?}: {err}", self.path),
+            )
+        })
+    }
+}
+
+impl PriceSource for CsvSource {
+    fn next_sample(&mut self) -> Option<Result<Sample>> {
+        let row = self.rows.next()?;
+        Some(
+            row.with_context(|| "failed to deserialize CSV row")
+                .and_then(|row| {
+                    let ts = DateTime::parse_from_rfc3339(&row.timestamp)
+                        .with_context(|| format!("failed to parse timestamp: {}", row.timestamp))?
+                        .with_timezone(&Utc);
+                    Ok(Sample {
+                        ts,
+                        price: row.price,
+                        high: row.high,
+                        low: row.low,
+                        volume: row.volume,
+                    })
+                }),
+        )
+    }
+}
+
+/// In-memory source, handy for tests and for replaying already loaded data.
+pub struct VecSource {
+    samples: std::vec::IntoIter<Sample>,
+}
+
+impl VecSource {
+    pub fn new(samples: Vec<Sample>) -> Self {
+        Self {
+            samples: samples.into_iter(),
+        }
+    }
+}
+
+impl PriceSource for VecSource {
+    fn next_sample(&mut self) -> Option<Result<Sample>> {
+        self.samples.next().map(Ok)
+    }
+}
+
+/// Replays samples already in memory, cloning one at a time; lets a sweep run the
+/// same candles through many backtests without copying the whole slice per run.
+pub struct SliceSource<'a> {
+    samples: std::slice::Iter<'a, Sample>,
+}
+
+impl<'a> SliceSource<'a> {
+    pub fn new(samples: &'a [Sample]) -> Self {
+        Self {
+            samples: samples.iter(),
+        }
+    }
+}
+
+impl PriceSource for SliceSource<'_> {
+    fn next_sample(&mut self) -> Option<Result<Sample>> {
+        self.samples.next().cloned().map(Ok)
+    }
+}
+
+/// Drain a source into a vector, stopping at the first error.
+pub fn collect_samples<S: PriceSource + ?Sized>(source: &mut S) -> Result<Vec<Sample>> {
+    let mut samples = Vec::new();
+    while let Some(sample) = source.next_sample() {
+        samples.push(sample?);
+    }
+    Ok(samples)
+}
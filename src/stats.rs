@@ -0,0 +1,244 @@
+/// How `percentile` picks a value when the requested rank falls between two samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PercentileMethod {
+    /// Round `(len - 1) * p` to the nearest index and take that sample.
+    #[default]
+    NearestRank,
+    /// Linearly interpolate between the two samples around `(len - 1) * p`.
+    Linear,
+}
+
+/// Percentile of an ascending-sorted slice.
+/// `p` is a fraction (e.g. 0.4 = 40th percentile) and is clamped to 0.0..=1.0.
+/// Returns None for an empty slice.
+pub fn percentile(sorted_values: &[f64], p: f64, method: PercentileMethod) -> Option<f64> {
+    if sorted_values.is_empty() {
+        return None;
+    }
+
+    let p = p.clamp(0.0, 1.0);
+    let rank = (sorted_values.len() - 1) as f64 * p;
+
+    match method {
+        PercentileMethod::NearestRank => Some(sorted_values[rank.round() as usize]),
+        PercentileMethod::Linear => {
+            let lo = rank.floor() as usize;
+            let hi = rank.ceil() as usize;
+            let weight = rank - lo as f64;
+            Some(sorted_values[lo] + (sorted_values[hi] - sorted_values[lo]) * weight)
+        }
+    }
+}
+
+/// Relative spread below which a window is treated as flat.
+const FLAT_EPSILON: f64 = 1e-12;
+
+/// Standard deviation with `ddof` delta degrees of freedom
+/// (0 = population, 1 = sample).
+/// Returns None when there are not more than `ddof` values, or when all values are
+/// equal within a small relative epsilon, so callers never divide by a degenerate stddev.
+pub fn stddev(values: &[f64], ddof: usize) -> Option<f64> {
+    let n = values.len();
+    if n == 0 || n <= ddof {
+        return None;
+    }
+
+    let (min, max) = values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+    let mean = values.iter().sum::<f64>() / n as f64;
+    if max - min <= FLAT_EPSILON * mean.abs().max(1.0) {
+        return None;
+    }
+
+    let sum_sq: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    Some((sum_sq / (n - ddof) as f64).sqrt())
+}
+
+/// 1-based ranks of `values`; tied values share the average of their ranks.
+pub fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+
+    let mut out = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start;
+        while end + 1 < order.len() && values[order[end + 1]] == values[order[start]] {
+            end += 1;
+        }
+        let avg_rank = (start + end) as f64 / 2.0 + 1.0;
+        for &i in &order[start..=end] {
+            out[i] = avg_rank;
+        }
+        start = end + 1;
+    }
+    out
+}
+
+/// Pearson correlation of two equally long series.
+/// Returns None for mismatched lengths, fewer than 2 pairs, or a flat series.
+pub fn pearson_correlation(x: &[f64], y: &[f64]) -> Option<f64> {
+    if x.len() != y.len() || x.len() < 2 {
+        return None;
+    }
+    let sd_x = stddev(x, 0)?;
+    let sd_y = stddev(y, 0)?;
+
+    let n = x.len() as f64;
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+    let cov = x
+        .iter()
+        .zip(y)
+        .map(|(a, b)| (a - mean_x) * (b - mean_y))
+        .sum::<f64>()
+        / n;
+    Some((cov / (sd_x * sd_y)).clamp(-1.0, 1.0))
+}
+
+/// Spearman rank correlation: the Pearson correlation of the ranks (ties averaged).
+pub fn spearman_correlation(x: &[f64], y: &[f64]) -> Option<f64> {
+    pearson_correlation(&ranks(x), &ranks(y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f64, b: f64, eps: f64) {
+        assert!(
+            (a - b).abs() <= eps,
+            "expected {b}, got {a} (diff = {})",
+            (a - b).abs()
+        );
+    }
+
+    #[test]
+    fn test_percentile_empty_slice_is_none() {
+        assert_eq!(percentile(&[], 0.5, PercentileMethod::NearestRank), None);
+        assert_eq!(percentile(&[], 0.5, PercentileMethod::Linear), None);
+    }
+
+    #[test]
+    fn test_percentile_single_value_is_that_value_for_any_method() {
+        let values = [42.0];
+        for p in [0.0, 0.3, 1.0] {
+            approx_eq(
+                percentile(&values, p, PercentileMethod::NearestRank).unwrap(),
+                42.0,
+                1e-12,
+            );
+            approx_eq(
+                percentile(&values, p, PercentileMethod::Linear).unwrap(),
+                42.0,
+                1e-12,
+            );
+        }
+    }
+
+    #[test]
+    fn test_percentile_nearest_rank_vs_linear_on_small_sample() {
+        // values = [10, 20, 30, 40], len - 1 = 3
+        let values = [10.0, 20.0, 30.0, 40.0];
+
+        // p = 0.5 -> rank 1.5
+        // NearestRank: round(1.5) = 2 -> 30
+        // Linear:      20 + (30 - 20) * 0.5 = 25
+        approx_eq(
+            percentile(&values, 0.5, PercentileMethod::NearestRank).unwrap(),
+            30.0,
+            1e-12,
+        );
+        approx_eq(
+            percentile(&values, 0.5, PercentileMethod::Linear).unwrap(),
+            25.0,
+            1e-12,
+        );
+
+        // p = 0.4 -> rank 1.2
+        // NearestRank: round(1.2) = 1 -> 20
+        // Linear:      20 + (30 - 20) * 0.2 = 22
+        approx_eq(
+            percentile(&values, 0.4, PercentileMethod::NearestRank).unwrap(),
+            20.0,
+            1e-12,
+        );
+        approx_eq(
+            percentile(&values, 0.4, PercentileMethod::Linear).unwrap(),
+            22.0,
+            1e-12,
+        );
+    }
+
+    #[test]
+    fn test_percentile_endpoints_agree_for_both_methods() {
+        let values = [1.0, 5.0, 9.0];
+        for method in [PercentileMethod::NearestRank, PercentileMethod::Linear] {
+            approx_eq(percentile(&values, 0.0, method).unwrap(), 1.0, 1e-12);
+            approx_eq(percentile(&values, 1.0, method).unwrap(), 9.0, 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_percentile_clamps_out_of_range_p() {
+        let values = [1.0, 5.0, 9.0];
+        approx_eq(
+            percentile(&values, -1.0, PercentileMethod::Linear).unwrap(),
+            1.0,
+            1e-12,
+        );
+        approx_eq(
+            percentile(&values, 2.0, PercentileMethod::Linear).unwrap(),
+            9.0,
+            1e-12,
+        );
+    }
+
+    #[test]
+    fn test_stddev_flat_window_is_none() {
+        assert_eq!(stddev(&[100.0; 20], 0), None);
+        assert_eq!(stddev(&[100.0; 20], 1), None);
+        // Float noise far below the epsilon is still "flat"
+        assert_eq!(stddev(&[100.0, 100.0 + 1e-13, 100.0], 0), None);
+    }
+
+    #[test]
+    fn test_stddev_not_enough_values_is_none() {
+        assert_eq!(stddev(&[], 0), None);
+        assert_eq!(stddev(&[1.0], 1), None);
+    }
+
+    #[test]
+    fn test_stddev_known_variance() {
+        // mean = 5, squared deviations sum to 32
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+
+        // Population: sqrt(32 / 8) = 2
+        approx_eq(stddev(&values, 0).unwrap(), 2.0, 1e-12);
+        // Sample: sqrt(32 / 7)
+        approx_eq(stddev(&values, 1).unwrap(), (32.0_f64 / 7.0).sqrt(), 1e-12);
+    }
+
+    #[test]
+    fn test_ranks_average_ties() {
+        assert_eq!(ranks(&[3.0, 1.0, 3.0, 2.0]), vec![3.5, 1.0, 3.5, 2.0]);
+        assert!(ranks(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_spearman_correlation_of_monotonic_series() {
+        let x = [1.0, 2.0, 3.0, 4.0, 5.0];
+        // Monotonic but not linear: rank correlation is still perfect
+        let y = [1.0, 4.0, 9.0, 16.0, 100.0];
+        approx_eq(spearman_correlation(&x, &y).unwrap(), 1.0, 1e-12);
+
+        let reversed: Vec<f64> = y.iter().rev().copied().collect();
+        approx_eq(spearman_correlation(&x, &reversed).unwrap(), -1.0, 1e-12);
+
+        assert_eq!(spearman_correlation(&x, &[2.0; 5]), None);
+        assert_eq!(spearman_correlation(&x, &y[..4]), None);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+/// A fraction shown as a percentage (0.003 -> "0.30%").
+/// Two decimals unless a precision is given (`{:.4}`); the alternate form (`{:#}`)
+/// picks them from the magnitude, see `adaptive_decimals`. Width and `+` are honored.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Pct(pub f64);
+
+/// Most decimals the alternate form of `Pct` uses.
+const MAX_ADAPTIVE_DECIMALS: usize = 6;
+
+/// Decimals for a percentage of this size: two significant digits below 1%
+/// (0.03% -> "0.030%"), two from 1%, one from 10% and none from 100% on.
+pub fn adaptive_decimals(pct: f64) -> usize {
+    let magnitude = pct.abs();
+    if magnitude == 0.0 || !magnitude.is_finite() {
+        2
+    } else if magnitude >= 100.0 {
+        0
+    } else if magnitude >= 10.0 {
+        1
+    } else if magnitude >= 1.0 {
+        2
+    } else {
+        ((-magnitude.log10()).floor() as usize + 2).min(MAX_ADAPTIVE_DECIMALS)
+    }
+}
+
+impl fmt::Display for Pct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pct = self.0 * 100.0;
+        let decimals = match f.precision() {
+            Some(decimals) => decimals,
+            None if f.alternate() => adaptive_decimals(pct),
+            None => 2,
+        };
+        let text = if f.sign_plus() {
+            format!("{pct:+.decimals$}%")
+        } else {
+            format!("{pct:.decimals$}%")
+        };
+        match f.width() {
+            Some(width) => write!(f, "{text:>width$}"),
+            None => f.write_str(&text),
+        }
+    }
+}
+
+/// Basis points (10 bps = 0.10%).
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Bps(pub f64);
+
+impl Bps {
+    /// As a plain fraction, e.g. 10 bps -> 0.001.
+    pub fn as_fraction(self) -> f64 {
+        self.0 / 10_000.0
+    }
+}
+
+impl fmt::Display for Bps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bps", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pct_displays_fraction_as_percent() {
+        assert_eq!(Pct(0.003).to_string(), "0.30%");
+        assert_eq!(Pct(0.02).to_string(), "2.00%");
+        assert_eq!(Pct(0.0).to_string(), "0.00%");
+    }
+
+    #[test]
+    fn test_pct_alternate_form_scales_decimals_with_magnitude() {
+        // A basis-point edge doesn't round away...
+        assert_eq!(format!("{:#}", Pct(0.0003)), "0.030%");
+        assert_eq!(format!("{:#}", Pct(-0.004)), "-0.40%");
+        // ...and a huge return isn't cluttered with decimals
+        assert_eq!(format!("{:#}", Pct(5.0)), "500%");
+        assert_eq!(format!("{:#}", Pct(0.1234)), "12.3%");
+        assert_eq!(format!("{:#}", Pct(0.0)), "0.00%");
+
+        assert_eq!(format!("{:.3}", Pct(0.0003)), "0.030%");
+        assert_eq!(format!("{:+}", Pct(0.02)), "+2.00%");
+        assert_eq!(format!("{:>#8}", Pct(5.0)), "    500%");
+    }
+
+    #[test]
+    fn test_bps_display_and_fraction() {
+        assert_eq!(Bps(10.0).to_string(), "10 bps");
+        assert_eq!(Bps(2.5).to_string(), "2.5 bps");
+        assert!((Bps(10.0).as_fraction() - 0.001).abs() < 1e-15);
+    }
+}